@@ -1,8 +1,12 @@
 // src/app.rs
-use crate::config::{Config, Theme, Language};
+use crate::config::{Config, ProxyConfig, Theme, Language};
 use crate::downloader::{DownloadManager, DownloadTask};
-use crate::bilibili::{BilibiliApi, VideoInfo, QualityInfo};
-use crate::ui::{home::HomePage, download_queue::DownloadQueuePage, settings::SettingsPage, login::LoginWindow, video_detail::VideoDetailWindow};
+use crate::bilibili::{BilibiliApi, VideoInfo, ParsedInput};
+use crate::error::ApiError;
+use crate::server::ApiServer;
+use crate::subscription::SubscriptionManager;
+use crate::ui::{home::HomePage, download_queue::DownloadQueuePage, settings::SettingsPage, login::LoginWindow, video_detail::{VideoDetailWindow, DownloadRequest, DownloadType}};
+use crate::ui::{video_list::{VideoListWindow, BatchDownloadRequest}, favorites::FavoriteFoldersWindow, subscriptions::SubscriptionsPage, history::HistoryPage};
 use eframe::egui;
 use std::sync::Arc;
 use parking_lot::RwLock;
@@ -50,10 +54,18 @@ macro_rules! debug_eprintln {
     ($($arg:tt)*) => {}
 }
 
+/// 输入解析结果：单个视频直接打开详情，视频列表打开批量选择窗口
+enum ParseOutcome {
+    Video(Box<VideoInfo>),
+    List(ParsedInput),
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Page {
     Home,
     DownloadQueue,
+    History,
+    Subscriptions,
     Settings,
 }
 
@@ -65,6 +77,8 @@ pub struct BilibiliDownApp {
     
     home_page: HomePage,
     download_queue_page: DownloadQueuePage,
+    history_page: HistoryPage,
+    subscriptions_page: SubscriptionsPage,
     settings_page: SettingsPage,
     
     show_login_window: bool,
@@ -73,13 +87,16 @@ pub struct BilibiliDownApp {
     show_video_detail: bool,
     video_detail_window: Option<VideoDetailWindow>,
     
+    video_list_window: Option<VideoListWindow>,
+    favorite_folders_window: Option<FavoriteFoldersWindow>,
+    
     user_avatar: Option<egui::TextureHandle>,
     default_avatar: egui::TextureHandle,
     is_logged_in: bool,
     username: Option<String>,
     
     runtime: Arc<tokio::runtime::Runtime>,
-    video_info_receiver: Option<mpsc::Receiver<Result<VideoInfo, String>>>,
+    video_info_receiver: Option<mpsc::Receiver<Result<ParseOutcome, ApiError>>>,
     avatar_receiver: Option<mpsc::Receiver<(Vec<u8>, String)>>,
     error_message: Option<String>,
     loading: bool,
@@ -98,6 +115,12 @@ pub struct BilibiliDownApp {
     parse_dialog_url: Option<String>,
     notification_handler: Option<mpsc::Receiver<String>>,
     
+    api_server: Option<ApiServer>,
+    /// 上次应用的本地接口设置（是否启用、端口），设置变化时重启服务
+    api_server_state: Option<(bool, u16)>,
+    /// 上次应用到接口请求的代理设置
+    api_proxy_state: ProxyConfig,
+    
     #[cfg(target_os = "windows")]
     window_hwnd: Option<HWND>,
 }
@@ -119,15 +142,27 @@ impl BilibiliDownApp {
         #[cfg(not(target_os = "windows"))]
         Self::apply_theme_static(&cc.egui_ctx, &config.read().theme);
         
-        let bilibili_api = Arc::new(BilibiliApi::new(runtime.clone()));
+        let bilibili_api = Arc::new(BilibiliApi::new());
+        let api_proxy_state = config.read().api_proxy.clone();
+        if let Err(_e) = bilibili_api.set_proxy(&api_proxy_state) {
+            debug_eprintln!("设置接口代理失败: {}", _e);
+        }
         
         let download_manager = Arc::new(DownloadManager::new(
             config.read().download_path.clone(),
-            config.read().download_threads,
             runtime.clone(),
             bilibili_api.clone(),
             config.clone(),
         ));
+        download_manager.resume_queue();
+        
+        let subscription_manager = SubscriptionManager::new(
+            runtime.clone(),
+            bilibili_api.clone(),
+            download_manager.clone(),
+            config.clone(),
+        );
+        subscription_manager.start();
         
         let default_avatar = Self::create_default_avatar_texture(cc);
         
@@ -146,11 +181,15 @@ impl BilibiliDownApp {
             bilibili_api: bilibili_api.clone(),
             home_page: HomePage::new(),
             download_queue_page: DownloadQueuePage::new(download_manager.clone()),
-            settings_page: SettingsPage::new(config.clone()),
+            history_page: HistoryPage::new(download_manager.history(), config.clone()),
+            subscriptions_page: SubscriptionsPage::new(subscription_manager, runtime.clone(), config.clone()),
+            settings_page: SettingsPage::new(config.clone(), runtime.clone()),
             show_login_window: false,
             login_window: LoginWindow::new(bilibili_api.clone(), runtime.clone()),
             show_video_detail: false,
             video_detail_window: None,
+            video_list_window: None,
+            favorite_folders_window: None,
             user_avatar: None,
             default_avatar,
             is_logged_in: false,
@@ -171,6 +210,9 @@ impl BilibiliDownApp {
             show_parse_dialog: false,
             parse_dialog_url: None,
             notification_handler: Some(rx),
+            api_server: None,
+            api_server_state: None,
+            api_proxy_state,
             #[cfg(target_os = "windows")]
             window_hwnd,
         };
@@ -539,6 +581,8 @@ impl BilibiliDownApp {
                 match key {
                     "home" => "首页".to_string(),
                     "download_queue" => "下载队列".to_string(),
+                    "history" => "下载历史".to_string(),
+                    "subscriptions" => "订阅".to_string(),
                     "settings" => "设置".to_string(),
                     "login" => "登录".to_string(),
                     "logout" => "退出登录".to_string(),
@@ -546,7 +590,7 @@ impl BilibiliDownApp {
                     "not_logged_in" => "未登录".to_string(),
                     "logged_in_user" => "已登录用户".to_string(),
                     "parse_video" => "B站视频解析".to_string(),
-                    "input_hint" => "请输入视频BV号、视频链接或短链接".to_string(),
+                    "input_hint" => "请输入视频BV号、视频链接、番剧链接或短链接".to_string(),
                     "parse" => "解析".to_string(),
                     "download_video" => "下载视频".to_string(),
                     "download_mp3" => "下载MP3".to_string(),
@@ -560,10 +604,13 @@ impl BilibiliDownApp {
                     "parsing_video" => "正在解析视频信息...".to_string(),
                     "error" => "错误".to_string(),
                     "need_login" => "需要登录才能下载高质量视频".to_string(),
+                    "already_downloaded" => "以下视频已下载过，已跳过（可在下载历史中打开）：".to_string(),
                     "parse_notification_title" => "检测到B站视频链接".to_string(),
                     "parse_notification_body" => "是否解析该视频？".to_string(),
                     "parse_confirm_title" => "视频解析确认".to_string(),
                     "parse_confirm_body" => "检测到B站链接，是否开始解析？".to_string(),
+                    "my_favorites" => "我的收藏夹".to_string(),
+                    "batch_download" => "批量下载".to_string(),
                     _ => key.to_string(),
                 }
             }
@@ -571,6 +618,8 @@ impl BilibiliDownApp {
                 match key {
                     "home" => "Home".to_string(),
                     "download_queue" => "Download Queue".to_string(),
+                    "history" => "History".to_string(),
+                    "subscriptions" => "Subscriptions".to_string(),
                     "settings" => "Settings".to_string(),
                     "login" => "Login".to_string(),
                     "logout" => "Logout".to_string(),
@@ -578,7 +627,7 @@ impl BilibiliDownApp {
                     "not_logged_in" => "Not Logged In".to_string(),
                     "logged_in_user" => "Logged In User".to_string(),
                     "parse_video" => "Bilibili Video Parser".to_string(),
-                    "input_hint" => "Enter BV ID, video link or short link".to_string(),
+                    "input_hint" => "Enter BV ID, video link, bangumi link or short link".to_string(),
                     "parse" => "Parse".to_string(),
                     "download_video" => "Download Video".to_string(),
                     "download_mp3" => "Download MP3".to_string(),
@@ -592,10 +641,13 @@ impl BilibiliDownApp {
                     "parsing_video" => "Parsing video information...".to_string(),
                     "error" => "Error".to_string(),
                     "need_login" => "Login required for high quality video".to_string(),
+                    "already_downloaded" => "Already downloaded, skipped (see History):".to_string(),
                     "parse_notification_title" => "Bilibili link detected".to_string(),
                     "parse_notification_body" => "Parse this video?".to_string(),
                     "parse_confirm_title" => "Video Parse Confirmation".to_string(),
                     "parse_confirm_body" => "Bilibili link detected, start parsing?".to_string(),
+                    "my_favorites" => "My Favorites".to_string(),
+                    "batch_download" => "Batch Download".to_string(),
                     _ => key.to_string(),
                 }
             }
@@ -611,32 +663,115 @@ impl BilibiliDownApp {
         self.loading = true;
         
         self.runtime.spawn(async move {
-            let result = match api.get_video_info(&input).await {
-                Ok(info) => Ok(info),
-                Err(e) => Err(e.to_string()),
+            let result = match api.parse_input(&input).await {
+                Ok(parsed) if parsed.is_list() => Ok(ParseOutcome::List(parsed)),
+                Ok(parsed) => api.get_parsed_video_info(parsed).await
+                    .map(|info| ParseOutcome::Video(Box::new(info))),
+                Err(e) => Err(e),
             };
             let _ = tx.send(result);
         });
     }
     
-    fn start_download(&mut self, video_info: VideoInfo, quality: QualityInfo, download_type: crate::ui::video_detail::DownloadType) {
+    fn open_video_list(&mut self, source: ParsedInput) {
+        self.video_list_window = Some(VideoListWindow::new(
+            source,
+            self.bilibili_api.clone(),
+            self.runtime.clone(),
+            self.config.clone(),
+        ));
+    }
+    
+    fn start_download(&mut self, request: DownloadRequest) {
+        let DownloadRequest { video_info, quality, download_type, pages, codecs, audio_quality, with_danmaku, with_subtitles } = request;
+        
         if !self.is_logged_in && quality.id > 80 {
             self.error_message = Some(self.get_text("need_login"));
             self.show_login_window = true;
             return;
         }
         
-        let task = DownloadTask::new(
-            video_info.bvid.clone(),
-            video_info.title.clone(),
-            video_info.owner.name.clone(),
-            video_info.cover.clone(),
-            quality.id,
-            download_type == crate::ui::video_detail::DownloadType::Mp3,
-            video_info.cid,
-        );
+        // 每个勾选的分P单独建一个任务；单P视频不在文件名中附加分P信息，番剧剧集总是附带
+        let is_multi_part = video_info.pages.len() > 1 || video_info.season.is_some();
+        let is_mp3 = download_type == DownloadType::Mp3;
+        let history = self.download_manager.history();
+        let mut skipped = Vec::new();
+        for page in pages {
+            // 已经以同样或更高画质下载过且文件仍在时跳过
+            let bvid = page.bvid.as_deref().unwrap_or(&video_info.bvid);
+            if history.find_downloaded(bvid, page.cid, is_mp3, quality.id).is_some() {
+                skipped.push(if is_multi_part { format!("P{} {}", page.page, page.part) } else { video_info.title.clone() });
+                continue;
+            }
+            
+            let mut task = DownloadTask::for_page(
+                &video_info,
+                &page,
+                quality.id,
+                is_mp3,
+                is_multi_part,
+            );
+            task.apply_config(&self.config.read());
+            task.codecs = codecs.clone();
+            task.audio_quality = audio_quality;
+            task.danmaku = with_danmaku;
+            task.subtitles = with_subtitles;
+            
+            self.download_manager.add_task(task);
+        }
+        
+        if !skipped.is_empty() {
+            self.error_message = Some(format!("{}\n{}", self.get_text("already_downloaded"), skipped.join("\n")));
+        }
+    }
+    
+    fn start_batch_download(&mut self, request: BatchDownloadRequest) {
+        let BatchDownloadRequest { videos, quality, download_type, with_danmaku, with_subtitles } = request;
         
-        self.download_manager.add_task(task);
+        if !self.is_logged_in && quality > 80 {
+            self.error_message = Some(self.get_text("need_login"));
+            self.show_login_window = true;
+            return;
+        }
+        
+        // 列表中的视频只有基本信息，逐个获取分P和UP主信息后再入队
+        let config = self.config.clone();
+        let api = self.bilibili_api.clone();
+        let download_manager = self.download_manager.clone();
+        let history = download_manager.history();
+        let is_mp3 = download_type == DownloadType::Mp3;
+        self.runtime.spawn(async move {
+            for stub in videos {
+                let video = match api.get_video_basic_info(&stub.bvid).await {
+                    Ok(video) => video,
+                    Err(_e) => {
+                        debug_eprintln!("获取视频信息失败 {}: {}", stub.bvid, _e);
+                        continue;
+                    }
+                };
+                
+                let is_multi_part = video.pages.len() > 1;
+                for page in &video.pages {
+                    if history.find_downloaded(&video.bvid, page.cid, is_mp3, quality).is_some() {
+                        debug_println!("已下载过，跳过: {} P{}", video.bvid, page.page);
+                        continue;
+                    }
+                    
+                    let mut task = DownloadTask::for_page(
+                        &video,
+                        page,
+                        quality,
+                        is_mp3,
+                        is_multi_part,
+                    );
+                    task.apply_config(&config.read());
+                    task.danmaku = with_danmaku;
+                    task.subtitles = with_subtitles;
+                    
+                    download_manager.add_task(task);
+                }
+            }
+        });
     }
     
     fn handle_logout(&mut self, ctx: &egui::Context) {
@@ -659,6 +794,48 @@ impl BilibiliDownApp {
         self.show_login_window = true;
     }
     
+    /// 按设置启动、重启或停止本地HTTP接口
+    fn sync_api_server(&mut self) {
+        let state = {
+            let config = self.config.read();
+            (config.api_enabled, config.api_port)
+        };
+        if self.api_server_state == Some(state) {
+            return;
+        }
+        self.api_server_state = Some(state);
+        
+        // 先停止旧服务，释放端口
+        self.api_server = None;
+        let (enabled, port) = state;
+        if !enabled {
+            return;
+        }
+        
+        match ApiServer::start(
+            port,
+            self.runtime.clone(),
+            self.download_manager.clone(),
+            self.bilibili_api.clone(),
+            self.config.clone(),
+        ) {
+            Ok(server) => self.api_server = Some(server),
+            Err(e) => self.error_message = Some(e),
+        }
+    }
+    
+    /// 代理设置变化时更换接口请求的代理，地址无效时保持原来的设置
+    fn sync_api_proxy(&mut self) {
+        let proxy = self.config.read().api_proxy.clone();
+        if proxy == self.api_proxy_state {
+            return;
+        }
+        if let Err(_e) = self.bilibili_api.set_proxy(&proxy) {
+            debug_eprintln!("设置接口代理失败: {}", _e);
+        }
+        self.api_proxy_state = proxy;
+    }
+    
     fn check_clipboard(&mut self, ctx: &egui::Context) {
         if self.app_started_time.elapsed() < std::time::Duration::from_secs(3) {
             return;
@@ -676,6 +853,7 @@ impl BilibiliDownApp {
             self.last_clipboard_content = contents.clone();
             
             if (contents.contains("bilibili.com/video/") || 
+                contents.contains("bilibili.com/bangumi/") || 
                 contents.contains("b23.tv/") || 
                 (contents.starts_with("BV") && contents.len() >= 10)) &&
                !self.notification_shown_for.contains(&contents) {
@@ -694,6 +872,7 @@ impl BilibiliDownApp {
     }
     
     #[allow(dead_code)]
+    #[cfg_attr(not(target_os = "windows"), allow(unused_variables))]
     fn show_interactive_notification(&self, url: String) {
         #[cfg(target_os = "windows")]
         {
@@ -820,16 +999,17 @@ impl BilibiliDownApp {
 impl eframe::App for BilibiliDownApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         self.check_clipboard(ctx);
+        self.sync_api_server();
+        self.sync_api_proxy();
         
         // 处理通知点击事件
         if let Some(ref receiver) = self.notification_handler {
             while let Ok(url) = receiver.try_recv() {
                 debug_println!("收到通知点击事件，URL: {}", url);
                 
-                let clean_url = if url.starts_with("parseurl:") {
-                    url[9..].to_string()
-                } else {
-                    url
+                let clean_url = match url.strip_prefix("parseurl:") {
+                    Some(rest) => rest.to_string(),
+                    None => url,
                 };
                 
                 debug_println!("清理后的URL: {}", clean_url);
@@ -895,10 +1075,15 @@ impl eframe::App for BilibiliDownApp {
             if let Ok(result) = receiver.try_recv() {
                 self.loading = false;
                 match result {
-                    Ok(video_info) => {
+                    Ok(ParseOutcome::List(source)) => {
+                        debug_println!("解析到视频列表: {:?}", source);
+                        self.open_video_list(source);
+                        self.error_message = None;
+                    }
+                    Ok(ParseOutcome::Video(video_info)) => {
                         debug_println!("视频解析成功: {}", video_info.title);
                         self.video_detail_window = Some(VideoDetailWindow::new(
-                            video_info,
+                            *video_info,
                             self.bilibili_api.clone(),
                             self.runtime.clone(),
                             self.config.clone()
@@ -908,7 +1093,11 @@ impl eframe::App for BilibiliDownApp {
                     }
                     Err(err) => {
                         debug_eprintln!("视频解析失败: {}", err);
-                        self.error_message = Some(err);
+                        // 需要登录时直接打开登录窗口
+                        if err == ApiError::NotLoggedIn {
+                            self.show_login_window = true;
+                        }
+                        self.error_message = Some(err.localized(&self.config.read().language));
                     }
                 }
                 self.video_info_receiver = None;
@@ -943,10 +1132,14 @@ impl eframe::App for BilibiliDownApp {
             ui.horizontal(|ui| {
                 let home_text = self.get_text("home");
                 let queue_text = self.get_text("download_queue");
+                let history_text = self.get_text("history");
+                let subscriptions_text = self.get_text("subscriptions");
                 let settings_text = self.get_text("settings");
                 
                 ui.selectable_value(&mut self.current_page, Page::Home, home_text);
                 ui.selectable_value(&mut self.current_page, Page::DownloadQueue, queue_text);
+                ui.selectable_value(&mut self.current_page, Page::History, history_text);
+                ui.selectable_value(&mut self.current_page, Page::Subscriptions, subscriptions_text);
                 ui.selectable_value(&mut self.current_page, Page::Settings, settings_text);
                 
                 ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
@@ -997,6 +1190,15 @@ impl eframe::App for BilibiliDownApp {
                                     ui.separator();
                                 }
                                 
+                                if ui.button(self.get_text("my_favorites")).clicked() {
+                                    self.favorite_folders_window = Some(FavoriteFoldersWindow::new(
+                                        self.bilibili_api.clone(),
+                                        self.runtime.clone(),
+                                        self.config.clone(),
+                                    ));
+                                    close_menu = true;
+                                }
+                                
                                 if ui.button(self.get_text("relogin")).clicked() {
                                     self.handle_relogin();
                                     close_menu = true;
@@ -1066,10 +1268,26 @@ impl eframe::App for BilibiliDownApp {
                         let delete_text = self.get_text("delete");
                         
                         self.download_queue_page.show_with_texts(ui, &pause_text, &resume_text, &delete_text);
+                        if self.download_queue_page.take_login_request() {
+                            self.show_login_window = true;
+                        }
+                    }
+                    Page::History => {
+                        if let Some(url) = self.history_page.show(ui) {
+                            self.parse_video(url);
+                        }
+                    }
+                    Page::Subscriptions => {
+                        self.subscriptions_page.show(ui);
                     }
                     Page::Settings => {
                         let settings_text = self.get_text("settings");
-                        if self.settings_page.show_with_text(ui, &settings_text) {
+                        // 设置项较多，放在滚动区域中
+                        let theme_changed = egui::ScrollArea::vertical()
+                            .id_salt("settings_scroll")
+                            .show(ui, |ui| self.settings_page.show_with_text(ui, &settings_text))
+                            .inner;
+                        if theme_changed {
                             let new_theme = self.config.read().theme.clone();
                             self.apply_theme(ctx, &new_theme);
                         }
@@ -1130,15 +1348,15 @@ impl eframe::App for BilibiliDownApp {
                     .default_size([700.0, 500.0])
                     .open(&mut self.show_video_detail)
                     .show(ctx, |ui| {
-                        if let Some((video_info, quality, download_type)) = 
+                        if let Some(request) = 
                             window.show_with_texts(ui, &download_video_text, &download_mp3_text, &cancel_text) {
-                            download_request = Some((video_info, quality, download_type));
+                            download_request = Some(request);
                             close_window = true;
                         }
                     });
                 
-                if let Some((video_info, quality, download_type)) = download_request {
-                    self.start_download(video_info, quality, download_type);
+                if let Some(request) = download_request {
+                    self.start_download(request);
                     self.current_page = Page::DownloadQueue;
                 }
                 
@@ -1151,6 +1369,63 @@ impl eframe::App for BilibiliDownApp {
             }
         }
         
+        // 收藏夹选择窗口
+        let favorites_title = self.get_text("my_favorites");
+        if let Some(window) = &mut self.favorite_folders_window {
+            let mut open = true;
+            let mut selected_folder = None;
+            
+            egui::Window::new(favorites_title)
+                .collapsible(false)
+                .resizable(false)
+                .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+                .default_size([360.0, 400.0])
+                .open(&mut open)
+                .show(ctx, |ui| {
+                    selected_folder = window.show(ui);
+                });
+            
+            if let Some(media_id) = selected_folder {
+                self.favorite_folders_window = None;
+                self.open_video_list(ParsedInput::Favorite(media_id));
+            } else if !open {
+                self.favorite_folders_window = None;
+            }
+        }
+        
+        // 批量下载窗口
+        if self.video_list_window.is_some() {
+            let download_video_text = self.get_text("download_video");
+            let download_mp3_text = self.get_text("download_mp3");
+            let cancel_text = self.get_text("cancel");
+            let batch_title = self.get_text("batch_download");
+            
+            let mut open = true;
+            let mut batch_request = None;
+            let mut closed = false;
+            
+            if let Some(window) = &mut self.video_list_window {
+                egui::Window::new(batch_title)
+                    .collapsible(false)
+                    .resizable(true)
+                    .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+                    .default_size([700.0, 500.0])
+                    .open(&mut open)
+                    .show(ctx, |ui| {
+                        batch_request = window.show_with_texts(ui, &download_video_text, &download_mp3_text, &cancel_text);
+                    });
+                closed = window.is_closed();
+            }
+            
+            if let Some(request) = batch_request {
+                self.video_list_window = None;
+                self.start_batch_download(request);
+                self.current_page = Page::DownloadQueue;
+            } else if !open || closed {
+                self.video_list_window = None;
+            }
+        }
+        
         ctx.request_repaint_after(std::time::Duration::from_millis(500));
     }
 }
@@ -0,0 +1,513 @@
+// backend/aria2.rs
+// 通过RPC控制外部aria2c进程下载
+use super::{DownloadBackend, JobState, JobStatus, DOWNLOAD_HEADERS};
+use crate::config::Config;
+use aria2_ws::response::TaskStatus;
+use aria2_ws::{Client as Aria2Client, TaskOptions};
+use futures::future::BoxFuture;
+use parking_lot::{Mutex, RwLock};
+use serde_json::json;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::runtime::Runtime;
+
+#[cfg(target_os = "windows")]
+use std::os::windows::process::CommandExt;
+
+#[cfg(target_os = "windows")]
+const CREATE_NO_WINDOW: u32 = 0x08000000;
+
+#[cfg(target_os = "windows")]
+use winapi::{
+    um::{
+        tlhelp32::{CreateToolhelp32Snapshot, Process32First, Process32Next, PROCESSENTRY32, TH32CS_SNAPPROCESS},
+        processthreadsapi::{OpenProcess, TerminateProcess},
+        handleapi::CloseHandle,
+        winnt::PROCESS_TERMINATE,
+    },
+    shared::minwindef::DWORD,
+};
+
+#[cfg(target_os = "windows")]
+use std::os::windows::ffi::OsStringExt;
+
+#[cfg(debug_assertions)]
+macro_rules! debug_println {
+    ($($arg:tt)*) => { println!($($arg)*) }
+}
+#[cfg(not(debug_assertions))]
+macro_rules! debug_println {
+    ($($arg:tt)*) => {}
+}
+
+#[cfg(debug_assertions)]
+macro_rules! debug_eprintln {
+    ($($arg:tt)*) => { eprintln!($($arg)*) }
+}
+#[cfg(not(debug_assertions))]
+macro_rules! debug_eprintln {
+    ($($arg:tt)*) => {}
+}
+
+/// 外部aria2的默认RPC地址
+pub const DEFAULT_RPC_URL: &str = "ws://127.0.0.1:6800/jsonrpc";
+/// 自动启动aria2c时默认使用的RPC端口
+pub const DEFAULT_RPC_PORT: u16 = 6800;
+/// 创建RPC客户端失败后最长的重试间隔
+const RECONNECT_MAX_DELAY_SECS: u64 = 30;
+/// 开始下载前确认aria2可用的次数
+const READY_RETRIES: u32 = 10;
+
+pub struct Aria2Backend {
+    client: Arc<RwLock<Option<Aria2Client>>>,
+    /// 自动启动的aria2c进程，连接外部aria2时为None
+    process: Mutex<Option<Child>>,
+    /// 新添加的下载使用的限速（字节/秒）
+    download_limit: AtomicU64,
+    /// 新添加的下载使用的HTTP代理
+    proxy: RwLock<Option<String>>,
+    /// 是否修改过外部aria2的整体限速
+    overall_limited: AtomicBool,
+}
+
+impl Aria2Backend {
+    /// 是否找到了aria2c可执行文件
+    pub fn is_available() -> bool {
+        let available = Self::get_aria2_path().exists();
+        if !available {
+            debug_eprintln!("警告: aria2c 未找到，使用内置下载器");
+            debug_eprintln!("如需使用aria2，请下载 aria2c 并放置到: {:?}", Self::get_aria2_path());
+        }
+        available
+    }
+    
+    /// 启动aria2c并在后台连接RPC
+    pub fn start(config: &Config, runtime: &Arc<Runtime>, download_path: &Path) -> Self {
+        let port = Self::pick_port(config.aria2_rpc_port);
+        // 自己启动的aria2c不需要固定密钥，每次随机生成
+        let secret = if config.aria2_secret.is_empty() {
+            uuid::Uuid::new_v4().simple().to_string()
+        } else {
+            config.aria2_secret.clone()
+        };
+        
+        let backend = Self {
+            client: Arc::new(RwLock::new(None)),
+            process: Mutex::new(Self::start_process(download_path, port, &secret)),
+            download_limit: AtomicU64::new(0),
+            proxy: RwLock::new(None),
+            overall_limited: AtomicBool::new(false),
+        };
+        backend.connect(runtime, format!("ws://127.0.0.1:{}/jsonrpc", port), secret);
+        backend
+    }
+    
+    /// 连接已经运行的aria2，不启动也不结束任何进程
+    pub fn external(config: &Config, runtime: &Arc<Runtime>) -> Self {
+        let backend = Self {
+            client: Arc::new(RwLock::new(None)),
+            process: Mutex::new(None),
+            download_limit: AtomicU64::new(0),
+            proxy: RwLock::new(None),
+            overall_limited: AtomicBool::new(false),
+        };
+        backend.connect(runtime, normalize_rpc_url(&config.aria2_rpc_url), config.aria2_secret.clone());
+        backend
+    }
+    
+    fn current_client(&self) -> Option<Aria2Client> {
+        let guard = self.client.read();
+        guard.as_ref().cloned()
+    }
+    
+    /// 优先使用设置的端口，被占用时由系统分配一个空闲端口
+    fn pick_port(preferred: u16) -> u16 {
+        if std::net::TcpListener::bind(("127.0.0.1", preferred)).is_ok() {
+            return preferred;
+        }
+        let port = std::net::TcpListener::bind(("127.0.0.1", 0))
+            .and_then(|listener| listener.local_addr())
+            .map(|addr| addr.port())
+            .unwrap_or(preferred);
+        debug_println!("端口 {} 已被占用，aria2使用端口 {}", preferred, port);
+        port
+    }
+    
+    fn pid_path() -> PathBuf {
+        Config::data_file("aria2.pid")
+    }
+    
+    /// 结束上次运行时启动、没有正常退出的aria2c，其他aria2c进程不受影响
+    fn kill_previous_process() {
+        let pid_path = Self::pid_path();
+        let pid = std::fs::read_to_string(&pid_path).ok().and_then(|pid| pid.trim().parse().ok());
+        let _ = std::fs::remove_file(&pid_path);
+        if let Some(pid) = pid {
+            if Self::kill_process(pid) {
+                std::thread::sleep(std::time::Duration::from_millis(500));
+            }
+        }
+    }
+    
+    /// 进程仍是aria2c时结束它，PID可能已被其他程序重用
+    #[cfg(target_os = "windows")]
+    fn kill_process(pid: u32) -> bool {
+        let mut killed = false;
+        unsafe {
+            let snapshot = CreateToolhelp32Snapshot(TH32CS_SNAPPROCESS, 0);
+            if snapshot == winapi::um::handleapi::INVALID_HANDLE_VALUE {
+                return false;
+            }
+            
+            let mut process_entry: PROCESSENTRY32 = std::mem::zeroed();
+            process_entry.dwSize = std::mem::size_of::<PROCESSENTRY32>() as DWORD;
+            
+            if Process32First(snapshot, &mut process_entry) != 0 {
+                loop {
+                    if process_entry.th32ProcessID == pid {
+                        let exe_name_wide: Vec<u16> = process_entry.szExeFile
+                            .iter()
+                            .take_while(|&&c| c != 0)
+                            .map(|&c| c as u8 as u16)
+                            .collect();
+                        
+                        let exe_name = std::ffi::OsString::from_wide(&exe_name_wide);
+                        
+                        if exe_name.to_str().is_some_and(|name| name.eq_ignore_ascii_case("aria2c.exe")) {
+                            let process_handle = OpenProcess(PROCESS_TERMINATE, 0, pid);
+                            if !process_handle.is_null() {
+                                killed = TerminateProcess(process_handle, 101) != 0;
+                                CloseHandle(process_handle);
+                                debug_println!("终止上次启动的aria2c (PID: {})", pid);
+                            }
+                        }
+                        break;
+                    }
+                    
+                    if Process32Next(snapshot, &mut process_entry) == 0 {
+                        break;
+                    }
+                }
+            }
+            
+            CloseHandle(snapshot);
+        }
+        killed
+    }
+    
+    #[cfg(not(target_os = "windows"))]
+    fn kill_process(pid: u32) -> bool {
+        let is_aria2 = Command::new("ps")
+            .args(["-p", &pid.to_string(), "-o", "comm="])
+            .output()
+            .map(|output| {
+                let name = String::from_utf8_lossy(&output.stdout).trim().to_string();
+                Path::new(&name).file_name().is_some_and(|name| name == "aria2c")
+            })
+            .unwrap_or(false);
+        if !is_aria2 {
+            return false;
+        }
+        debug_println!("终止上次启动的aria2c (PID: {})", pid);
+        Command::new("kill").arg(pid.to_string()).status().is_ok_and(|status| status.success())
+    }
+    
+    fn start_process(download_path: &Path, port: u16, secret: &str) -> Option<Child> {
+        let aria2_path = Self::get_aria2_path();
+        
+        Self::kill_previous_process();
+        
+        // aria2会话文件保存未完成的下载，重启后GID保持不变
+        let session_path = Config::data_file("aria2.session");
+        
+        let mut cmd = Command::new(&aria2_path);
+        if session_path.exists() {
+            cmd.arg("--input-file").arg(&session_path);
+        }
+        cmd.arg("--save-session").arg(&session_path)
+            .arg("--save-session-interval=10")
+            .arg("--enable-rpc")
+            .arg("--rpc-listen-all=false")
+            .arg(format!("--rpc-listen-port={}", port))
+            .arg(format!("--rpc-secret={}", secret))
+            .arg("--rpc-allow-origin-all")
+            .arg("--continue=true")
+            .arg("--max-connection-per-server=16")
+            .arg("--split=16")
+            .arg("--min-split-size=1M")
+            .arg("--piece-length=1M")
+            .arg("--allow-piece-length-change=true")
+            .arg("--dir")
+            .arg(download_path)
+            .arg("--check-certificate=false")
+            .arg("--user-agent=Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36");
+        
+        #[cfg(target_os = "windows")]
+        {
+            cmd.creation_flags(CREATE_NO_WINDOW);
+        }
+        
+        match cmd.spawn() {
+            Ok(child) => {
+                debug_println!("aria2c 启动成功，PID: {:?}，端口: {}", child.id(), port);
+                let _ = std::fs::write(Self::pid_path(), child.id().to_string());
+                std::thread::sleep(std::time::Duration::from_secs(2));
+                Some(child)
+            }
+            Err(_e) => {
+                debug_eprintln!("无法启动 aria2c: {}", _e);
+                None
+            }
+        }
+    }
+    
+    /// 在后台创建RPC客户端，失败时按退避时间一直重试。
+    /// 客户端创建后连接断开（例如外部aria2重启）时会自动重连
+    fn connect(&self, runtime: &Runtime, url: String, secret: String) {
+        let aria2_client = self.client.clone();
+        
+        runtime.spawn(async move {
+            let mut delay = 1;
+            let token = Some(secret.as_str()).filter(|secret| !secret.is_empty());
+            
+            loop {
+                match Aria2Client::connect(&url, token).await {
+                    Ok(client) => {
+                        debug_println!("成功连接到 aria2 RPC: {}", url);
+                        *aria2_client.write() = Some(client);
+                        break;
+                    }
+                    Err(_e) => {
+                        debug_eprintln!("连接aria2失败，{}秒后重试: {}", delay, _e);
+                        tokio::time::sleep(tokio::time::Duration::from_secs(delay)).await;
+                        delay = (delay * 2).min(RECONNECT_MAX_DELAY_SECS);
+                    }
+                }
+            }
+        });
+    }
+    
+    fn get_aria2_path() -> PathBuf {
+        let exe_dir = std::env::current_exe()
+            .unwrap_or_else(|_| PathBuf::from("."))
+            .parent()
+            .unwrap_or(&PathBuf::from("."))
+            .to_path_buf();
+        
+        #[cfg(target_os = "windows")]
+        let aria2_name = "aria2c.exe";
+        #[cfg(not(target_os = "windows"))]
+        let aria2_name = "aria2c";
+        
+        // 先检查 tools 目录
+        let tools_path = exe_dir.join("tools").join(aria2_name);
+        if tools_path.exists() {
+            return tools_path;
+        }
+        
+        // 再检查可执行文件旁边
+        let beside_path = exe_dir.join(aria2_name);
+        if beside_path.exists() {
+            return beside_path;
+        }
+        
+        // 非 Windows 平台：尝试从 PATH 中查找
+        #[cfg(not(target_os = "windows"))]
+        {
+            if let Ok(output) = Command::new("which").arg("aria2c").output() {
+                if output.status.success() {
+                    let path = String::from_utf8_lossy(&output.stdout).trim().to_string();
+                    if !path.is_empty() {
+                        return PathBuf::from(path);
+                    }
+                }
+            }
+        }
+        
+        // 回退到 tools 目录路径
+        tools_path
+    }
+}
+
+impl DownloadBackend for Aria2Backend {
+    /// 确认aria2可以响应请求。连接断开时客户端会自动重连，这里等待重连完成
+    fn wait_ready(&self) -> BoxFuture<'_, bool> {
+        Box::pin(async move {
+            for _ in 0..READY_RETRIES {
+                if let Some(client) = self.current_client() {
+                    let ping = tokio::time::timeout(tokio::time::Duration::from_secs(2), client.get_version()).await;
+                    if matches!(ping, Ok(Ok(_))) {
+                        return true;
+                    }
+                }
+                tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+            }
+            debug_eprintln!("无法连接到 aria2 RPC");
+            false
+        })
+    }
+    
+    fn add(&self, urls: Vec<String>, output: PathBuf, overwrite: bool) -> BoxFuture<'_, Result<String, String>> {
+        Box::pin(async move {
+            let client = self.current_client().ok_or("aria2客户端未连接")?;
+            let mut extra_options = json!({
+                "min-split-size": "1M",
+                "piece-length": "1M",
+                "allow-piece-length-change": "true",
+                "check-certificate": "false",
+                "auto-file-renaming": "false",
+                "continue": "true",
+            }).as_object().unwrap().clone();
+            // 只有冲突策略为覆盖时才允许aria2覆盖已存在的文件
+            if overwrite {
+                extra_options.insert("allow-overwrite".to_string(), json!("true"));
+            }
+            let options = TaskOptions {
+                dir: output.parent().map(|dir| dir.to_string_lossy().to_string()),
+                out: output.file_name().map(|name| name.to_string_lossy().to_string()),
+                header: Some(DOWNLOAD_HEADERS.iter().map(|(key, value)| format!("{}: {}", key, value)).collect()),
+                split: Some(16),
+                max_connection_per_server: Some(16),
+                max_download_limit: Some(self.download_limit.load(Ordering::Relaxed).to_string()),
+                all_proxy: self.proxy.read().clone(),
+                extra_options,
+                ..Default::default()
+            };
+            client.add_uri(urls, Some(options), None, None).await.map_err(|e| e.to_string())
+        })
+    }
+    
+    fn status(&self, id: String) -> BoxFuture<'_, Option<JobStatus>> {
+        Box::pin(async move {
+            let status = self.current_client()?.tell_status(id).await.ok()?;
+            let state = match status.status {
+                TaskStatus::Active => JobState::Active,
+                TaskStatus::Waiting => JobState::Waiting,
+                TaskStatus::Paused => JobState::Paused,
+                TaskStatus::Complete => JobState::Complete,
+                TaskStatus::Removed => JobState::Removed,
+                TaskStatus::Error => JobState::Error(status.error_message.unwrap_or_else(|| "下载失败".to_string())),
+            };
+            Some(JobStatus {
+                state,
+                total: status.total_length,
+                completed: status.completed_length,
+                speed: status.download_speed,
+            })
+        })
+    }
+    
+    fn pause(&self, id: String) -> BoxFuture<'_, ()> {
+        Box::pin(async move {
+            if let Some(client) = self.current_client() {
+                let _ = client.pause(id).await;
+            }
+        })
+    }
+    
+    fn unpause(&self, id: String) -> BoxFuture<'_, ()> {
+        Box::pin(async move {
+            if let Some(client) = self.current_client() {
+                let _ = client.unpause(id).await;
+            }
+        })
+    }
+    
+    fn remove(&self, id: String) -> BoxFuture<'_, ()> {
+        Box::pin(async move {
+            if let Some(client) = self.current_client() {
+                let _ = client.force_remove(id.clone()).await;
+                let _ = client.remove_download_result(id).await;
+            }
+        })
+    }
+    
+    fn set_speed_limits(&self, overall: u64, per_download: u64, ids: Vec<String>) -> BoxFuture<'_, bool> {
+        Box::pin(async move {
+            self.download_limit.store(per_download, Ordering::Relaxed);
+            let Some(client) = self.current_client() else {
+                return false;
+            };
+            
+            // 外部aria2可能还有其他程序在用：单个下载的限速在添加时指定，
+            // 整体限速只在设置过时修改，不把别人设置的限速改成不限速
+            let was_limited = self.overall_limited.swap(overall > 0, Ordering::Relaxed);
+            let global = if self.process.lock().is_some() {
+                Some(json!({
+                    "max-download-limit": per_download.to_string(),
+                    "max-overall-download-limit": overall.to_string(),
+                }))
+            } else if overall > 0 || was_limited {
+                Some(json!({ "max-overall-download-limit": overall.to_string() }))
+            } else {
+                None
+            };
+            if let Some(global) = global {
+                let options = TaskOptions {
+                    extra_options: global.as_object().unwrap().clone(),
+                    ..Default::default()
+                };
+                if client.change_global_option(options).await.is_err() {
+                    return false;
+                }
+            }
+            
+            // 已添加的下载不受全局选项影响，需要逐个修改
+            for gid in ids {
+                let options = TaskOptions {
+                    max_download_limit: Some(per_download.to_string()),
+                    ..Default::default()
+                };
+                let _ = client.change_option(gid, options).await;
+            }
+            true
+        })
+    }
+    
+    fn set_proxy(&self, proxy: Option<String>) -> Result<(), String> {
+        if proxy.as_deref().is_some_and(|proxy| proxy.starts_with("socks")) {
+            return Err("aria2不支持SOCKS代理，请使用内置下载器".to_string());
+        }
+        *self.proxy.write() = proxy;
+        Ok(())
+    }
+    
+    fn control_file(&self, output: &Path) -> PathBuf {
+        let mut control_file = output.to_path_buf().into_os_string();
+        control_file.push(".aria2");
+        PathBuf::from(control_file)
+    }
+    
+    fn save_state(&self) -> BoxFuture<'_, ()> {
+        Box::pin(async move {
+            if let Some(client) = self.current_client() {
+                let _ = client.save_session().await;
+            }
+        })
+    }
+    
+    fn stop(&self) {
+        if let Some(mut child) = self.process.lock().take() {
+            let _ = child.kill();
+            let _ = std::fs::remove_file(Self::pid_path());
+        }
+    }
+}
+
+/// 支持http(s)://和ws(s)://地址，省略协议时使用ws://，省略路径时使用/jsonrpc
+fn normalize_rpc_url(url: &str) -> String {
+    let url = url.trim();
+    let (scheme, rest) = match url.split_once("://") {
+        Some(("http", rest)) => ("ws", rest),
+        Some(("https", rest)) => ("wss", rest),
+        Some((scheme, rest)) => (scheme, rest),
+        None => ("ws", url),
+    };
+    if rest.contains('/') {
+        format!("{}://{}", scheme, rest)
+    } else {
+        format!("{}://{}/jsonrpc", scheme, rest)
+    }
+}
@@ -0,0 +1,96 @@
+// backend/mod.rs
+// 下载后端：外部aria2或内置的分段下载器，下载管理器只通过DownloadBackend使用它们
+pub mod aria2;
+pub mod native;
+
+use crate::config::{Config, DownloadBackendKind};
+use futures::future::BoxFuture;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::runtime::Runtime;
+
+pub use aria2::Aria2Backend;
+pub use native::NativeBackend;
+
+/// 下载B站CDN资源需要的请求头
+pub const DOWNLOAD_HEADERS: [(&str, &str); 5] = [
+    ("Referer", "https://www.bilibili.com"),
+    ("User-Agent", "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36"),
+    ("Accept", "*/*"),
+    ("Accept-Language", "zh-CN,zh;q=0.9,en;q=0.8"),
+    ("Origin", "https://www.bilibili.com"),
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum JobState {
+    Active,
+    Waiting,
+    Paused,
+    Complete,
+    Error(String),
+    Removed,
+}
+
+/// 单个文件的下载状态
+#[derive(Debug, Clone)]
+pub struct JobStatus {
+    pub state: JobState,
+    /// 文件大小（字节），未知时为0
+    pub total: u64,
+    pub completed: u64,
+    /// 下载速度（字节/秒）
+    pub speed: u64,
+}
+
+/// 下载后端。每个文件对应一个下载任务，用返回的ID查询和控制
+pub trait DownloadBackend: Send + Sync {
+    /// 等待后端可用，不可用时返回false
+    fn wait_ready(&self) -> BoxFuture<'_, bool>;
+
+    /// 添加下载，urls是同一文件的多个镜像地址。
+    /// overwrite为false时不覆盖已存在的文件，只从已有内容继续下载
+    fn add(&self, urls: Vec<String>, output: PathBuf, overwrite: bool) -> BoxFuture<'_, Result<String, String>>;
+
+    /// 查询下载状态，任务不存在时返回None
+    fn status(&self, id: String) -> BoxFuture<'_, Option<JobStatus>>;
+
+    fn pause(&self, id: String) -> BoxFuture<'_, ()>;
+
+    fn unpause(&self, id: String) -> BoxFuture<'_, ()>;
+
+    /// 停止并移除任务，不删除已下载的文件
+    fn remove(&self, id: String) -> BoxFuture<'_, ()>;
+
+    /// 设置整体限速和单个下载的限速（字节/秒），0表示不限速。
+    /// 对ids中正在下载的任务立即生效，不修改其他程序添加到同一个aria2的下载。后端未就绪时返回false
+    fn set_speed_limits(&self, overall: u64, per_download: u64, ids: Vec<String>) -> BoxFuture<'_, bool>;
+    
+    /// 更换下载使用的代理，None表示直接连接。对之后添加或继续的下载生效
+    fn set_proxy(&self, proxy: Option<String>) -> Result<(), String>;
+    
+    /// 断点续传用的控制文件，取消任务时一并删除
+    fn control_file(&self, output: &Path) -> PathBuf;
+
+    /// 保存未完成任务的进度，下次启动时继续
+    fn save_state(&self) -> BoxFuture<'_, ()>;
+
+    /// 程序退出时停止所有下载
+    fn stop(&self);
+}
+
+/// 按设置创建下载后端。没有外部aria2且找不到aria2c，或使用SOCKS代理时使用内置下载器
+pub fn create(config: &Config, runtime: &Arc<Runtime>, download_path: &Path) -> Arc<dyn DownloadBackend> {
+    // aria2只支持HTTP代理
+    if config.media_proxy.is_socks() {
+        return Arc::new(NativeBackend::new(config.download_threads));
+    }
+    match config.download_backend {
+        DownloadBackendKind::Aria2 if config.aria2_external => {
+            Arc::new(Aria2Backend::external(config, runtime))
+        }
+        DownloadBackendKind::Aria2 if Aria2Backend::is_available() => {
+            Arc::new(Aria2Backend::start(config, runtime, download_path))
+        }
+        _ => Arc::new(NativeBackend::new(config.download_threads)),
+    }
+}
@@ -0,0 +1,563 @@
+// backend/native.rs
+// 内置的多连接分段下载器，不依赖aria2c。
+// 进度保存在输出文件旁的.bdstate文件中，暂停、重试或重启后从断点继续
+use super::{DownloadBackend, JobState, JobStatus, DOWNLOAD_HEADERS};
+use crate::rate_limit::RateLimiter;
+use futures::future::BoxFuture;
+use futures::StreamExt;
+use parking_lot::{Mutex, RwLock};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::SeekFrom;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncSeekExt, AsyncWriteExt};
+use tokio::task::{JoinHandle, JoinSet};
+
+#[cfg(debug_assertions)]
+macro_rules! debug_eprintln {
+    ($($arg:tt)*) => { eprintln!($($arg)*) }
+}
+#[cfg(not(debug_assertions))]
+macro_rules! debug_eprintln {
+    ($($arg:tt)*) => {}
+}
+
+const STATE_SUFFIX: &str = ".bdstate";
+/// 每个分段至少1MB，小文件使用更少的连接
+const MIN_SEGMENT_SIZE: u64 = 1024 * 1024;
+/// 分段连续失败的重试次数，收到数据后重新计算
+const SEGMENT_RETRIES: u32 = 5;
+const MAX_CONNECTIONS: u32 = 32;
+
+/// 保存在.bdstate中的进度
+#[derive(Serialize, Deserialize)]
+struct SavedState {
+    total: u64,
+    segments: Vec<SavedSegment>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SavedSegment {
+    start: u64,
+    /// 分段最后一个字节的位置（包含）
+    end: u64,
+    downloaded: u64,
+}
+
+struct Segment {
+    start: u64,
+    end: u64,
+    downloaded: AtomicU64,
+}
+
+impl Segment {
+    fn len(&self) -> u64 {
+        self.end + 1 - self.start
+    }
+
+    fn is_done(&self) -> bool {
+        self.downloaded.load(Ordering::Relaxed) >= self.len()
+    }
+}
+
+struct SpeedSample {
+    time: Instant,
+    completed: u64,
+    speed: u64,
+}
+
+struct Job {
+    urls: Vec<String>,
+    output: PathBuf,
+    state: RwLock<JobState>,
+    total: AtomicU64,
+    segments: RwLock<Vec<Arc<Segment>>>,
+    handle: Mutex<Option<JoinHandle<()>>>,
+    sample: Mutex<SpeedSample>,
+    limiter: Arc<RateLimiter>,
+}
+
+impl Job {
+    fn completed(&self) -> u64 {
+        self.segments.read().iter().map(|s| s.downloaded.load(Ordering::Relaxed)).sum()
+    }
+
+    fn state_path(&self) -> PathBuf {
+        state_path(&self.output)
+    }
+
+    /// .bdstate的内容，还没有分段时为None
+    fn saved_state(&self) -> Option<String> {
+        let segments = self.segments.read();
+        if segments.is_empty() {
+            return None;
+        }
+        let state = SavedState {
+            total: self.total.load(Ordering::Relaxed),
+            segments: segments
+                .iter()
+                .map(|s| SavedSegment {
+                    start: s.start,
+                    end: s.end,
+                    downloaded: s.downloaded.load(Ordering::Relaxed),
+                })
+                .collect(),
+        };
+        serde_json::to_string(&state).ok()
+    }
+    
+    async fn save_state(&self) {
+        if let Some(content) = self.saved_state() {
+            let _ = tokio::fs::write(self.state_path(), content).await;
+        }
+    }
+    
+    /// 程序退出时在运行时之外保存
+    fn save_state_blocking(&self) {
+        if let Some(content) = self.saved_state() {
+            let _ = std::fs::write(self.state_path(), content);
+        }
+    }
+    
+    /// 读取上次保存的进度，文件大小不一致或输出文件不存在时重新下载
+    async fn load_state(&self, total: u64) -> Option<Vec<Arc<Segment>>> {
+        let content = tokio::fs::read_to_string(self.state_path()).await.ok()?;
+        let state: SavedState = serde_json::from_str(&content).ok()?;
+        let file_len = tokio::fs::metadata(&self.output).await.ok()?.len();
+        if state.total != total || file_len != total {
+            return None;
+        }
+        Some(
+            state
+                .segments
+                .into_iter()
+                .map(|s| {
+                    Arc::new(Segment {
+                        start: s.start,
+                        end: s.end,
+                        downloaded: AtomicU64::new(s.downloaded.min(s.end + 1 - s.start)),
+                    })
+                })
+                .collect(),
+        )
+    }
+}
+
+fn state_path(output: &Path) -> PathBuf {
+    let mut path = output.to_path_buf().into_os_string();
+    path.push(STATE_SUFFIX);
+    PathBuf::from(path)
+}
+
+pub struct NativeBackend {
+    /// 更换代理时重新创建，对之后开始的下载生效
+    client: RwLock<reqwest::Client>,
+    connections: u32,
+    jobs: RwLock<HashMap<String, Arc<Job>>>,
+    /// 所有下载共用的整体限速
+    limiter: Arc<RateLimiter>,
+    /// 新添加的下载使用的限速（字节/秒）
+    download_limit: AtomicU64,
+}
+
+impl NativeBackend {
+    /// connections为每个文件的最大连接数
+    pub fn new(connections: u32) -> Self {
+        Self {
+            client: RwLock::new(Self::build_client(None).unwrap_or_default()),
+            connections: connections.clamp(1, MAX_CONNECTIONS),
+            jobs: RwLock::new(HashMap::new()),
+            limiter: Arc::new(RateLimiter::new(0)),
+            download_limit: AtomicU64::new(0),
+        }
+    }
+
+    fn build_client(proxy: Option<&str>) -> Result<reqwest::Client, String> {
+        let mut headers = reqwest::header::HeaderMap::new();
+        for (key, value) in DOWNLOAD_HEADERS {
+            if let Ok(value) = value.parse() {
+                headers.insert(key, value);
+            }
+        }
+        let mut builder = reqwest::Client::builder()
+            .default_headers(headers)
+            .connect_timeout(Duration::from_secs(15))
+            .read_timeout(Duration::from_secs(30));
+        if let Some(proxy) = proxy {
+            builder = builder.proxy(reqwest::Proxy::all(proxy).map_err(|e| format!("代理地址无效: {}", e))?);
+        }
+        builder.build().map_err(|e| format!("创建HTTP客户端失败: {}", e))
+    }
+    
+    fn job(&self, id: &str) -> Option<Arc<Job>> {
+        self.jobs.read().get(id).cloned()
+    }
+
+    fn spawn(&self, job: Arc<Job>) {
+        *job.state.write() = JobState::Active;
+        let client = self.client.read().clone();
+        let connections = self.connections;
+        let limiters = [self.limiter.clone(), job.limiter.clone()];
+        let task_job = job.clone();
+        let handle = tokio::spawn(async move {
+            let result = download(&task_job, &client, connections, &limiters).await;
+            task_job.save_state().await;
+            match result {
+                Ok(()) => {
+                    let _ = tokio::fs::remove_file(task_job.state_path()).await;
+                    *task_job.state.write() = JobState::Complete;
+                }
+                Err(e) => {
+                    debug_eprintln!("下载失败 {:?}: {}", task_job.output, e);
+                    *task_job.state.write() = JobState::Error(e);
+                }
+            }
+        });
+        *job.handle.lock() = Some(handle);
+    }
+
+    /// 停止下载，之后由调用方保存进度
+    fn halt(job: &Job) {
+        if let Some(handle) = job.handle.lock().take() {
+            handle.abort();
+        }
+    }
+    
+    fn all_jobs(&self) -> Vec<Arc<Job>> {
+        self.jobs.read().values().cloned().collect()
+    }
+}
+
+impl DownloadBackend for NativeBackend {
+    fn wait_ready(&self) -> BoxFuture<'_, bool> {
+        Box::pin(async { true })
+    }
+
+    /// 没有.bdstate时总是重新写入：输出的是合并前的临时文件，冲突策略在确定文件名时已经处理
+    fn add(&self, urls: Vec<String>, output: PathBuf, _overwrite: bool) -> BoxFuture<'_, Result<String, String>> {
+        Box::pin(async move {
+            if urls.is_empty() {
+                return Err("没有可用的下载地址".to_string());
+            }
+            let id = uuid::Uuid::new_v4().simple().to_string();
+            let job = Arc::new(Job {
+                urls,
+                output,
+                state: RwLock::new(JobState::Waiting),
+                total: AtomicU64::new(0),
+                segments: RwLock::new(Vec::new()),
+                handle: Mutex::new(None),
+                sample: Mutex::new(SpeedSample {
+                    time: Instant::now(),
+                    completed: 0,
+                    speed: 0,
+                }),
+                limiter: Arc::new(RateLimiter::new(self.download_limit.load(Ordering::Relaxed))),
+            });
+            self.jobs.write().insert(id.clone(), job.clone());
+            self.spawn(job);
+            Ok(id)
+        })
+    }
+
+    fn status(&self, id: String) -> BoxFuture<'_, Option<JobStatus>> {
+        Box::pin(async move {
+            let job = self.job(&id)?;
+            let state = job.state.read().clone();
+            let completed = job.completed();
+
+            // 两次查询间隔超过0.5秒时重新计算速度
+            let speed = {
+                let mut sample = job.sample.lock();
+                let elapsed = sample.time.elapsed().as_secs_f64();
+                if elapsed >= 0.5 {
+                    sample.speed = (completed.saturating_sub(sample.completed) as f64 / elapsed) as u64;
+                    sample.time = Instant::now();
+                    sample.completed = completed;
+                }
+                if state == JobState::Active { sample.speed } else { 0 }
+            };
+
+            Some(JobStatus {
+                state,
+                total: job.total.load(Ordering::Relaxed),
+                completed,
+                speed,
+            })
+        })
+    }
+
+    fn pause(&self, id: String) -> BoxFuture<'_, ()> {
+        Box::pin(async move {
+            if let Some(job) = self.job(&id) {
+                if matches!(*job.state.read(), JobState::Active | JobState::Waiting) {
+                    Self::halt(&job);
+                    *job.state.write() = JobState::Paused;
+                    job.save_state().await;
+                }
+            }
+        })
+    }
+
+    fn unpause(&self, id: String) -> BoxFuture<'_, ()> {
+        Box::pin(async move {
+            if let Some(job) = self.job(&id) {
+                if *job.state.read() == JobState::Paused {
+                    self.spawn(job);
+                }
+            }
+        })
+    }
+
+    fn remove(&self, id: String) -> BoxFuture<'_, ()> {
+        Box::pin(async move {
+            if let Some(job) = self.jobs.write().remove(&id) {
+                if let Some(handle) = job.handle.lock().take() {
+                    handle.abort();
+                }
+                *job.state.write() = JobState::Removed;
+            }
+        })
+    }
+
+    /// 内置下载器中的下载都属于当前程序，不需要ids
+    fn set_speed_limits(&self, overall: u64, per_download: u64, _ids: Vec<String>) -> BoxFuture<'_, bool> {
+        Box::pin(async move {
+            self.limiter.set_rate(overall);
+            self.download_limit.store(per_download, Ordering::Relaxed);
+            for job in self.jobs.read().values() {
+                job.limiter.set_rate(per_download);
+            }
+            true
+        })
+    }
+    
+    fn set_proxy(&self, proxy: Option<String>) -> Result<(), String> {
+        *self.client.write() = Self::build_client(proxy.as_deref())?;
+        Ok(())
+    }
+    
+    fn control_file(&self, output: &Path) -> PathBuf {
+        state_path(output)
+    }
+
+    fn save_state(&self) -> BoxFuture<'_, ()> {
+        Box::pin(async move {
+            for job in self.all_jobs() {
+                job.save_state().await;
+            }
+        })
+    }
+    
+    fn stop(&self) {
+        for job in self.all_jobs() {
+            Self::halt(&job);
+            job.save_state_blocking();
+        }
+    }
+}
+
+/// 探测文件大小以及服务器是否支持Range请求，依次尝试各个镜像
+async fn probe(client: &reqwest::Client, urls: &[String]) -> Result<(u64, bool), String> {
+    let mut last_error = String::new();
+    for url in urls {
+        let response = match client.get(url).header(reqwest::header::RANGE, "bytes=0-0").send().await {
+            Ok(response) => response,
+            Err(e) => {
+                last_error = format!("请求失败: {}", e);
+                continue;
+            }
+        };
+
+        match response.status() {
+            reqwest::StatusCode::PARTIAL_CONTENT => {
+                // Content-Range: bytes 0-0/12345
+                let total = response
+                    .headers()
+                    .get(reqwest::header::CONTENT_RANGE)
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(|value| value.rsplit('/').next())
+                    .and_then(|value| value.parse().ok());
+                if let Some(total) = total {
+                    return Ok((total, true));
+                }
+                last_error = "无法获取文件大小".to_string();
+            }
+            reqwest::StatusCode::OK => {
+                if let Some(total) = response.content_length() {
+                    return Ok((total, false));
+                }
+                last_error = "无法获取文件大小".to_string();
+            }
+            status => last_error = format!("服务器返回错误: {}", status),
+        }
+    }
+    Err(last_error)
+}
+
+/// 把文件平均分成若干段
+fn split(total: u64, connections: u32) -> Vec<Arc<Segment>> {
+    if total == 0 {
+        return Vec::new();
+    }
+    let count = (total.div_ceil(MIN_SEGMENT_SIZE)).clamp(1, connections as u64);
+    let size = total.div_ceil(count);
+    (0..count)
+        .map(|i| i * size)
+        .take_while(|&start| start < total)
+        .map(|start| {
+            Arc::new(Segment {
+                start,
+                end: (start + size).min(total) - 1,
+                downloaded: AtomicU64::new(0),
+            })
+        })
+        .collect()
+}
+
+async fn download(
+    job: &Arc<Job>,
+    client: &reqwest::Client,
+    connections: u32,
+    limiters: &[Arc<RateLimiter>; 2],
+) -> Result<(), String> {
+    // 第一次运行时确定分段，暂停后继续沿用内存中的进度
+    if job.segments.read().is_empty() {
+        let (total, ranged) = probe(client, &job.urls).await?;
+        let saved = if ranged { job.load_state(total).await } else { None };
+        let segments = match saved {
+            Some(segments) => segments,
+            None => {
+                let file = tokio::fs::File::create(&job.output).await.map_err(|e| format!("创建文件失败: {}", e))?;
+                file.set_len(total).await.map_err(|e| format!("创建文件失败: {}", e))?;
+                // 不支持Range时只能单连接从头下载
+                split(total, if ranged { connections } else { 1 })
+            }
+        };
+        job.total.store(total, Ordering::Relaxed);
+        *job.segments.write() = segments;
+    }
+
+    let segments: Vec<Arc<Segment>> = job.segments.read().iter().filter(|s| !s.is_done()).cloned().collect();
+    let mut workers = JoinSet::new();
+    for (index, segment) in segments.into_iter().enumerate() {
+        let client = client.clone();
+        let urls = job.urls.clone();
+        let output = job.output.clone();
+        let limiters = limiters.clone();
+        workers.spawn(async move { download_segment(&client, &urls, index, &output, &segment, &limiters).await });
+    }
+
+    // 等待所有分段完成，期间每秒保存一次进度
+    let mut ticker = tokio::time::interval(Duration::from_secs(1));
+    loop {
+        tokio::select! {
+            result = workers.join_next() => match result {
+                None => break,
+                Some(Ok(Ok(()))) => {}
+                Some(Ok(Err(e))) => return Err(e),
+                Some(Err(e)) => return Err(format!("下载线程异常: {}", e)),
+            },
+            _ = ticker.tick() => job.save_state().await,
+        }
+    }
+
+    if job.segments.read().iter().all(|s| s.is_done()) {
+        Ok(())
+    } else {
+        Err("下载不完整".to_string())
+    }
+}
+
+/// 下载一个分段，失败时换用下一个镜像重试
+async fn download_segment(
+    client: &reqwest::Client,
+    urls: &[String],
+    index: usize,
+    output: &Path,
+    segment: &Segment,
+    limiters: &[Arc<RateLimiter>; 2],
+) -> Result<(), String> {
+    let mut failures = 0;
+    let mut attempt = index;
+    loop {
+        let offset = segment.start + segment.downloaded.load(Ordering::Relaxed);
+        if offset > segment.end {
+            return Ok(());
+        }
+
+        let url = &urls[attempt % urls.len()];
+        attempt += 1;
+        let before = segment.downloaded.load(Ordering::Relaxed);
+        let result = fetch_range(client, url, output, offset, segment, limiters).await;
+        if result.is_ok() && segment.is_done() {
+            return Ok(());
+        }
+
+        // 有新数据时说明连接正常，重新计算失败次数
+        if segment.downloaded.load(Ordering::Relaxed) > before {
+            failures = 0;
+        } else {
+            failures += 1;
+        }
+        if failures >= SEGMENT_RETRIES {
+            return Err(result.err().unwrap_or_else(|| "下载不完整".to_string()));
+        }
+        tokio::time::sleep(Duration::from_secs(failures as u64)).await;
+    }
+}
+
+async fn fetch_range(
+    client: &reqwest::Client,
+    url: &str,
+    output: &Path,
+    mut offset: u64,
+    segment: &Segment,
+    limiters: &[Arc<RateLimiter>; 2],
+) -> Result<(), String> {
+    let response = client
+        .get(url)
+        .header(reqwest::header::RANGE, format!("bytes={}-{}", offset, segment.end))
+        .send()
+        .await
+        .map_err(|e| format!("请求失败: {}", e))?;
+
+    match response.status() {
+        reqwest::StatusCode::PARTIAL_CONTENT => {}
+        // 服务器忽略Range时返回整个文件，只能从文件开头重新写入
+        reqwest::StatusCode::OK if segment.start == 0 => {
+            segment.downloaded.store(0, Ordering::Relaxed);
+            offset = 0;
+        }
+        status => return Err(format!("服务器返回错误: {}", status)),
+    }
+    
+    let mut file = tokio::fs::OpenOptions::new()
+        .write(true)
+        .open(output)
+        .await
+        .map_err(|e| format!("打开文件失败: {}", e))?;
+    file.seek(SeekFrom::Start(offset)).await.map_err(|e| format!("写入文件失败: {}", e))?;
+
+    let mut stream = response.bytes_stream();
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.map_err(|e| format!("下载中断: {}", e))?;
+        let remaining = segment.len() - segment.downloaded.load(Ordering::Relaxed);
+        let data = &chunk[..chunk.len().min(remaining as usize)];
+        file.write_all(data).await.map_err(|e| format!("写入文件失败: {}", e))?;
+        // tokio的文件写入在后台线程完成，flush后数据才真正写入文件，
+        // 之后再计入进度，保存的进度不会超过文件中的数据
+        file.flush().await.map_err(|e| format!("写入文件失败: {}", e))?;
+        segment.downloaded.fetch_add(data.len() as u64, Ordering::Relaxed);
+        if segment.is_done() {
+            break;
+        }
+        for limiter in limiters {
+            limiter.consume(data.len() as u64).await;
+        }
+    }
+    Ok(())
+}
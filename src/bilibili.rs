@@ -1,12 +1,17 @@
 // src/bilibili.rs
-use md5;
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock};
 use reqwest::header::{HeaderMap, HeaderValue, COOKIE, REFERER, USER_AGENT};
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
+use std::path::PathBuf;
 use std::sync::Arc;
-use std::time::{SystemTime, UNIX_EPOCH};
-use tokio::runtime::Runtime;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use crate::config::{Config, ProxyConfig};
+use crate::danmaku::{self, Danmaku};
+use crate::error::ApiError;
+use crate::metadata::Chapter;
+use crate::rate_limit::RateLimiter;
+use crate::subtitle::SubtitleLine;
 
 #[cfg(debug_assertions)]
 macro_rules! debug_println {
@@ -23,10 +28,87 @@ pub struct VideoInfo {
     pub title: String,
     pub desc: String,
     pub cover: String,
+    /// 发布时间（秒级时间戳），未知时为0
+    #[serde(default)]
+    pub pubdate: i64,
     pub owner: Owner,
     pub qualities: Vec<QualityInfo>,
+    /// 可选音质，包括Hi-Res无损和杜比全景声
+    #[serde(default)]
+    pub audio_qualities: Vec<AudioQualityInfo>,
     pub cid: u64,
     pub aid: u64,
+    pub pages: Vec<PageInfo>,
+    /// 番剧/影视（PGC）时为Some，此时pages为剧集列表
+    pub season: Option<SeasonInfo>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PageInfo {
+    pub cid: u64,
+    pub page: u32,
+    pub part: String,
+    pub duration: u64,
+    /// 番剧剧集的ep_id，普通视频分P为None
+    #[serde(default)]
+    pub ep_id: Option<u64>,
+    /// 番剧剧集所属稿件的BV号
+    #[serde(default)]
+    pub bvid: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SeasonInfo {
+    pub season_id: u64,
+    /// 通过ep链接解析时指向的剧集
+    pub target_ep_id: Option<u64>,
+}
+
+/// 用户输入解析后的目标类型
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParsedInput {
+    Video(String),
+    Episode(u64),
+    Season(u64),
+    Media(u64),
+    /// 收藏夹，参数为media_id
+    Favorite(u64),
+    /// UP主空间的全部投稿，参数为mid
+    Space(u64),
+    /// UP主空间中的合集
+    Collection { mid: u64, season_id: u64 },
+    /// UP主空间中的系列
+    Series { mid: u64, series_id: u64 },
+}
+
+impl ParsedInput {
+    /// 是否为需要批量选择的视频列表
+    pub fn is_list(&self) -> bool {
+        matches!(
+            self,
+            ParsedInput::Favorite(_)
+                | ParsedInput::Space(_)
+                | ParsedInput::Collection { .. }
+                | ParsedInput::Series { .. }
+        )
+    }
+}
+
+/// 视频列表（收藏夹等）的一页，其中的VideoInfo只填充了基本信息，
+/// qualities/pages为空、cid为0，下载前需要再取分P信息
+#[derive(Debug, Clone)]
+pub struct VideoListPage {
+    pub title: String,
+    pub videos: Vec<VideoInfo>,
+    pub total: u32,
+    pub has_more: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FavoriteFolder {
+    pub id: u64,
+    pub title: String,
+    pub media_count: u32,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -40,6 +122,107 @@ pub struct QualityInfo {
     pub id: u32,
     pub desc: String,
     pub is_available: bool,
+    /// 该画质下可用的DASH视频流
+    #[serde(default)]
+    pub streams: Vec<StreamInfo>,
+}
+
+/// DASH视频编码
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum VideoCodec {
+    Avc,
+    Hevc,
+    Av1,
+}
+
+impl VideoCodec {
+    pub const ALL: [VideoCodec; 3] = [VideoCodec::Avc, VideoCodec::Hevc, VideoCodec::Av1];
+
+    /// 根据DASH流的codecs字段识别编码，如 avc1.640032、hev1.1.6.L150.90、av01.0.08M.08
+    pub fn from_codecs(codecs: &str) -> Option<Self> {
+        if codecs.starts_with("avc") {
+            Some(VideoCodec::Avc)
+        } else if codecs.starts_with("hev") || codecs.starts_with("hvc") {
+            Some(VideoCodec::Hevc)
+        } else if codecs.starts_with("av01") {
+            Some(VideoCodec::Av1)
+        } else {
+            None
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            VideoCodec::Avc => "AVC/H.264",
+            VideoCodec::Hevc => "HEVC/H.265",
+            VideoCodec::Av1 => "AV1",
+        }
+    }
+
+    /// 用于文件名的简短名称
+    pub fn short_name(&self) -> &'static str {
+        match self {
+            VideoCodec::Avc => "AVC",
+            VideoCodec::Hevc => "HEVC",
+            VideoCodec::Av1 => "AV1",
+        }
+    }
+}
+
+/// 画质ID对应的简短名称，用于文件名
+pub fn quality_name(quality: u32) -> String {
+    match quality {
+        127 => "8K".to_string(),
+        126 => "Dolby Vision".to_string(),
+        125 => "HDR".to_string(),
+        120 => "4K".to_string(),
+        116 => "1080P60".to_string(),
+        112 => "1080P+".to_string(),
+        80 => "1080P".to_string(),
+        74 => "720P60".to_string(),
+        64 => "720P".to_string(),
+        32 => "480P".to_string(),
+        16 => "360P".to_string(),
+        6 => "240P".to_string(),
+        _ => format!("Q{}", quality),
+    }
+}
+
+/// 杜比全景声音轨（E-AC-3）
+pub const AUDIO_DOLBY: u32 = 30250;
+/// Hi-Res无损音轨（FLAC）
+pub const AUDIO_HIRES: u32 = 30251;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AudioQualityInfo {
+    pub id: u32,
+    pub desc: String,
+    /// 码率，单位bps
+    pub bandwidth: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StreamInfo {
+    pub codec: VideoCodec,
+    /// 码率，单位bps
+    pub bandwidth: u64,
+    pub width: u32,
+    pub height: u32,
+    pub frame_rate: String,
+}
+
+/// 选中的音视频流地址，每个流包含主地址和所有备用镜像
+#[derive(Debug, Clone)]
+pub struct StreamUrls {
+    pub video: Vec<String>,
+    /// durl（FLV/MP4单文件）格式没有单独的音频流
+    pub audio: Option<Vec<String>>,
+    /// 实际选中的画质ID
+    pub quality: u32,
+    /// 实际选中的视频编码，durl格式为None
+    pub codec: Option<VideoCodec>,
+    /// 实际选中的音轨ID，没有Hi-Res/杜比音轨时会退回普通音轨，durl格式为0
+    pub audio_quality: u32,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -72,8 +255,21 @@ struct BiliVideoData {
     title: String,
     desc: String,
     pic: String,
+    #[serde(default)]
+    pubdate: i64,
     owner: BiliOwner,
     cid: u64,
+    #[serde(default)]
+    pages: Vec<BiliPage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BiliPage {
+    cid: u64,
+    page: u32,
+    part: String,
+    #[serde(default)]
+    duration: u64,
 }
 
 #[derive(Debug, Deserialize)]
@@ -131,6 +327,32 @@ struct PlayUrlData {
 struct DashData {
     video: Vec<DashVideo>,
     audio: Vec<DashAudio>,
+    #[serde(default)]
+    dolby: Option<DashDolby>,
+    #[serde(default)]
+    flac: Option<DashFlac>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DashDolby {
+    #[serde(default)]
+    audio: Option<Vec<DashAudio>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DashFlac {
+    #[serde(default)]
+    audio: Option<DashAudio>,
+}
+
+impl DashData {
+    /// 普通音轨、杜比音轨和无损音轨
+    fn all_audio(&self) -> impl Iterator<Item = &DashAudio> {
+        self.audio
+            .iter()
+            .chain(self.dolby.iter().flat_map(|d| d.audio.iter().flatten()))
+            .chain(self.flac.iter().flat_map(|f| f.audio.iter()))
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -140,26 +362,21 @@ struct DashVideo {
     base_url: String,
     #[serde(rename = "backupUrl")]
     backup_url: Option<Vec<String>>,
-    #[allow(dead_code)]
     bandwidth: u64,
-    #[allow(dead_code)]
     codecs: String,
-    #[allow(dead_code)]
     width: u32,
-    #[allow(dead_code)]
     height: u32,
     #[serde(rename = "frameRate")]
-    #[allow(dead_code)]
     frame_rate: String,
 }
 
 #[derive(Debug, Deserialize)]
 struct DashAudio {
+    id: u32,
     #[serde(rename = "baseUrl")]
     base_url: String,
     #[serde(rename = "backupUrl")]
     backup_url: Option<Vec<String>>,
-    #[allow(dead_code)]
     bandwidth: u64,
     #[allow(dead_code)]
     codecs: String,
@@ -168,12 +385,279 @@ struct DashAudio {
 #[derive(Debug, Deserialize)]
 struct DurlData {
     url: String,
+    #[serde(default)]
+    backup_url: Option<Vec<String>>,
     #[allow(dead_code)]
     size: u64,
     #[allow(dead_code)]
     length: u64,
 }
 
+#[derive(Debug, Deserialize)]
+struct PgcPlayUrlResponse {
+    code: i32,
+    result: Option<PlayUrlData>,
+    message: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PgcSeasonResponse {
+    code: i32,
+    result: Option<PgcSeasonData>,
+    message: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PgcSeasonData {
+    season_id: u64,
+    title: String,
+    #[serde(default)]
+    evaluate: String,
+    cover: String,
+    up_info: Option<PgcUpInfo>,
+    #[serde(default)]
+    episodes: Vec<PgcEpisode>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PgcUpInfo {
+    uname: String,
+    avatar: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct PgcEpisode {
+    id: u64,
+    aid: u64,
+    #[serde(default)]
+    bvid: String,
+    cid: u64,
+    title: String,
+    #[serde(default)]
+    long_title: String,
+    /// 毫秒
+    #[serde(default)]
+    duration: u64,
+    #[serde(default)]
+    pub_time: i64,
+}
+
+#[derive(Debug, Deserialize)]
+struct PgcMediaResponse {
+    code: i32,
+    result: Option<PgcMediaData>,
+    message: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PgcMediaData {
+    media: PgcMedia,
+}
+
+#[derive(Debug, Deserialize)]
+struct PgcMedia {
+    season_id: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct FavFolderListResponse {
+    code: i32,
+    data: Option<FavFolderListData>,
+    message: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct FavFolderListData {
+    #[serde(default)]
+    list: Option<Vec<FavFolder>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct FavFolder {
+    id: u64,
+    title: String,
+    media_count: u32,
+}
+
+#[derive(Debug, Deserialize)]
+struct FavResourceResponse {
+    code: i32,
+    data: Option<FavResourceData>,
+    message: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct FavResourceData {
+    info: FavInfo,
+    #[serde(default)]
+    medias: Option<Vec<FavMedia>>,
+    has_more: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct FavInfo {
+    title: String,
+    media_count: u32,
+}
+
+#[derive(Debug, Deserialize)]
+struct FavMedia {
+    id: u64,
+    #[serde(rename = "type")]
+    media_type: u32,
+    title: String,
+    cover: String,
+    #[serde(default)]
+    intro: String,
+    /// 失效视频的attr最低位为1
+    #[serde(default)]
+    attr: u32,
+    upper: BiliOwner,
+    bvid: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct SpaceArcResponse {
+    code: i32,
+    data: Option<SpaceArcData>,
+    message: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SpaceArcData {
+    list: SpaceArcList,
+    page: SpaceArcPage,
+}
+
+#[derive(Debug, Deserialize)]
+struct SpaceArcList {
+    #[serde(default)]
+    vlist: Vec<SpaceArcVideo>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SpaceArcVideo {
+    aid: u64,
+    bvid: String,
+    title: String,
+    pic: String,
+    #[serde(default)]
+    description: String,
+    author: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct SpaceArcPage {
+    pn: u32,
+    ps: u32,
+    count: u32,
+}
+
+#[derive(Debug, Deserialize)]
+struct ArchiveListResponse {
+    code: i32,
+    data: Option<ArchiveListData>,
+    message: Option<String>,
+}
+
+/// 合集和系列接口共用的稿件列表
+#[derive(Debug, Deserialize)]
+struct ArchiveListData {
+    #[serde(default)]
+    archives: Vec<ArchiveItem>,
+    meta: Option<ArchiveMeta>,
+    page: ArchivePage,
+}
+
+#[derive(Debug, Deserialize)]
+struct ArchiveItem {
+    aid: u64,
+    bvid: String,
+    title: String,
+    pic: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ArchiveMeta {
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ArchivePage {
+    /// 合集接口为page_num/page_size，系列接口为num/size
+    #[serde(alias = "page_num")]
+    num: u32,
+    #[serde(alias = "page_size")]
+    size: u32,
+    total: u32,
+}
+
+#[derive(Debug, Deserialize)]
+struct SeriesInfoResponse {
+    code: i32,
+    data: Option<SeriesInfoData>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SeriesInfoData {
+    meta: ArchiveMeta,
+}
+
+/// 视频的一条CC字幕轨道
+#[derive(Debug, Clone)]
+pub struct SubtitleTrack {
+    pub lan: String,
+    pub lan_doc: String,
+    pub url: String,
+    /// AI生成的字幕
+    pub is_ai: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct PlayerV2Response {
+    code: i32,
+    message: Option<String>,
+    data: Option<PlayerV2Data>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PlayerV2Data {
+    subtitle: Option<PlayerSubtitle>,
+    #[serde(default)]
+    view_points: Vec<PlayerViewPoint>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PlayerViewPoint {
+    /// 2为分段章节
+    #[serde(rename = "type")]
+    kind: u32,
+    from: u64,
+    to: u64,
+    content: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct PlayerSubtitle {
+    #[serde(default)]
+    subtitles: Vec<PlayerSubtitleItem>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PlayerSubtitleItem {
+    lan: String,
+    lan_doc: String,
+    #[serde(default)]
+    subtitle_url: String,
+    #[serde(default)]
+    ai_type: u32,
+}
+
+#[derive(Debug, Deserialize)]
+struct SubtitleBody {
+    body: Vec<SubtitleLine>,
+}
+
 #[derive(Debug, Deserialize)]
 struct NavResponse {
     code: i32,
@@ -197,6 +681,47 @@ struct WbiImg {
     sub_url: String,
 }
 
+/// 接口请求的客户端设置，不自动跟随重定向
+fn client_builder(proxy: Option<&str>) -> Result<reqwest::ClientBuilder, String> {
+    let mut builder = reqwest::Client::builder()
+        .timeout(std::time::Duration::from_secs(30))
+        .redirect(reqwest::redirect::Policy::none());
+    if let Some(proxy) = proxy {
+        builder = builder.proxy(reqwest::Proxy::all(proxy).map_err(|e| format!("代理地址无效: {}", e))?);
+    }
+    Ok(builder)
+}
+
+/// 通过代理请求B站的地区接口，返回耗时（毫秒）和出口地区
+pub async fn test_proxy(proxy: &ProxyConfig) -> Result<(u128, String), String> {
+    let proxy = proxy.to_url()?.ok_or("未启用代理")?;
+    let client = client_builder(Some(&proxy))?
+        .timeout(std::time::Duration::from_secs(10))
+        .build()
+        .map_err(|e| format!("创建HTTP客户端失败: {}", e))?;
+    
+    let start = std::time::Instant::now();
+    let json: serde_json::Value = client
+        .get("https://api.bilibili.com/x/web-interface/zone")
+        .header(USER_AGENT, "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36")
+        .send()
+        .await
+        .map_err(|e| format!("连接失败: {}", e))?
+        .json()
+        .await
+        .map_err(|e| format!("读取响应失败: {}", e))?;
+    let elapsed = start.elapsed().as_millis();
+    
+    let data = &json["data"];
+    let region = [&data["country"], &data["province"]]
+        .iter()
+        .filter_map(|value| value.as_str())
+        .filter(|value| !value.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ");
+    Ok((elapsed, region))
+}
+
 // Wbi 签名
 const MIXIN_KEY_ENC_TAB: [usize; 64] = [
     46, 47, 18, 2, 53, 8, 23, 32, 15, 50, 10, 31, 58, 3, 45, 35, 27, 43, 5, 49, 33, 9, 42, 19, 29,
@@ -204,32 +729,318 @@ const MIXIN_KEY_ENC_TAB: [usize; 64] = [
     54, 21, 56, 59, 6, 63, 57, 62, 11, 36, 20, 34, 44, 52,
 ];
 
-pub struct BilibiliApi {
-    client: reqwest::Client,
-    cookies: Arc<RwLock<Option<String>>>,
-    #[allow(dead_code)]
-    runtime: Arc<Runtime>,
-    user_info: Arc<RwLock<Option<UserInfo>>>,
-    wbi_keys: Arc<RwLock<Option<(String, String)>>>,
+/// 每秒最多发出的接口请求数
+const API_REQUESTS_PER_SECOND: u64 = 4;
+/// 被风控拦截后的重试次数，等待时间从2秒开始每次翻倍
+const RISK_CONTROL_RETRIES: u32 = 3;
+const RISK_CONTROL_BASE_DELAY: Duration = Duration::from_secs(2);
+
+/// Wbi密钥的缓存时间，B站每天更换密钥
+const WBI_KEYS_TTL_SECS: i64 = 6 * 3600;
+
+/// 响应JSON中的code，HTTP 412以外的风控拦截和签名错误只能从这里看出
+fn response_code(text: &str) -> Option<i32> {
+    #[derive(Deserialize)]
+    struct Envelope {
+        code: i32,
+    }
+    
+    serde_json::from_str::<Envelope>(text).ok().map(|envelope| envelope.code)
 }
 
-impl BilibiliApi {
-    pub fn new(runtime: Arc<Runtime>) -> Self {
-        let client = reqwest::Client::builder()
-            .timeout(std::time::Duration::from_secs(30))
-            .redirect(reqwest::redirect::Policy::none())
-            .build()
-            .unwrap();
+fn parse_json<T: serde::de::DeserializeOwned>(text: &str) -> Result<T, ApiError> {
+    serde_json::from_str(text).map_err(|e| ApiError::Other(format!("解析响应失败: {}", e)))
+}
 
+/// Wbi签名密钥和获取时间，保存在wbi_keys.json中，重启后继续使用
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct WbiKeys {
+    img_key: String,
+    sub_key: String,
+    /// 获取时间（秒级时间戳）
+    fetched_at: i64,
+}
+
+impl WbiKeys {
+    fn is_fresh(&self) -> bool {
+        let age = chrono::Local::now().timestamp() - self.fetched_at;
+        (0..WBI_KEYS_TTL_SECS).contains(&age)
+    }
+}
+
+/// 未登录时使用的buvid3、buvid4和b_nut，带上设备标识的请求不容易被风控拦截
+fn anonymous_cookies() -> String {
+    let device_id = || {
+        let uuid = uuid::Uuid::new_v4();
+        format!("{}{:05}", uuid.to_string().to_uppercase(), uuid.as_u128() % 100000)
+    };
+    let now = chrono::Local::now();
+    format!(
+        "buvid3={}infoc; buvid4={}-{}-{}; b_nut={}",
+        device_id(),
+        device_id(),
+        now.format("%y%m%d%H%M"),
+        &uuid::Uuid::new_v4().simple().to_string()[..16],
+        now.timestamp()
+    )
+}
+
+/// 各类接口的地址（协议和域名，不以/结尾），测试时可以指向本地的模拟服务
+#[derive(Debug, Clone)]
+pub struct Endpoints {
+    pub api: String,
+    pub passport: String,
+    /// XML弹幕
+    pub comment: String,
+    /// b23.tv短链接
+    pub short_link: String,
+}
+
+impl Default for Endpoints {
+    fn default() -> Self {
         Self {
-            client,
+            api: "https://api.bilibili.com".to_string(),
+            passport: "https://passport.bilibili.com".to_string(),
+            comment: "https://comment.bilibili.com".to_string(),
+            short_link: "https://b23.tv".to_string(),
+        }
+    }
+}
+
+/// 创建BilibiliApi，可以替换接口地址和请求频率
+pub struct BilibiliApiBuilder {
+    endpoints: Endpoints,
+    requests_per_second: u64,
+    risk_backoff: Duration,
+    wbi_keys_path: Option<PathBuf>,
+}
+
+impl BilibiliApiBuilder {
+    pub fn endpoints(mut self, endpoints: Endpoints) -> Self {
+        self.endpoints = endpoints;
+        self
+    }
+    
+    /// 所有接口都使用同一个地址，用于本地模拟服务
+    pub fn base_url(self, url: &str) -> Self {
+        let url = url.trim_end_matches('/').to_string();
+        self.endpoints(Endpoints {
+            api: url.clone(),
+            passport: url.clone(),
+            comment: url.clone(),
+            short_link: url,
+        })
+    }
+    
+    /// 每秒最多发出的接口请求数，0表示不限速
+    pub fn requests_per_second(mut self, requests: u64) -> Self {
+        self.requests_per_second = requests;
+        self
+    }
+    
+    /// 被风控拦截后第一次重试前的等待时间
+    pub fn risk_backoff(mut self, delay: Duration) -> Self {
+        self.risk_backoff = delay;
+        self
+    }
+    
+    /// 保存Wbi密钥的文件，None表示只缓存在内存中
+    pub fn wbi_keys_path(mut self, path: Option<PathBuf>) -> Self {
+        self.wbi_keys_path = path;
+        self
+    }
+    
+    pub fn build(self) -> BilibiliApi {
+        let client = client_builder(None)
+            .and_then(|builder| builder.build().map_err(|e| e.to_string()))
+            .unwrap();
+        let wbi_keys = self
+            .wbi_keys_path
+            .as_ref()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .and_then(|content| serde_json::from_str(&content).ok());
+        
+        BilibiliApi {
+            client: RwLock::new(client),
+            proxy: RwLock::new(None),
+            endpoints: self.endpoints,
             cookies: Arc::new(RwLock::new(None)),
-            runtime,
+            anonymous_cookies: anonymous_cookies(),
+            limiter: RateLimiter::new(self.requests_per_second),
+            blocked_until: Mutex::new(None),
+            risk_backoff: self.risk_backoff,
             user_info: Arc::new(RwLock::new(None)),
-            wbi_keys: Arc::new(RwLock::new(None)),
+            wbi_keys: Arc::new(RwLock::new(wbi_keys)),
+            wbi_keys_path: self.wbi_keys_path,
+        }
+    }
+}
+
+impl Default for BilibiliApi {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct BilibiliApi {
+    client: RwLock<reqwest::Client>,
+    /// 当前使用的代理地址，短链接解析也要使用
+    proxy: RwLock<Option<String>>,
+    endpoints: Endpoints,
+    cookies: Arc<RwLock<Option<String>>>,
+    /// 未登录时发送的设备标识Cookie
+    anonymous_cookies: String,
+    /// 所有接口请求共用的限速
+    limiter: RateLimiter,
+    /// 被风控拦截后，所有请求暂停到这个时间
+    blocked_until: Mutex<Option<Instant>>,
+    /// 被风控拦截后第一次重试前的等待时间
+    risk_backoff: Duration,
+    user_info: Arc<RwLock<Option<UserInfo>>>,
+    wbi_keys: Arc<RwLock<Option<WbiKeys>>>,
+    /// 保存Wbi密钥的文件，None时不保存
+    wbi_keys_path: Option<PathBuf>,
+}
+
+impl BilibiliApi {
+    pub fn new() -> Self {
+        Self::builder().build()
+    }
+    
+    pub fn builder() -> BilibiliApiBuilder {
+        BilibiliApiBuilder {
+            endpoints: Endpoints::default(),
+            requests_per_second: API_REQUESTS_PER_SECOND,
+            risk_backoff: RISK_CONTROL_BASE_DELAY,
+            wbi_keys_path: Some(Config::data_file("wbi_keys.json")),
         }
     }
 
+    fn client(&self) -> reqwest::Client {
+        self.client.read().clone()
+    }
+    
+    /// 更换接口请求使用的代理，对之后的请求生效
+    pub fn set_proxy(&self, proxy: &ProxyConfig) -> Result<(), String> {
+        let proxy = proxy.to_url()?;
+        let client = client_builder(proxy.as_deref())?
+            .build()
+            .map_err(|e| format!("创建HTTP客户端失败: {}", e))?;
+        *self.client.write() = client;
+        *self.proxy.write() = proxy;
+        Ok(())
+    }
+    
+    /// 等待风控暂停结束，再取得一个请求令牌
+    async fn wait_turn(&self) {
+        let blocked_until = *self.blocked_until.lock();
+        if let Some(until) = blocked_until {
+            tokio::time::sleep_until(until.into()).await;
+        }
+        self.limiter.consume(1).await;
+    }
+    
+    /// 被风控拦截后暂停所有请求，等待时间每次翻倍，重试次数用完时返回错误
+    async fn back_off(&self, code: i32, attempt: &mut u32) -> Result<(), ApiError> {
+        if *attempt >= RISK_CONTROL_RETRIES {
+            return Err(ApiError::RateLimited(code));
+        }
+        let delay = self.risk_backoff * 2u32.pow(*attempt);
+        *attempt += 1;
+        debug_println!("请求被风控拦截 (code={})，{}毫秒后第{}次重试", code, delay.as_millis(), attempt);
+        
+        let until = Instant::now() + delay;
+        let mut blocked_until = self.blocked_until.lock();
+        *blocked_until = Some(blocked_until.map_or(until, |current| current.max(until)));
+        Ok(())
+    }
+    
+    /// 限速后发送请求，HTTP 412时退避重试
+    async fn send(&self, request: reqwest::RequestBuilder) -> Result<reqwest::Response, ApiError> {
+        let mut attempt = 0;
+        loop {
+            let retry = request.try_clone().ok_or_else(|| ApiError::Other("请求无法重试".to_string()))?;
+            self.wait_turn().await;
+            let response = retry
+                .send()
+                .await
+                .map_err(|e| ApiError::Network(format!("请求失败: {}", e)))?;
+            if response.status() != reqwest::StatusCode::PRECONDITION_FAILED {
+                return Ok(response);
+            }
+            self.back_off(-412, &mut attempt).await?;
+        }
+    }
+    
+    /// 请求接口并读取响应内容，返回-412、-352时同样退避重试
+    async fn fetch_text(&self, request: reqwest::RequestBuilder) -> Result<String, ApiError> {
+        self.fetch_text_with(request, &[-412, -352]).await
+    }
+    
+    /// 只对risk_codes中的错误码退避重试，其余错误码交给调用方处理
+    async fn fetch_text_with(&self, request: reqwest::RequestBuilder, risk_codes: &[i32]) -> Result<String, ApiError> {
+        let mut attempt = 0;
+        loop {
+            let retry = request.try_clone().ok_or_else(|| ApiError::Other("请求无法重试".to_string()))?;
+            let text = self
+                .send(retry)
+                .await?
+                .text()
+                .await
+                .map_err(|e| ApiError::Network(format!("读取响应失败: {}", e)))?;
+            match response_code(&text).filter(|code| risk_codes.contains(code)) {
+                Some(code) => self.back_off(code, &mut attempt).await?,
+                None => return Ok(text),
+            }
+        }
+    }
+    
+    /// 请求接口并解析JSON
+    async fn fetch_json<T: serde::de::DeserializeOwned>(&self, request: reqwest::RequestBuilder) -> Result<T, ApiError> {
+        let text = self.fetch_text(request).await?;
+        parse_json(&text)
+    }
+    
+    /// 请求需要Wbi签名的接口，path不含查询参数。
+    /// 签名校验失败（-403、-352）时刷新密钥重新签名一次，仍然失败才请求不签名的fallback地址
+    async fn fetch_signed(
+        &self,
+        path: &str,
+        params: &BTreeMap<String, String>,
+        fallback: Option<&str>,
+    ) -> Result<String, ApiError> {
+        let mut last = Err(ApiError::Other("无法获取Wbi签名密钥".to_string()));
+        for refresh in [false, true] {
+            let (img_key, sub_key) = match self.update_wbi_keys(refresh).await {
+                Ok(keys) => keys,
+                Err(e) => {
+                    last = Err(e);
+                    break;
+                }
+            };
+            let query = Self::encode_wbi(params, &img_key, &sub_key);
+            let url = format!("{}{}?{}", self.endpoints.api, path, query);
+            let request = self.client().get(&url).headers(self.build_headers(true));
+            let text = self.fetch_text_with(request, &[-412]).await?;
+            match response_code(&text) {
+                Some(_code @ (-403 | -352)) => {
+                    debug_println!("Wbi签名校验失败 (code={})", _code);
+                    last = Ok(text);
+                }
+                _ => return Ok(text),
+            }
+        }
+        
+        match fallback {
+            Some(url) => {
+                debug_println!("重新签名后仍然失败，使用不签名的地址");
+                let request = self.client().get(url).headers(self.build_headers(true));
+                self.fetch_text(request).await
+            }
+            None => last,
+        }
+    }
+    
     fn get_mixin_key(orig: &str) -> String {
         let mut s = String::new();
         for &idx in MIXIN_KEY_ENC_TAB.iter() {
@@ -276,35 +1087,45 @@ impl BilibiliApi {
             .map(|s| s.to_string())
     }
 
-    async fn update_wbi_keys(&self) -> Result<(String, String), String> {
-        if let Some(keys) = self.wbi_keys.read().as_ref() {
-            return Ok(keys.clone());
+    /// 获取Wbi签名密钥，缓存未过期时直接使用，refresh为true时重新获取
+    async fn update_wbi_keys(&self, refresh: bool) -> Result<(String, String), ApiError> {
+        if !refresh {
+            if let Some(keys) = self.wbi_keys.read().as_ref().filter(|keys| keys.is_fresh()) {
+                return Ok((keys.img_key.clone(), keys.sub_key.clone()));
+            }
         }
 
-        let url = "https://api.bilibili.com/x/web-interface/nav";
+        let url = format!("{}/x/web-interface/nav", self.endpoints.api);
         let headers = self.build_headers(true);
-        let response = self
-            .client
-            .get(url)
-            .headers(headers)
-            .send()
-            .await
-            .map_err(|e| format!("Nav请求失败: {}", e))?
-            .json::<NavResponse>()
-            .await
-            .map_err(|e| format!("Nav解析失败: {}", e))?;
+        let request = self.client().get(url).headers(headers);
+        let response: NavResponse = self.fetch_json(request).await?;
 
         if let Some(data) = response.data {
             if let Some(wbi_img) = data.wbi_img {
                 let img_key = Self::extract_wbi_key(&wbi_img.img_url).ok_or("无效的img_key")?;
                 let sub_key = Self::extract_wbi_key(&wbi_img.sub_url).ok_or("无效的sub_key")?;
 
-                *self.wbi_keys.write() = Some((img_key.clone(), sub_key.clone()));
+                self.store_wbi_keys(&img_key, &sub_key);
                 return Ok((img_key, sub_key));
             }
         }
 
-        Err("无法获取Wbi签名密钥".to_string())
+        Err("无法获取Wbi签名密钥".into())
+    }
+    
+    /// 缓存密钥并写入文件
+    fn store_wbi_keys(&self, img_key: &str, sub_key: &str) {
+        let keys = WbiKeys {
+            img_key: img_key.to_string(),
+            sub_key: sub_key.to_string(),
+            fetched_at: chrono::Local::now().timestamp(),
+        };
+        if let Some(path) = &self.wbi_keys_path {
+            if let Ok(content) = serde_json::to_string_pretty(&keys) {
+                let _ = std::fs::write(path, content);
+            }
+        }
+        *self.wbi_keys.write() = Some(keys);
     }
 
     fn build_headers(&self, with_cookie: bool) -> HeaderMap {
@@ -319,12 +1140,15 @@ impl BilibiliApi {
             HeaderValue::from_static("https://www.bilibili.com"),
         );
 
-        if with_cookie {
-            if let Some(cookies) = self.cookies.read().as_ref() {
-                if let Ok(cookie_value) = HeaderValue::from_str(cookies) {
-                    headers.insert(COOKIE, cookie_value);
-                }
-            }
+        // 总是带上设备标识，登录Cookie中已有buvid3时使用登录Cookie中的
+        let login_cookies = if with_cookie { self.cookies.read().clone() } else { None };
+        let cookies = match login_cookies {
+            Some(login) if login.contains("buvid3=") => login,
+            Some(login) => format!("{}; {}", login.trim_end_matches(|c: char| c == ';' || c.is_whitespace()), self.anonymous_cookies),
+            None => self.anonymous_cookies.clone(),
+        };
+        if let Ok(cookie_value) = HeaderValue::from_str(&cookies) {
+            headers.insert(COOKIE, cookie_value);
         }
 
         headers
@@ -341,24 +1165,16 @@ impl BilibiliApi {
         *self.wbi_keys.write() = None;
     }
 
-    pub async fn get_user_info(&self) -> Result<UserInfo, String> {
-        let url = "https://api.bilibili.com/x/web-interface/nav";
+    pub async fn get_user_info(&self) -> Result<UserInfo, ApiError> {
+        let url = format!("{}/x/web-interface/nav", self.endpoints.api);
 
         let headers = self.build_headers(true);
 
-        let response = self
-            .client
-            .get(url)
-            .headers(headers)
-            .send()
-            .await
-            .map_err(|e| format!("请求失败: {}", e))?
-            .json::<NavResponse>()
-            .await
-            .map_err(|e| format!("解析响应失败: {}", e))?;
+        let request = self.client().get(url).headers(headers);
+        let response: NavResponse = self.fetch_json(request).await?;
 
         if response.code != 0 {
-            return Err(format!("获取用户信息失败: code={}", response.code));
+            return Err(ApiError::from_code(response.code, "获取用户信息失败"));
         }
 
         let data = response.data.ok_or_else(|| "用户信息为空".to_string())?;
@@ -368,162 +1184,665 @@ impl BilibiliApi {
                 Self::extract_wbi_key(&wbi_img.img_url),
                 Self::extract_wbi_key(&wbi_img.sub_url),
             ) {
-                *self.wbi_keys.write() = Some((img, sub));
+                self.store_wbi_keys(&img, &sub);
             }
         }
 
         if !data.is_login {
-            return Err("用户未登录".to_string());
+            return Err(ApiError::NotLoggedIn);
         }
 
         let user_info = UserInfo {
             mid: data.mid.unwrap_or(0),
             name: data.uname.unwrap_or_else(|| "未知用户".to_string()),
-            face: data.face.unwrap_or_else(|| String::new()),
+            face: data.face.unwrap_or_default(),
             is_vip: data.vip_status.unwrap_or(0) == 1,
         };
 
-        *self.user_info.write() = Some(user_info.clone());
+        *self.user_info.write() = Some(user_info.clone());
+
+        Ok(user_info)
+    }
+
+    pub async fn download_avatar(&self, url: &str) -> Result<Vec<u8>, ApiError> {
+        if url.is_empty() {
+            return Err("头像URL为空".into());
+        }
+
+        let response = self
+            .client()
+            .get(url)
+            .send()
+            .await
+            .map_err(|e| ApiError::Network(format!("下载头像失败: {}", e)))?;
+
+        let bytes = response
+            .bytes()
+            .await
+            .map_err(|e| format!("读取头像数据失败: {}", e))?;
+
+        Ok(bytes.to_vec())
+    }
+
+    /// 获取弹幕，返回原始XML和完整弹幕列表。
+    /// XML接口有条数上限，优先使用protobuf分段接口（每段6分钟），失败时退回XML中的弹幕
+    pub async fn get_danmaku(&self, cid: u64, duration: u64) -> Result<(String, Vec<Danmaku>), ApiError> {
+        let xml = self.get_danmaku_xml(cid).await?;
+
+        // 时长未知时逐段获取，直到遇到空段
+        let segment_count = if duration > 0 { duration.div_ceil(360) } else { 100 };
+        let mut danmaku = Vec::new();
+        for index in 1..=segment_count {
+            match self.get_danmaku_segment(cid, index).await {
+                Ok(segment) if segment.is_empty() && duration == 0 => break,
+                Ok(segment) => danmaku.extend(segment),
+                Err(_e) => {
+                    debug_println!("获取弹幕分段{}失败: {}", index, _e);
+                    danmaku.clear();
+                    break;
+                }
+            }
+        }
+
+        if danmaku.is_empty() {
+            danmaku = danmaku::parse_xml(&xml);
+        }
+
+        Ok((xml, danmaku))
+    }
+
+    async fn get_danmaku_xml(&self, cid: u64) -> Result<String, ApiError> {
+        let url = format!("{}/{}.xml", self.endpoints.comment, cid);
+
+        let request = self.client().get(&url).headers(self.build_headers(false));
+        let bytes = self
+            .send(request)
+            .await?
+            .bytes()
+            .await
+            .map_err(|e| format!("读取弹幕失败: {}", e))?;
+
+        // 接口返回raw deflate压缩的数据
+        let mut xml = String::new();
+        let mut decoder = flate2::read::DeflateDecoder::new(&bytes[..]);
+        if std::io::Read::read_to_string(&mut decoder, &mut xml).is_err() {
+            xml = String::from_utf8_lossy(&bytes).to_string();
+        }
+
+        Ok(xml)
+    }
+
+    async fn get_danmaku_segment(&self, cid: u64, index: u64) -> Result<Vec<Danmaku>, ApiError> {
+        let url = format!(
+            "{}/x/v2/dm/web/seg.so?type=1&oid={}&segment_index={}",
+            self.endpoints.api, cid, index
+        );
+
+        let request = self.client().get(&url).headers(self.build_headers(true));
+        let response = self.send(request).await?;
+
+        if !response.status().is_success() {
+            return Err(format!("获取弹幕分段失败: HTTP {}", response.status()).into());
+        }
+
+        let bytes = response
+            .bytes()
+            .await
+            .map_err(|e| format!("读取弹幕分段失败: {}", e))?;
+
+        Ok(danmaku::parse_segment(&bytes))
+    }
+
+    /// 播放器信息，包括字幕列表和视频看点
+    async fn get_player_info(&self, bvid: &str, cid: u64) -> Result<PlayerV2Data, ApiError> {
+        let mut params = BTreeMap::new();
+        params.insert("bvid".to_string(), bvid.to_string());
+        params.insert("cid".to_string(), cid.to_string());
+        let fallback = format!("{}/x/player/v2?bvid={}&cid={}", self.endpoints.api, bvid, cid);
+        
+        let response_text = self.fetch_signed("/x/player/wbi/v2", &params, Some(&fallback)).await?;
+        let response: PlayerV2Response = parse_json(&response_text)?;
+
+        if response.code != 0 {
+            return Err(ApiError::from_code(
+                response.code,
+                format!("获取播放器信息失败: {}", response.message.unwrap_or_default())
+            ));
+        }
+
+        response.data.ok_or_else(|| "播放器信息为空".into())
+    }
+
+    /// 获取分P的所有CC字幕轨道（包括AI字幕，AI字幕需要登录）
+    pub async fn get_subtitles(&self, bvid: &str, cid: u64) -> Result<Vec<SubtitleTrack>, ApiError> {
+        let subtitles = self
+            .get_player_info(bvid, cid)
+            .await?
+            .subtitle
+            .map(|s| s.subtitles)
+            .unwrap_or_default();
+
+        Ok(subtitles
+            .into_iter()
+            .filter(|s| !s.subtitle_url.is_empty())
+            .map(|s| SubtitleTrack {
+                is_ai: s.ai_type > 0 || s.lan.starts_with("ai-"),
+                url: if s.subtitle_url.starts_with("//") {
+                    format!("https:{}", s.subtitle_url)
+                } else {
+                    s.subtitle_url
+                },
+                lan: s.lan,
+                lan_doc: s.lan_doc,
+            })
+            .collect())
+    }
+
+    /// 获取UP主设置的分段章节（视频看点）
+    pub async fn get_chapters(&self, bvid: &str, cid: u64) -> Result<Vec<Chapter>, ApiError> {
+        let info = self.get_player_info(bvid, cid).await?;
+
+        Ok(info
+            .view_points
+            .into_iter()
+            .filter(|p| p.kind == 2 && p.to > p.from)
+            .map(|p| Chapter {
+                start: p.from,
+                end: p.to,
+                title: p.content,
+            })
+            .collect())
+    }
+
+    /// 下载字幕JSON
+    pub async fn get_subtitle_lines(&self, track: &SubtitleTrack) -> Result<Vec<SubtitleLine>, ApiError> {
+        let request = self.client().get(&track.url).headers(self.build_headers(false));
+        let body: SubtitleBody = self.fetch_json(request).await?;
+
+        Ok(body.body)
+    }
+
+    pub async fn get_video_info(&self, input: &str) -> Result<VideoInfo, ApiError> {
+        let parsed = self.parse_input(input).await?;
+        self.get_parsed_video_info(parsed).await
+    }
+
+    pub async fn get_parsed_video_info(&self, parsed: ParsedInput) -> Result<VideoInfo, ApiError> {
+        let bvid = match parsed {
+            ParsedInput::Video(bvid) => bvid,
+            ParsedInput::Episode(ep_id) => return self.get_bangumi_info(None, Some(ep_id)).await,
+            ParsedInput::Season(season_id) => return self.get_bangumi_info(Some(season_id), None).await,
+            ParsedInput::Media(media_id) => {
+                let season_id = self.get_season_id_by_media(media_id).await?;
+                return self.get_bangumi_info(Some(season_id), None).await;
+            }
+            ParsedInput::Favorite(_)
+            | ParsedInput::Space(_)
+            | ParsedInput::Collection { .. }
+            | ParsedInput::Series { .. } => return Err("视频列表需要批量解析".into()),
+        };
+
+        // 尝试预加载 Keys
+        let _ = self.update_wbi_keys(false).await;
+
+        let mut info = self.get_video_basic_info(&bvid).await?;
+        (info.qualities, info.audio_qualities) = self.get_available_qualities(&info.bvid, info.cid).await?;
+
+        Ok(info)
+    }
+
+    /// 获取稿件基本信息和分P列表（不请求playurl，qualities为空），
+    /// 用于批量下载时补全cid和UP主信息
+    pub async fn get_video_basic_info(&self, bvid: &str) -> Result<VideoInfo, ApiError> {
+        let url = format!(
+            "{}/x/web-interface/view?bvid={}",
+            self.endpoints.api, bvid
+        );
+
+        let headers = self.build_headers(false);
+
+        let request = self.client().get(&url).headers(headers);
+        let response: BiliVideoResponse = self.fetch_json(request).await?;
+
+        if response.code != 0 {
+            return Err(ApiError::from_code(
+                response.code,
+                format!("API返回错误: {}", response.message.unwrap_or_else(|| "未知错误".to_string()))
+            ));
+        }
+
+        let data = response.data.ok_or_else(|| "视频信息为空".to_string())?;
+
+        // 分P列表，单P视频的API也会返回一项；为空时用顶层cid兜底
+        let mut pages: Vec<PageInfo> = data
+            .pages
+            .into_iter()
+            .map(|p| PageInfo {
+                cid: p.cid,
+                page: p.page,
+                part: p.part,
+                duration: p.duration,
+                ep_id: None,
+                bvid: None,
+            })
+            .collect();
+        if pages.is_empty() {
+            pages.push(PageInfo {
+                cid: data.cid,
+                page: 1,
+                part: data.title.clone(),
+                duration: 0,
+                ep_id: None,
+                bvid: None,
+            });
+        }
+
+        Ok(VideoInfo {
+            bvid: data.bvid,
+            title: data.title,
+            desc: data.desc,
+            cover: data.pic,
+            pubdate: data.pubdate,
+            owner: Owner {
+                name: data.owner.name,
+                face: data.owner.face,
+            },
+            qualities: Vec::new(),
+            audio_qualities: Vec::new(),
+            cid: data.cid,
+            aid: data.aid,
+            pages,
+            season: None,
+        })
+    }
+
+    /// 获取视频列表的第pn页（从1开始）
+    pub async fn get_video_list_page(
+        &self,
+        source: &ParsedInput,
+        pn: u32,
+    ) -> Result<VideoListPage, ApiError> {
+        match source {
+            ParsedInput::Favorite(media_id) => self.get_favorite_page(*media_id, pn).await,
+            ParsedInput::Space(mid) => self.get_space_page(*mid, pn).await,
+            ParsedInput::Collection { mid, season_id } => {
+                self.get_collection_page(*mid, *season_id, pn).await
+            }
+            ParsedInput::Series { mid, series_id } => {
+                self.get_series_page(*mid, *series_id, pn).await
+            }
+            _ => Err("不支持的视频列表类型".into()),
+        }
+    }
+
+    /// 当前登录用户创建的收藏夹
+    pub async fn get_favorite_folders(&self) -> Result<Vec<FavoriteFolder>, ApiError> {
+        let mid = self
+            .user_info
+            .read()
+            .as_ref()
+            .map(|info| info.mid)
+            .ok_or(ApiError::NotLoggedIn)?;
+
+        self.get_favorite_folders_of(mid).await
+    }
+
+    async fn get_favorite_folders_of(&self, mid: u64) -> Result<Vec<FavoriteFolder>, ApiError> {
+        let url = format!(
+            "{}/x/v3/fav/folder/created/list-all?up_mid={}",
+            self.endpoints.api, mid
+        );
+
+        let headers = self.build_headers(true);
+
+        let request = self.client().get(&url).headers(headers);
+        let response: FavFolderListResponse = self.fetch_json(request).await?;
+
+        if response.code != 0 {
+            return Err(ApiError::from_code(
+                response.code,
+                format!("获取收藏夹列表失败: {}", response.message.unwrap_or_else(|| "未知错误".to_string()))
+            ));
+        }
+
+        Ok(response
+            .data
+            .and_then(|d| d.list)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|f| FavoriteFolder {
+                id: f.id,
+                title: f.title,
+                media_count: f.media_count,
+            })
+            .collect())
+    }
+
+    async fn get_favorite_page(&self, media_id: u64, pn: u32) -> Result<VideoListPage, ApiError> {
+        let url = format!(
+            "{}/x/v3/fav/resource/list?media_id={}&pn={}&ps=20&platform=web",
+            self.endpoints.api, media_id, pn
+        );
+
+        let headers = self.build_headers(true);
+
+        let request = self.client().get(&url).headers(headers);
+        let response: FavResourceResponse = self.fetch_json(request).await?;
+
+        if response.code != 0 {
+            return Err(ApiError::from_code(
+                response.code,
+                format!("获取收藏夹内容失败: {}", response.message.unwrap_or_else(|| "未知错误".to_string()))
+            ));
+        }
+
+        let data = response.data.ok_or_else(|| "收藏夹内容为空".to_string())?;
+
+        // 只保留未失效的视频稿件（type=2），音频和合集条目跳过
+        let videos = data
+            .medias
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|m| m.media_type == 2 && m.attr & 1 == 0)
+            .map(|m| VideoInfo {
+                bvid: m.bvid,
+                title: m.title,
+                desc: m.intro,
+                cover: m.cover,
+                pubdate: 0,
+                owner: Owner {
+                    name: m.upper.name,
+                    face: m.upper.face,
+                },
+                qualities: Vec::new(),
+                audio_qualities: Vec::new(),
+                cid: 0,
+                aid: m.id,
+                pages: Vec::new(),
+                season: None,
+            })
+            .collect();
+
+        Ok(VideoListPage {
+            title: data.info.title,
+            videos,
+            total: data.info.media_count,
+            has_more: data.has_more,
+        })
+    }
+
+    async fn get_space_page(&self, mid: u64, pn: u32) -> Result<VideoListPage, ApiError> {
+        let mut params = BTreeMap::new();
+        params.insert("mid".to_string(), mid.to_string());
+        params.insert("pn".to_string(), pn.to_string());
+        params.insert("ps".to_string(), "30".to_string());
+        params.insert("order".to_string(), "pubdate".to_string());
+        // 空间接口的风控校验参数，缺少时容易返回-352
+        params.insert("dm_img_list".to_string(), "[]".to_string());
+        params.insert("dm_img_str".to_string(), "V2ViR0wgMS4wIChPcGVuR0wgRVMgMi4wIENocm9taXVtKQ".to_string());
+        params.insert("dm_cover_img_str".to_string(), "QU5HTEUgKEludGVsLCBJbnRlbChSKSBVSEQgR3JhcGhpY3MgNjMwIERpcmVjdDNEMTEgdnNfNV8wIHBzXzVfMCwgRDNEMTEpR29vZ2xlIEluYy4gKEludGVsKQ".to_string());
+
+        let response_text = self.fetch_signed("/x/space/wbi/arc/search", &params, None).await?;
+        let response: SpaceArcResponse = parse_json(&response_text)?;
+
+        if response.code != 0 {
+            return Err(ApiError::from_code(
+                response.code,
+                format!("获取UP主投稿失败: {}", response.message.unwrap_or_else(|| "未知错误".to_string()))
+            ));
+        }
+
+        let data = response.data.ok_or_else(|| "投稿列表为空".to_string())?;
+
+        let title = data
+            .list
+            .vlist
+            .first()
+            .map(|v| v.author.clone())
+            .unwrap_or_else(|| mid.to_string());
+
+        let videos = data
+            .list
+            .vlist
+            .into_iter()
+            .map(|v| VideoInfo {
+                bvid: v.bvid,
+                title: v.title,
+                desc: v.description,
+                cover: v.pic,
+                pubdate: 0,
+                owner: Owner {
+                    name: v.author,
+                    face: String::new(),
+                },
+                qualities: Vec::new(),
+                audio_qualities: Vec::new(),
+                cid: 0,
+                aid: v.aid,
+                pages: Vec::new(),
+                season: None,
+            })
+            .collect();
+
+        Ok(VideoListPage {
+            title,
+            videos,
+            total: data.page.count,
+            has_more: data.page.pn * data.page.ps < data.page.count,
+        })
+    }
+
+    async fn get_collection_page(
+        &self,
+        mid: u64,
+        season_id: u64,
+        pn: u32,
+    ) -> Result<VideoListPage, ApiError> {
+        let url = format!(
+            "{}/x/polymer/web-space/seasons_archives_list?mid={}&season_id={}&page_num={}&page_size=30&sort_reverse=false",
+            self.endpoints.api, mid, season_id, pn
+        );
+
+        let data = self.get_archive_list(&url).await?;
+        let title = data
+            .meta
+            .as_ref()
+            .map(|m| m.name.clone())
+            .unwrap_or_else(|| season_id.to_string());
 
-        Ok(user_info)
+        Ok(Self::archive_list_to_page(data, title))
     }
 
-    pub async fn download_avatar(&self, url: &str) -> Result<Vec<u8>, String> {
-        if url.is_empty() {
-            return Err("头像URL为空".to_string());
-        }
+    async fn get_series_page(
+        &self,
+        mid: u64,
+        series_id: u64,
+        pn: u32,
+    ) -> Result<VideoListPage, ApiError> {
+        let url = format!(
+            "{}/x/series/archives?mid={}&series_id={}&only_normal=true&sort=desc&pn={}&ps=30",
+            self.endpoints.api, mid, series_id, pn
+        );
 
-        let response = self
-            .client
-            .get(url)
-            .send()
-            .await
-            .map_err(|e| format!("下载头像失败: {}", e))?;
+        let data = self.get_archive_list(&url).await?;
 
-        let bytes = response
-            .bytes()
-            .await
-            .map_err(|e| format!("读取头像数据失败: {}", e))?;
+        // 系列的稿件列表不带名称，单独查询一次
+        let title = match self.get_series_name(series_id).await {
+            Ok(name) => name,
+            Err(_) => series_id.to_string(),
+        };
 
-        Ok(bytes.to_vec())
+        Ok(Self::archive_list_to_page(data, title))
     }
 
-    pub async fn get_video_info(&self, input: &str) -> Result<VideoInfo, String> {
-        let bvid = self.extract_bvid(input).await?;
-
+    async fn get_series_name(&self, series_id: u64) -> Result<String, ApiError> {
         let url = format!(
-            "https://api.bilibili.com/x/web-interface/view?bvid={}",
-            bvid
+            "{}/x/series/series?series_id={}",
+            self.endpoints.api, series_id
         );
 
-        let headers = self.build_headers(false);
+        let headers = self.build_headers(true);
 
-        let response = self
-            .client
-            .get(&url)
-            .headers(headers)
-            .send()
-            .await
-            .map_err(|e| format!("请求失败: {}", e))?
-            .json::<BiliVideoResponse>()
-            .await
-            .map_err(|e| format!("解析响应失败: {}", e))?;
+        let request = self.client().get(&url).headers(headers);
+        let response: SeriesInfoResponse = self.fetch_json(request).await?;
 
         if response.code != 0 {
-            return Err(format!(
-                "API返回错误: code={}, message={}",
-                response.code,
-                response.message.unwrap_or_else(|| "未知错误".to_string())
-            ));
+            return Err(ApiError::from_code(response.code, "获取系列信息失败"));
         }
 
-        let data = response.data.ok_or_else(|| "视频信息为空".to_string())?;
+        response
+            .data
+            .map(|d| d.meta.name)
+            .ok_or_else(|| "系列信息为空".into())
+    }
 
-        // 尝试预加载 Keys
-        let _ = self.update_wbi_keys().await;
+    async fn get_archive_list(&self, url: &str) -> Result<ArchiveListData, ApiError> {
+        let headers = self.build_headers(true);
 
-        let qualities = self.get_available_qualities(&bvid, data.cid).await?;
+        let request = self.client().get(url).headers(headers);
+        let response: ArchiveListResponse = self.fetch_json(request).await?;
 
-        Ok(VideoInfo {
-            bvid: data.bvid,
-            title: data.title,
-            desc: data.desc,
-            cover: data.pic,
-            owner: Owner {
-                name: data.owner.name,
-                face: data.owner.face,
-            },
-            qualities,
-            cid: data.cid,
-            aid: data.aid,
-        })
+        if response.code != 0 {
+            return Err(ApiError::from_code(
+                response.code,
+                format!("获取视频列表失败: {}", response.message.unwrap_or_else(|| "未知错误".to_string()))
+            ));
+        }
+
+        response.data.ok_or_else(|| "视频列表为空".into())
+    }
+
+    /// 合集/系列的稿件不含UP主信息，批量下载时由get_video_basic_info补全
+    fn archive_list_to_page(data: ArchiveListData, title: String) -> VideoListPage {
+        let has_more = data.page.num * data.page.size < data.page.total;
+        let total = data.page.total;
+
+        let videos = data
+            .archives
+            .into_iter()
+            .map(|a| VideoInfo {
+                bvid: a.bvid,
+                title: a.title,
+                desc: String::new(),
+                cover: a.pic,
+                pubdate: 0,
+                owner: Owner {
+                    name: String::new(),
+                    face: String::new(),
+                },
+                qualities: Vec::new(),
+                audio_qualities: Vec::new(),
+                cid: 0,
+                aid: a.aid,
+                pages: Vec::new(),
+                season: None,
+            })
+            .collect();
+
+        VideoListPage {
+            title,
+            videos,
+            total,
+            has_more,
+        }
     }
 
+    /// 视频的可选画质和音质
     async fn get_available_qualities(
         &self,
         bvid: &str,
         cid: u64,
-    ) -> Result<Vec<QualityInfo>, String> {
-        let keys_opt = self.update_wbi_keys().await.ok();
-
-        let (url, signed_query) = if let Some((img_key, sub_key)) = keys_opt {
-            let mut params = BTreeMap::new();
-            params.insert("bvid".to_string(), bvid.to_string());
-            params.insert("cid".to_string(), cid.to_string());
-            params.insert("qn".to_string(), "80".to_string());
-            params.insert("fnval".to_string(), "4048".to_string());
-            params.insert("fourk".to_string(), "1".to_string());
-            params.insert("try_look".to_string(), "1".to_string());
-
-            let query = Self::encode_wbi(&params, &img_key, &sub_key);
-            (
-                format!("https://api.bilibili.com/x/player/wbi/playurl?{}", query),
-                None::<egui::Key>,
-            )
-        } else {
-            // Fallback
-            (
-                format!(
-                "https://api.bilibili.com/x/player/playurl?bvid={}&cid={}&qn=80&fnval=4048&fourk=1",
-                bvid, cid
-            ),
-                None,
-            )
-        };
-
-        let headers = self.build_headers(true);
-
-        let response = self
-            .client
-            .get(&url)
-            .headers(headers)
-            .send()
-            .await
-            .map_err(|e| format!("请求失败: {}", e))?
-            .json::<PlayUrlResponse>()
-            .await
-            .map_err(|e| format!("解析响应失败: {}", e))?;
+    ) -> Result<(Vec<QualityInfo>, Vec<AudioQualityInfo>), ApiError> {
+        let mut params = BTreeMap::new();
+        params.insert("bvid".to_string(), bvid.to_string());
+        params.insert("cid".to_string(), cid.to_string());
+        params.insert("qn".to_string(), "80".to_string());
+        params.insert("fnval".to_string(), "4048".to_string());
+        params.insert("fourk".to_string(), "1".to_string());
+        params.insert("try_look".to_string(), "1".to_string());
+        // Fallback
+        let fallback = format!(
+            "{}/x/player/playurl?bvid={}&cid={}&qn=80&fnval=4048&fourk=1",
+            self.endpoints.api, bvid, cid
+        );
+        
+        let response_text = self.fetch_signed("/x/player/wbi/playurl", &params, Some(&fallback)).await?;
+        let response: PlayUrlResponse = parse_json(&response_text)?;
 
         if response.code != 0 {
             if response.code == -400 || response.code == -404 {
-                return Ok(vec![
+                return Ok((vec![
                     QualityInfo {
                         id: 32,
                         desc: "480P 清晰".to_string(),
                         is_available: true,
+                        streams: Vec::new(),
                     },
                     QualityInfo {
                         id: 16,
                         desc: "360P 流畅".to_string(),
                         is_available: true,
+                        streams: Vec::new(),
                     },
-                ]);
+                ], Vec::new()));
             }
-            return Err(format!("获取分辨率失败: code={}", response.code));
+            return Err(ApiError::from_code(response.code, "获取分辨率失败"));
         }
 
         let data = response.data.ok_or_else(|| "播放数据为空".to_string())?;
 
+        Ok((self.build_quality_list(&data), Self::build_audio_list(&data)))
+    }
+
+    /// DASH中可选的音轨，按音质从高到低排列
+    fn build_audio_list(data: &PlayUrlData) -> Vec<AudioQualityInfo> {
+        let Some(dash) = &data.dash else {
+            return Vec::new();
+        };
+
+        let mut audio_qualities: Vec<AudioQualityInfo> = dash
+            .all_audio()
+            .map(|a| AudioQualityInfo {
+                id: a.id,
+                desc: match a.id {
+                    AUDIO_HIRES => "Hi-Res 无损".to_string(),
+                    AUDIO_DOLBY => "杜比全景声".to_string(),
+                    30280 => "192K".to_string(),
+                    30232 => "132K".to_string(),
+                    30216 => "64K".to_string(),
+                    id => format!("音质 {}", id),
+                },
+                bandwidth: a.bandwidth,
+            })
+            .collect();
+        audio_qualities.sort_by_key(|a| std::cmp::Reverse(a.id));
+        audio_qualities.dedup_by_key(|a| a.id);
+        audio_qualities
+    }
+
+    /// 某个画质下各编码的DASH视频流
+    fn quality_streams(data: &PlayUrlData, quality_id: u32) -> Vec<StreamInfo> {
+        let Some(dash) = &data.dash else {
+            return Vec::new();
+        };
+
+        dash.video
+            .iter()
+            .filter(|v| v.id == quality_id)
+            .filter_map(|v| {
+                Some(StreamInfo {
+                    codec: VideoCodec::from_codecs(&v.codecs)?,
+                    bandwidth: v.bandwidth,
+                    width: v.width,
+                    height: v.height,
+                    frame_rate: v.frame_rate.clone(),
+                })
+            })
+            .collect()
+    }
+
+    /// 根据playurl返回的accept_quality生成画质列表，并按登录/大会员状态标记可用性
+    fn build_quality_list(&self, data: &PlayUrlData) -> Vec<QualityInfo> {
         // current_quality 是API返回的"推荐"画质，免登录时通常是64
         // 但我们不使用它来判断可用性，因为DASH流中实际包含更高画质
         let _current_quality = data.quality;
@@ -535,7 +1854,7 @@ impl BilibiliApi {
             .user_info
             .read()
             .as_ref()
-            .map_or(false, |info| info.is_vip);
+            .is_some_and(|info| info.is_vip);
 
         let mut qualities = Vec::new();
         // 更新映射表，标记各画质是否需要登录/大会员
@@ -589,6 +1908,7 @@ impl BilibiliApi {
                     desc
                 },
                 is_available,
+                streams: Self::quality_streams(data, quality_id),
             });
         }
 
@@ -597,15 +1917,17 @@ impl BilibiliApi {
                 id: 32,
                 desc: "480P 清晰".to_string(),
                 is_available: true,
+                streams: Vec::new(),
             });
             qualities.push(QualityInfo {
                 id: 16,
                 desc: "360P 流畅".to_string(),
                 is_available: true,
+                streams: Vec::new(),
             });
         }
 
-        Ok(qualities)
+        qualities
     }
 
     // --- 修改：获取实际画质，使用 Wbi ---
@@ -614,49 +1936,28 @@ impl BilibiliApi {
         bvid: &str,
         cid: u64,
         requested_quality: u32,
-    ) -> Result<u32, String> {
-        let keys_opt = self.update_wbi_keys().await.ok();
-
-        let url = if let Some((img_key, sub_key)) = keys_opt {
-            let mut params = BTreeMap::new();
-            params.insert("bvid".to_string(), bvid.to_string());
-            params.insert("cid".to_string(), cid.to_string());
-            params.insert("qn".to_string(), requested_quality.to_string());
-            params.insert("fnval".to_string(), "4048".to_string());
-            params.insert("fourk".to_string(), "1".to_string());
-            params.insert("try_look".to_string(), "1".to_string()); // 关键
-
-            let query = Self::encode_wbi(&params, &img_key, &sub_key);
-            format!("https://api.bilibili.com/x/player/wbi/playurl?{}", query)
-        } else {
-            format!(
-                "https://api.bilibili.com/x/player/playurl?bvid={}&cid={}&qn={}&fnval=4048&fourk=1",
-                bvid, cid, requested_quality
-            )
-        };
-
+    ) -> Result<u32, ApiError> {
+        let mut params = BTreeMap::new();
+        params.insert("bvid".to_string(), bvid.to_string());
+        params.insert("cid".to_string(), cid.to_string());
+        params.insert("qn".to_string(), requested_quality.to_string());
+        params.insert("fnval".to_string(), "4048".to_string());
+        params.insert("fourk".to_string(), "1".to_string());
+        params.insert("try_look".to_string(), "1".to_string()); // 关键
+        let fallback = format!(
+            "{}/x/player/playurl?bvid={}&cid={}&qn={}&fnval=4048&fourk=1",
+            self.endpoints.api, bvid, cid, requested_quality
+        );
+        
         debug_println!("检查实际可获取的画质: {}", requested_quality);
-
-        let headers = self.build_headers(true);
-
-        let response = self
-            .client
-            .get(&url)
-            .headers(headers)
-            .send()
-            .await
-            .map_err(|e| format!("请求失败: {}", e))?;
-
-        let response_text = response
-            .text()
-            .await
-            .map_err(|e| format!("读取响应失败: {}", e))?;
+        
+        let response_text = self.fetch_signed("/x/player/wbi/playurl", &params, Some(&fallback)).await?;
 
         let response: PlayUrlResponse =
             serde_json::from_str(&response_text).map_err(|e| format!("解析JSON失败: {}", e))?;
 
         if response.code != 0 {
-            return Err(format!("获取画质失败: code={}", response.code));
+            return Err(ApiError::from_code(response.code, "获取画质失败"));
         }
 
         let data = response.data.ok_or_else(|| "播放数据为空".to_string())?;
@@ -681,7 +1982,9 @@ impl BilibiliApi {
         bvid: &str,
         cid: u64,
         quality: u32,
-    ) -> Result<(String, String), String> {
+        codecs: &[VideoCodec],
+        audio_quality: u32,
+    ) -> Result<StreamUrls, ApiError> {
         // 保留用户请求的画质，用于后续在DASH流中查找
         let requested_quality = quality;
         // 获取API返回的"官方"画质（通常免登录返回64，但DASH中可能有80）
@@ -689,43 +1992,22 @@ impl BilibiliApi {
         
         debug_println!("请求画质: {}, API返回画质: {}", requested_quality, actual_quality);
 
-        let keys_opt = self.update_wbi_keys().await.ok();
-
         // 使用请求的画质来构造URL，API会返回所有可用的DASH流
-        let url = if let Some((img_key, sub_key)) = keys_opt {
-            let mut params = BTreeMap::new();
-            params.insert("bvid".to_string(), bvid.to_string());
-            params.insert("cid".to_string(), cid.to_string());
-            params.insert("qn".to_string(), requested_quality.to_string()); // 使用请求的画质
-            params.insert("fnval".to_string(), "4048".to_string());
-            params.insert("fourk".to_string(), "1".to_string());
-            params.insert("try_look".to_string(), "1".to_string()); // 关键：免登录高画质
-
-            let query = Self::encode_wbi(&params, &img_key, &sub_key);
-            format!("https://api.bilibili.com/x/player/wbi/playurl?{}", query)
-        } else {
-            format!(
-                "https://api.bilibili.com/x/player/playurl?bvid={}&cid={}&qn={}&fnval=4048&fourk=1&try_look=1",
-                bvid, cid, requested_quality // 使用请求的画质，并添加try_look参数
-            )
-        };
-
+        let mut params = BTreeMap::new();
+        params.insert("bvid".to_string(), bvid.to_string());
+        params.insert("cid".to_string(), cid.to_string());
+        params.insert("qn".to_string(), requested_quality.to_string()); // 使用请求的画质
+        params.insert("fnval".to_string(), "4048".to_string());
+        params.insert("fourk".to_string(), "1".to_string());
+        params.insert("try_look".to_string(), "1".to_string()); // 关键：免登录高画质
+        let fallback = format!(
+            "{}/x/player/playurl?bvid={}&cid={}&qn={}&fnval=4048&fourk=1&try_look=1",
+            self.endpoints.api, bvid, cid, requested_quality // 使用请求的画质，并添加try_look参数
+        );
+        
         debug_println!("请求播放地址，请求画质: {}", requested_quality);
-
-        let headers = self.build_headers(true);
-
-        let response = self
-            .client
-            .get(&url)
-            .headers(headers)
-            .send()
-            .await
-            .map_err(|e| format!("请求失败: {}", e))?;
-
-        let response_text = response
-            .text()
-            .await
-            .map_err(|e| format!("读取响应失败: {}", e))?;
+        
+        let response_text = self.fetch_signed("/x/player/wbi/playurl", &params, Some(&fallback)).await?;
 
         debug_println!(
             "API响应前500字符: {}",
@@ -739,10 +2021,9 @@ impl BilibiliApi {
             if response.code == -400 || response.code == -404 {
                 return self.get_download_urls_fallback(bvid, cid).await;
             }
-            return Err(format!(
-                "获取下载地址失败: code={}, message={}",
+            return Err(ApiError::from_code(
                 response.code,
-                response.message.unwrap_or_else(|| "未知错误".to_string())
+                format!("获取下载地址失败: {}", response.message.unwrap_or_else(|| "未知错误".to_string()))
             ));
         }
 
@@ -750,112 +2031,275 @@ impl BilibiliApi {
             .data
             .ok_or_else(|| "下载地址数据为空".to_string())?;
 
+        Self::select_streams(data, requested_quality, actual_quality, codecs, audio_quality)
+    }
+
+    /// 主地址和备用地址，PCDN节点（域名含xy）放到最后
+    fn mirror_urls(base_url: &str, backup_url: &Option<Vec<String>>) -> Vec<String> {
+        let mut urls = vec![base_url.to_string()];
+        if let Some(backup_url) = backup_url {
+            urls.extend(backup_url.iter().cloned());
+        }
+        urls.sort_by_key(|url| url.contains("xy"));
+        urls
+    }
+
+    /// 从playurl数据中挑选视频流和音频流，同一画质下按codecs的顺序选择编码
+    fn select_streams(
+        data: PlayUrlData,
+        requested_quality: u32,
+        actual_quality: u32,
+        codecs: &[VideoCodec],
+        audio_quality: u32,
+    ) -> Result<StreamUrls, ApiError> {
         if let Some(dash) = data.dash {
             if !dash.video.is_empty() && !dash.audio.is_empty() {
                 // 关键修复：优先使用用户请求的画质，而不是API返回的quality
                 // 因为免登录时API返回quality=64，但DASH中实际有id=80(1080P)的流
-                // 同时优先选择AVC编码（兼容性更好）
-                let video = dash
-                    .video
-                    .iter()
-                    .filter(|v| v.id == requested_quality)
-                    .find(|v| v.codecs.starts_with("avc")) // 优先AVC编码
-                    .or_else(|| dash.video.iter().find(|v| v.id == requested_quality)) // 或任意编码的requested_quality
-                    .or_else(|| dash.video.iter().filter(|v| v.id == actual_quality).find(|v| v.codecs.starts_with("avc")))
-                    .or_else(|| dash.video.iter().find(|v| v.id == actual_quality))
-                    .or_else(|| {
-                        // 最后按画质从高到低找第一个可用的AVC流
-                        let mut sorted: Vec<_> = dash.video.iter()
-                            .filter(|v| v.codecs.starts_with("avc"))
-                            .collect();
-                        sorted.sort_by(|a, b| b.id.cmp(&a.id));
-                        sorted.first().copied()
-                    })
-                    .or_else(|| {
-                        // 如果没有AVC，就选最高画质的任意流
-                        let mut sorted: Vec<_> = dash.video.iter().collect();
-                        sorted.sort_by(|a, b| b.id.cmp(&a.id));
-                        sorted.first().copied()
-                    })
+                let pick = |quality_id: u32| {
+                    let candidates: Vec<&DashVideo> = dash.video.iter().filter(|v| v.id == quality_id).collect();
+                    codecs
+                        .iter()
+                        .find_map(|codec| {
+                            candidates
+                                .iter()
+                                .find(|v| VideoCodec::from_codecs(&v.codecs) == Some(*codec))
+                                .copied()
+                        })
+                        .or_else(|| candidates.first().copied())
+                };
+                let video = pick(requested_quality)
+                    .or_else(|| pick(actual_quality))
+                    // 最后选最高画质
+                    .or_else(|| dash.video.iter().map(|v| v.id).max().and_then(pick))
                     .ok_or_else(|| "没有可用的视频流".to_string())?;
                 
                 debug_println!("选择的视频流: id={}, codec={}, {}x{}", video.id, video.codecs, video.width, video.height);
 
+                // 未指定或不可用时使用码率最高的普通音轨
                 let audio = dash
-                    .audio
-                    .first()
+                    .all_audio()
+                    .find(|a| a.id == audio_quality)
+                    .or_else(|| dash.audio.iter().max_by_key(|a| a.bandwidth))
                     .ok_or_else(|| "没有可用的音频流".to_string())?;
+                
+                debug_println!("选择的音频流: id={}, codec={}", audio.id, audio.codecs);
 
-                let video_url = if video.base_url.contains("xy") {
-                    video
-                        .backup_url
-                        .as_ref()
-                        .and_then(|urls| urls.first())
-                        .unwrap_or(&video.base_url)
-                        .clone()
-                } else {
-                    video.base_url.clone()
-                };
-
-                let audio_url = if audio.base_url.contains("xy") {
-                    audio
-                        .backup_url
-                        .as_ref()
-                        .and_then(|urls| urls.first())
-                        .unwrap_or(&audio.base_url)
-                        .clone()
-                } else {
-                    audio.base_url.clone()
-                };
+                let video_urls = Self::mirror_urls(&video.base_url, &video.backup_url);
+                let audio_urls = Self::mirror_urls(&audio.base_url, &audio.backup_url);
 
-                debug_println!("找到DASH视频URL: {}", video_url);
-                debug_println!("找到DASH音频URL: {}", audio_url);
+                debug_println!("找到DASH视频URL: {:?}", video_urls);
+                debug_println!("找到DASH音频URL: {:?}", audio_urls);
 
-                return Ok((video_url, audio_url));
+                return Ok(StreamUrls {
+                    video: video_urls,
+                    audio: Some(audio_urls),
+                    quality: video.id,
+                    codec: VideoCodec::from_codecs(&video.codecs),
+                    audio_quality: audio.id,
+                });
             }
         }
 
         if let Some(durl) = data.durl {
-            if !durl.is_empty() {
-                let video_url = durl[0].url.clone();
-                debug_println!("找到FLV格式URL: {}", video_url);
-                return Ok((video_url.clone(), video_url));
+            if let Some(first) = durl.first() {
+                let video_urls = Self::mirror_urls(&first.url, &first.backup_url);
+                debug_println!("找到FLV格式URL: {:?}", video_urls);
+                return Ok(StreamUrls {
+                    video: video_urls,
+                    audio: None,
+                    quality: actual_quality,
+                    codec: None,
+                    audio_quality: 0,
+                });
             }
         }
 
-        Err("无法获取下载地址，可能需要登录或视频不可用".to_string())
+        Err("无法获取下载地址，可能需要登录或视频不可用".into())
+    }
+
+    async fn get_season_id_by_media(&self, media_id: u64) -> Result<u64, ApiError> {
+        let url = format!(
+            "{}/pgc/review/user?media_id={}",
+            self.endpoints.api, media_id
+        );
+
+        let headers = self.build_headers(true);
+
+        let request = self.client().get(&url).headers(headers);
+        let response: PgcMediaResponse = self.fetch_json(request).await?;
+
+        if response.code != 0 {
+            return Err(ApiError::from_code(
+                response.code,
+                format!("获取番剧信息失败: {}", response.message.unwrap_or_else(|| "未知错误".to_string()))
+            ));
+        }
+
+        response
+            .result
+            .map(|r| r.media.season_id)
+            .ok_or_else(|| "番剧信息为空".into())
+    }
+
+    /// 获取番剧/影视信息，season_id和ep_id二选一
+    async fn get_bangumi_info(
+        &self,
+        season_id: Option<u64>,
+        ep_id: Option<u64>,
+    ) -> Result<VideoInfo, ApiError> {
+        let url = match (season_id, ep_id) {
+            (_, Some(ep_id)) => format!(
+                "{}/pgc/view/web/season?ep_id={}",
+                self.endpoints.api, ep_id
+            ),
+            (Some(season_id), None) => format!(
+                "{}/pgc/view/web/season?season_id={}",
+                self.endpoints.api, season_id
+            ),
+            (None, None) => return Err("缺少番剧ID".into()),
+        };
+
+        let headers = self.build_headers(true);
+
+        let request = self.client().get(&url).headers(headers);
+        let response: PgcSeasonResponse = self.fetch_json(request).await?;
+
+        if response.code != 0 {
+            return Err(ApiError::from_code(
+                response.code,
+                format!("API返回错误: {}", response.message.unwrap_or_else(|| "未知错误".to_string()))
+            ));
+        }
+
+        let data = response.result.ok_or_else(|| "番剧信息为空".to_string())?;
+
+        if data.episodes.is_empty() {
+            return Err("该番剧暂无可下载的剧集".into());
+        }
+
+        let pages: Vec<PageInfo> = data
+            .episodes
+            .iter()
+            .enumerate()
+            .map(|(i, ep)| PageInfo {
+                cid: ep.cid,
+                page: (i + 1) as u32,
+                part: if ep.long_title.is_empty() {
+                    ep.title.clone()
+                } else {
+                    format!("{} {}", ep.title, ep.long_title)
+                },
+                duration: ep.duration / 1000,
+                ep_id: Some(ep.id),
+                bvid: Some(ep.bvid.clone()).filter(|b| !b.is_empty()),
+            })
+            .collect();
+
+        // 画质列表以目标剧集（或第一集）为准
+        let first = &data.episodes[0];
+        let target = ep_id
+            .and_then(|id| data.episodes.iter().find(|ep| ep.id == id))
+            .unwrap_or(first);
+
+        let (qualities, audio_qualities) = self.get_pgc_qualities(target.id, target.cid).await?;
+
+        let (owner_name, owner_face) = data
+            .up_info
+            .map(|up| (up.uname, up.avatar))
+            .unwrap_or_default();
+
+        Ok(VideoInfo {
+            bvid: target.bvid.clone(),
+            title: data.title,
+            desc: data.evaluate,
+            cover: data.cover,
+            pubdate: target.pub_time,
+            owner: Owner {
+                name: owner_name,
+                face: owner_face,
+            },
+            qualities,
+            audio_qualities,
+            cid: target.cid,
+            aid: target.aid,
+            pages,
+            season: Some(SeasonInfo {
+                season_id: data.season_id,
+                target_ep_id: ep_id,
+            }),
+        })
+    }
+
+    async fn get_pgc_play_data(
+        &self,
+        ep_id: u64,
+        cid: u64,
+        quality: u32,
+    ) -> Result<PlayUrlData, ApiError> {
+        let url = format!(
+            "{}/pgc/player/web/playurl?ep_id={}&cid={}&qn={}&fnval=4048&fourk=1",
+            self.endpoints.api, ep_id, cid, quality
+        );
+
+        debug_println!("请求番剧播放地址: ep_id={}, 画质={}", ep_id, quality);
+
+        let headers = self.build_headers(true);
+
+        let request = self.client().get(&url).headers(headers);
+        let response: PgcPlayUrlResponse = self.fetch_json(request).await?;
+
+        if response.code != 0 {
+            return Err(ApiError::from_code(
+                response.code,
+                format!("获取番剧播放地址失败: {}", response.message.unwrap_or_else(|| "未知错误".to_string()))
+            ));
+        }
+
+        response
+            .result
+            .ok_or_else(|| "播放数据为空".into())
+    }
+
+    async fn get_pgc_qualities(&self, ep_id: u64, cid: u64) -> Result<(Vec<QualityInfo>, Vec<AudioQualityInfo>), ApiError> {
+        let data = self.get_pgc_play_data(ep_id, cid, 80).await?;
+        Ok((self.build_quality_list(&data), Self::build_audio_list(&data)))
+    }
+
+    /// 番剧剧集的下载地址，走PGC playurl接口
+    pub async fn get_pgc_download_urls(
+        &self,
+        ep_id: u64,
+        cid: u64,
+        quality: u32,
+        codecs: &[VideoCodec],
+        audio_quality: u32,
+    ) -> Result<StreamUrls, ApiError> {
+        let data = self.get_pgc_play_data(ep_id, cid, quality).await?;
+        let actual_quality = data.quality;
+        Self::select_streams(data, quality, actual_quality, codecs, audio_quality)
     }
 
     async fn get_download_urls_fallback(
         &self,
         bvid: &str,
         cid: u64,
-    ) -> Result<(String, String), String> {
+    ) -> Result<StreamUrls, ApiError> {
         // Fallback 也可以尝试使用 Wbi，但这里为了保持逻辑简单，保留原来的低画质请求作为最后的救命稻草
         let url = format!(
-            "https://api.bilibili.com/x/player/playurl?bvid={}&cid={}&qn=32&fnval=1",
-            bvid, cid
+            "{}/x/player/playurl?bvid={}&cid={}&qn=32&fnval=1",
+            self.endpoints.api, bvid, cid
         );
 
         debug_println!("尝试获取低质量视频地址: {}", url);
 
         let headers = self.build_headers(false);
 
-        let response = self
-            .client
-            .get(&url)
-            .headers(headers)
-            .send()
-            .await
-            .map_err(|e| format!("请求失败: {}", e))?;
-
-        let response: PlayUrlResponse = response
-            .json()
-            .await
-            .map_err(|e| format!("解析JSON失败: {}", e))?;
+        let request = self.client().get(&url).headers(headers);
+        let response: PlayUrlResponse = self.fetch_json(request).await?;
 
         if response.code != 0 {
-            return Err(format!("获取下载地址失败: code={}", response.code));
+            return Err(ApiError::from_code(response.code, "获取下载地址失败"));
         }
 
         let data = response
@@ -863,40 +2307,45 @@ impl BilibiliApi {
             .ok_or_else(|| "下载地址数据为空".to_string())?;
 
         if let Some(durl) = data.durl {
-            if !durl.is_empty() {
-                let video_url = durl[0].url.clone();
-                debug_println!("找到低质量视频URL: {}", video_url);
-                return Ok((video_url.clone(), video_url));
+            if let Some(first) = durl.first() {
+                let video_urls = Self::mirror_urls(&first.url, &first.backup_url);
+                debug_println!("找到低质量视频URL: {:?}", video_urls);
+                return Ok(StreamUrls {
+                    video: video_urls,
+                    audio: None,
+                    quality: data.quality,
+                    codec: None,
+                    audio_quality: 0,
+                });
             }
         }
 
-        Err("无法获取任何可用的下载地址".to_string())
+        Err("无法获取任何可用的下载地址".into())
     }
 
-    async fn resolve_short_url(&self, short_url: &str) -> Result<String, String> {
+    async fn resolve_short_url(&self, short_url: &str) -> Result<String, ApiError> {
         debug_println!("解析短链接: {}", short_url);
 
-        let client = reqwest::Client::builder()
-            .timeout(std::time::Duration::from_secs(30))
+        let proxy = self.proxy.read().clone();
+        let client = client_builder(proxy.as_deref())?
             .user_agent("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/140.0.0.0 Safari/537.36 Edg/140.0.0.0")
-            .redirect(reqwest::redirect::Policy::none())
             .build()
             .map_err(|e| format!("创建HTTP客户端失败: {}", e))?;
 
-        let mut current_url = short_url.to_string();
+        // 短链接域名换成配置的地址
+        let path = short_url.split_once("b23.tv").map_or("", |(_, path)| path);
+        let mut current_url = format!("{}{}", self.endpoints.short_link, path);
         let mut max_redirects = 10;
 
         while max_redirects > 0 {
-            let response = client
+            let request = client
                 .get(&current_url)
                 .header(
                     "Accept",
                     "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8",
                 )
-                .header("Accept-Language", "zh-CN,zh;q=0.9,en;q=0.8")
-                .send()
-                .await
-                .map_err(|e| format!("请求失败: {}", e))?;
+                .header("Accept-Language", "zh-CN,zh;q=0.9,en;q=0.8");
+            let response = self.send(request).await?;
 
             let status = response.status();
             debug_println!("状态码: {}, URL: {}", status, current_url);
@@ -912,29 +2361,31 @@ impl BilibiliApi {
                     } else if location_str.starts_with("//") {
                         format!("https:{}", location_str)
                     } else {
-                        format!("https://b23.tv{}", location_str)
+                        format!("{}{}", self.endpoints.short_link, location_str)
                     };
 
                     debug_println!("重定向到: {}", current_url);
 
-                    if current_url.contains("bilibili.com/video/") {
+                    if current_url.contains("bilibili.com/video/")
+                        || current_url.contains("bilibili.com/bangumi/")
+                    {
                         debug_println!("找到完整链接: {}", current_url);
                         return Ok(current_url);
                     }
                 } else {
-                    return Err("重定向响应缺少Location header".to_string());
+                    return Err("重定向响应缺少Location header".into());
                 }
             } else if status.is_success() {
                 debug_println!("最终URL: {}", current_url);
                 return Ok(current_url);
             } else {
-                return Err(format!("请求失败，状态码: {}", status));
+                return Err(format!("请求失败，状态码: {}", status).into());
             }
 
             max_redirects -= 1;
         }
 
-        Err("重定向次数过多".to_string())
+        Err("重定向次数过多".into())
     }
 
     fn extract_url_from_text(&self, text: &str) -> Option<String> {
@@ -976,7 +2427,96 @@ impl BilibiliApi {
         None
     }
 
-    pub async fn extract_bvid(&self, input: &str) -> Result<String, String> {
+    /// 从番剧链接或裸ID（ep/ss/md开头）中识别PGC类型
+    fn extract_bangumi_id(input: &str) -> Option<ParsedInput> {
+        lazy_static::lazy_static! {
+            static ref BARE_ID: regex::Regex = regex::Regex::new(r"^(?i)(ep|ss|md)(\d+)$").unwrap();
+            static ref URL_ID: regex::Regex = regex::Regex::new(r"/(ep|ss|md)(\d+)").unwrap();
+        }
+
+        let caps = if input.contains("/bangumi/") {
+            URL_ID.captures(input)?
+        } else {
+            BARE_ID.captures(input)?
+        };
+
+        let id = caps[2].parse::<u64>().ok()?;
+        match caps[1].to_ascii_lowercase().as_str() {
+            "ep" => Some(ParsedInput::Episode(id)),
+            "ss" => Some(ParsedInput::Season(id)),
+            "md" => Some(ParsedInput::Media(id)),
+            _ => None,
+        }
+    }
+
+    /// 识别收藏夹、UP主空间、合集、系列等视频列表链接
+    async fn extract_list_target(&self, url: &str) -> Result<Option<ParsedInput>, ApiError> {
+        lazy_static::lazy_static! {
+            static ref FAV_FID: regex::Regex = regex::Regex::new(r"[?&]fid=(\d+)").unwrap();
+            static ref FAV_SPACE: regex::Regex = regex::Regex::new(r"space\.bilibili\.com/(\d+)/favlist").unwrap();
+            static ref MEDIALIST: regex::Regex = regex::Regex::new(r"(?:/medialist/(?:detail|play)/|/list/)ml(\d+)").unwrap();
+            static ref SID: regex::Regex = regex::Regex::new(r"[?&]sid=(\d+)").unwrap();
+            static ref SPACE_CHANNEL: regex::Regex = regex::Regex::new(r"space\.bilibili\.com/(\d+)/channel/(collectiondetail|seriesdetail)").unwrap();
+            static ref SPACE_LISTS: regex::Regex = regex::Regex::new(r"space\.bilibili\.com/(\d+)/lists/(\d+)").unwrap();
+            static ref SPACE: regex::Regex = regex::Regex::new(r"space\.bilibili\.com/(\d+)").unwrap();
+        }
+
+        // 旧版合集/系列链接: space.bilibili.com/<mid>/channel/collectiondetail?sid=<id>
+        if let Some(caps) = SPACE_CHANNEL.captures(url) {
+            let mid: u64 = caps[1].parse().map_err(|_| "无效的用户ID".to_string())?;
+            let id: u64 = SID
+                .captures(url)
+                .and_then(|c| c[1].parse().ok())
+                .ok_or_else(|| "链接中缺少合集/系列ID".to_string())?;
+            return Ok(Some(if &caps[2] == "collectiondetail" {
+                ParsedInput::Collection { mid, season_id: id }
+            } else {
+                ParsedInput::Series { mid, series_id: id }
+            }));
+        }
+
+        // 新版合集/系列链接: space.bilibili.com/<mid>/lists/<id>?type=season|series
+        if let Some(caps) = SPACE_LISTS.captures(url) {
+            let mid: u64 = caps[1].parse().map_err(|_| "无效的用户ID".to_string())?;
+            let id: u64 = caps[2].parse().map_err(|_| "无效的合集/系列ID".to_string())?;
+            return Ok(Some(if url.contains("type=series") {
+                ParsedInput::Series { mid, series_id: id }
+            } else {
+                ParsedInput::Collection { mid, season_id: id }
+            }));
+        }
+
+        if let Some(caps) = MEDIALIST.captures(url) {
+            if let Ok(media_id) = caps[1].parse() {
+                return Ok(Some(ParsedInput::Favorite(media_id)));
+            }
+        }
+
+        if let Some(caps) = FAV_SPACE.captures(url) {
+            if let Some(fid) = FAV_FID.captures(url).and_then(|c| c[1].parse().ok()) {
+                return Ok(Some(ParsedInput::Favorite(fid)));
+            }
+
+            // 未指定fid时使用该用户的默认收藏夹（列表第一个）
+            let mid: u64 = caps[1].parse().map_err(|_| "无效的用户ID".to_string())?;
+            let folders = self.get_favorite_folders_of(mid).await?;
+            let folder = folders
+                .first()
+                .ok_or_else(|| "该用户没有公开的收藏夹".to_string())?;
+            return Ok(Some(ParsedInput::Favorite(folder.id)));
+        }
+
+        // 其余空间链接一律视为UP主的全部投稿
+        if let Some(caps) = SPACE.captures(url) {
+            if let Ok(mid) = caps[1].parse() {
+                return Ok(Some(ParsedInput::Space(mid)));
+            }
+        }
+
+        Ok(None)
+    }
+
+    pub async fn parse_input(&self, input: &str) -> Result<ParsedInput, ApiError> {
         let input = input.trim();
 
         // 处理包含标题和链接的情况
@@ -999,12 +2539,32 @@ impl BilibiliApi {
             let resolved_url = self.resolve_short_url(&actual_input).await?;
             debug_println!("解析后的URL: {}", resolved_url);
 
+            if let Some(parsed) = Self::extract_bangumi_id(&resolved_url) {
+                return Ok(parsed);
+            }
+
+            if let Some(parsed) = self.extract_list_target(&resolved_url).await? {
+                return Ok(parsed);
+            }
+
             // 从解析后的URL中提取BV号
             if let Some(bvid) = self.extract_bvid_from_url(&resolved_url) {
-                return Ok(bvid);
+                return Ok(ParsedInput::Video(bvid));
             }
 
-            return Err("无法从解析后的URL中提取BV号".to_string());
+            return Err("无法从解析后的URL中提取BV号".into());
+        }
+
+        // 番剧链接或ep/ss/md号
+        if let Some(parsed) = Self::extract_bangumi_id(&actual_input) {
+            return Ok(parsed);
+        }
+
+        // 收藏夹等视频列表
+        if actual_input.contains("bilibili.com") {
+            if let Some(parsed) = self.extract_list_target(&actual_input).await? {
+                return Ok(parsed);
+            }
         }
 
         // 直接是BV号
@@ -1014,22 +2574,21 @@ impl BilibiliApi {
                 .take_while(|c| c.is_alphanumeric())
                 .collect::<String>();
             if bvid.len() >= 10 {
-                return Ok(if bvid.starts_with("bv") {
-                    format!("BV{}", &bvid[2..])
-                } else {
-                    bvid
-                });
+                return Ok(ParsedInput::Video(match bvid.strip_prefix("bv") {
+                    Some(rest) => format!("BV{}", rest),
+                    None => bvid,
+                }));
             }
         }
 
         // 从bilibili.com链接中提取
         if actual_input.contains("bilibili.com") {
             if let Some(bvid) = self.extract_bvid_from_url(&actual_input) {
-                return Ok(bvid);
+                return Ok(ParsedInput::Video(bvid));
             }
         }
 
-        Ok(actual_input)
+        Ok(ParsedInput::Video(actual_input))
     }
 
     fn extract_bvid_from_url(&self, url: &str) -> Option<String> {
@@ -1042,10 +2601,9 @@ impl BilibiliApi {
                     .take_while(|c| c.is_alphanumeric())
                     .collect();
                 if bvid.len() >= 10 {
-                    return Some(if bvid.starts_with("bv") {
-                        format!("BV{}", &bvid[2..])
-                    } else {
-                        bvid
+                    return Some(match bvid.strip_prefix("bv") {
+                        Some(rest) => format!("BV{}", rest),
+                        None => bvid,
                     });
                 }
             }
@@ -1053,40 +2611,28 @@ impl BilibiliApi {
         None
     }
 
-    pub async fn generate_qrcode(&self) -> Result<(String, String), String> {
-        let url = "https://passport.bilibili.com/x/passport-login/web/qrcode/generate";
+    pub async fn generate_qrcode(&self) -> Result<(String, String), ApiError> {
+        let url = format!("{}/x/passport-login/web/qrcode/generate", self.endpoints.passport);
 
-        let response = self
-            .client
-            .get(url)
-            .send()
-            .await
-            .map_err(|e| format!("请求失败: {}", e))?
-            .json::<QrcodeGenerateResponse>()
-            .await
-            .map_err(|e| format!("解析响应失败: {}", e))?;
+        let request = self.client().get(url);
+        let response: QrcodeGenerateResponse = self.fetch_json(request).await?;
 
         if response.code != 0 {
-            return Err(format!("生成二维码失败: code={}", response.code));
+            return Err(ApiError::from_code(response.code, "生成二维码失败"));
         }
 
         let data = response.data.ok_or_else(|| "二维码数据为空".to_string())?;
         Ok((data.url, data.qrcode_key))
     }
 
-    pub async fn poll_qrcode(&self, qrcode_key: &str) -> Result<LoginStatus, String> {
+    pub async fn poll_qrcode(&self, qrcode_key: &str) -> Result<LoginStatus, ApiError> {
         let url = format!(
-            "https://passport.bilibili.com/x/passport-login/web/qrcode/poll?qrcode_key={}",
-            qrcode_key
+            "{}/x/passport-login/web/qrcode/poll?qrcode_key={}",
+            self.endpoints.passport, qrcode_key
         );
 
-        let response = self
-            .client
-            .get(&url)
-            .send()
-            .await
-            .map_err(|e| format!("请求失败: {}", e))?;
-
+        let response = self.send(self.client().get(&url)).await?;
+        
         let headers = response.headers().clone();
         let response_text = response
             .text()
@@ -1097,7 +2643,7 @@ impl BilibiliApi {
             serde_json::from_str(&response_text).map_err(|e| format!("解析JSON失败: {}", e))?;
 
         if response.code != 0 {
-            return Err(format!("轮询二维码失败: code={}", response.code));
+            return Err(ApiError::from_code(response.code, "轮询二维码失败"));
         }
 
         let data = response.data.ok_or_else(|| "轮询数据为空".to_string())?;
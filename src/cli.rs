@@ -0,0 +1,714 @@
+// cli.rs
+// 命令行模式：不创建窗口，直接使用BilibiliApi和DownloadManager，
+// 供NAS、计划任务等无界面环境使用
+use crate::bilibili::{self, BilibiliApi, LoginStatus, PageInfo, VideoCodec, VideoInfo, AUDIO_DOLBY, AUDIO_HIRES};
+use crate::config::{Config, Language};
+use crate::downloader::{DownloadManager, DownloadStatus, DownloadTask};
+use crate::subscription::{self, SubscriptionManager, SubscriptionOptions};
+use parking_lot::RwLock;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::runtime::Runtime;
+
+const USAGE_ZH: &str = "\
+用法: bilibili-down [命令] [参数]
+
+不带参数时启动图形界面。
+
+命令:
+  info <链接>                 显示视频的分P、画质、编码和音质
+  download <链接>... [选项]   下载视频，支持视频、番剧、收藏夹、UP主空间等链接
+  login                      在终端显示二维码扫码登录
+  logout                     退出登录
+  queue [list|run|clear]     查看、继续或清理图形界面保存的下载队列
+  subscribe list             查看订阅
+  subscribe add <链接> [选项] 订阅UP主空间、收藏夹、合集或系列
+  subscribe remove <ID>      删除订阅
+  sync                       检查所有订阅并下载新视频
+  help                       显示本帮助
+  version                    显示版本
+
+download 选项:
+  -q, --quality <画质>        画质ID或名称，如 80、1080P、4K（默认1080P，不可用时使用最高可用画质）
+  -c, --codec <编码>          编码优先顺序，如 hevc,avc,av1（默认使用设置中的顺序）
+  -f, --format <格式>         video 或 mp3（默认video）
+  -a, --audio <音质>          音轨ID，或 hires、dolby（默认码率最高的普通音轨）
+  -p, --parts <分P>           要下载的分P，如 1,3-5（默认全部）
+  -o, --output <目录>         下载目录（默认使用设置中的目录）
+      --danmaku              同时下载弹幕
+      --subtitles            同时下载CC/AI字幕
+
+subscribe add 选项:
+  -q, -f, --danmaku, --subtitles  同 download
+      --interval <分钟>      图形界面中自动检查的间隔（默认60）
+      --all                  同时下载已有视频（默认只下载之后发布的新视频）";
+
+const USAGE_EN: &str = "\
+Usage: bilibili-down [command] [options]
+
+Starts the graphical interface when no arguments are given.
+
+Commands:
+  info <url>                 Show parts, qualities, codecs and audio tracks
+  download <url>... [opts]   Download videos, bangumi, favorites, uploader spaces, etc.
+  login                      Log in by scanning a QR code shown in the terminal
+  logout                     Log out
+  queue [list|run|clear]     List, run or clean up the queue saved by the GUI
+  subscribe list             List subscriptions
+  subscribe add <url> [opts] Subscribe to an uploader space, favorites, collection or series
+  subscribe remove <id>      Remove a subscription
+  sync                       Check all subscriptions and download new videos
+  help                       Show this help
+  version                    Show the version
+
+Download options:
+  -q, --quality <quality>    Quality ID or name, e.g. 80, 1080P, 4K (default 1080P, falls back to the best available)
+  -c, --codec <codecs>       Codec preference, e.g. hevc,avc,av1 (default: settings order)
+  -f, --format <format>      video or mp3 (default video)
+  -a, --audio <audio>        Audio track ID, or hires, dolby (default: best standard track)
+  -p, --parts <parts>        Parts to download, e.g. 1,3-5 (default: all)
+  -o, --output <dir>         Download directory (default: settings)
+      --danmaku              Also download danmaku
+      --subtitles            Also download CC/AI subtitles
+
+Subscribe add options:
+  -q, -f, --danmaku, --subtitles  Same as download
+      --interval <minutes>   Check interval used by the GUI (default 60)
+      --all                  Also download existing videos (default: only new uploads)";
+
+// 画质ID，按从高到低排列，用于按名称解析画质
+const QUALITY_IDS: [u32; 12] = [127, 126, 125, 120, 116, 112, 80, 74, 64, 32, 16, 6];
+
+/// 是否以命令行模式运行。macOS从Finder启动时会带-psn参数，此时仍启动界面
+pub fn is_cli_invocation(args: &[String]) -> bool {
+    args.first().is_some_and(|arg| !arg.starts_with("-psn"))
+}
+
+/// 执行命令，返回进程退出码
+pub fn run(args: Vec<String>) -> i32 {
+    // Release版本是窗口程序，需要附加到启动它的控制台才能输出
+    #[cfg(target_os = "windows")]
+    unsafe {
+        winapi::um::wincon::AttachConsole(winapi::um::wincon::ATTACH_PARENT_PROCESS);
+    }
+
+    let cli = Cli::new();
+    match cli.dispatch(&args) {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("{}: {}", cli.tr("错误", "Error"), e);
+            1
+        }
+    }
+}
+
+struct DownloadOptions {
+    urls: Vec<String>,
+    quality: u32,
+    codecs: Option<Vec<VideoCodec>>,
+    is_mp3: bool,
+    audio_quality: u32,
+    parts: Option<Vec<u32>>,
+    output: Option<PathBuf>,
+    danmaku: bool,
+    subtitles: bool,
+}
+
+impl DownloadOptions {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut options = Self {
+            urls: Vec::new(),
+            quality: 80,
+            codecs: None,
+            is_mp3: false,
+            audio_quality: 0,
+            parts: None,
+            output: None,
+            danmaku: false,
+            subtitles: false,
+        };
+
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            let mut value = || iter.next().ok_or_else(|| format!("{} 缺少参数值", arg));
+            match arg.as_str() {
+                "-q" | "--quality" => options.quality = parse_quality(value()?)?,
+                "-c" | "--codec" => options.codecs = Some(parse_codecs(value()?)?),
+                "-f" | "--format" => {
+                    options.is_mp3 = match value()?.to_lowercase().as_str() {
+                        "video" | "mp4" => false,
+                        "mp3" | "audio" => true,
+                        other => return Err(format!("未知的格式: {}", other)),
+                    }
+                }
+                "-a" | "--audio" => options.audio_quality = parse_audio(value()?)?,
+                "-p" | "--parts" => options.parts = Some(parse_parts(value()?)?),
+                "-o" | "--output" => options.output = Some(PathBuf::from(value()?)),
+                "--danmaku" => options.danmaku = true,
+                "--subtitles" => options.subtitles = true,
+                _ if arg.starts_with('-') => return Err(format!("未知的选项: {}", arg)),
+                _ => options.urls.push(arg.clone()),
+            }
+        }
+
+        if options.urls.is_empty() {
+            return Err("请提供至少一个视频链接".to_string());
+        }
+        Ok(options)
+    }
+}
+
+fn parse_quality(value: &str) -> Result<u32, String> {
+    if let Ok(id) = value.parse() {
+        return Ok(id);
+    }
+    QUALITY_IDS
+        .into_iter()
+        .find(|&id| bilibili::quality_name(id).eq_ignore_ascii_case(value))
+        .ok_or_else(|| format!("未知的画质: {}", value))
+}
+
+fn parse_codecs(value: &str) -> Result<Vec<VideoCodec>, String> {
+    let mut codecs = Vec::new();
+    for name in value.split(',') {
+        let codec = match name.trim().to_lowercase().as_str() {
+            "avc" | "h264" => VideoCodec::Avc,
+            "hevc" | "h265" => VideoCodec::Hevc,
+            "av1" => VideoCodec::Av1,
+            other => return Err(format!("未知的编码: {}", other)),
+        };
+        if !codecs.contains(&codec) {
+            codecs.push(codec);
+        }
+    }
+    // 未列出的编码排在最后
+    for codec in VideoCodec::ALL {
+        if !codecs.contains(&codec) {
+            codecs.push(codec);
+        }
+    }
+    Ok(codecs)
+}
+
+fn parse_audio(value: &str) -> Result<u32, String> {
+    match value.to_lowercase().as_str() {
+        "hires" | "flac" => Ok(AUDIO_HIRES),
+        "dolby" => Ok(AUDIO_DOLBY),
+        other => other.parse().map_err(|_| format!("未知的音质: {}", value)),
+    }
+}
+
+/// 解析 1,3-5 形式的分P列表
+fn parse_parts(value: &str) -> Result<Vec<u32>, String> {
+    let invalid = || format!("无效的分P: {}", value);
+    let mut parts = Vec::new();
+    for item in value.split(',') {
+        match item.trim().split_once('-') {
+            Some((start, end)) => {
+                let start: u32 = start.trim().parse().map_err(|_| invalid())?;
+                let end: u32 = end.trim().parse().map_err(|_| invalid())?;
+                parts.extend(start..=end);
+            }
+            None => parts.push(item.trim().parse().map_err(|_| invalid())?),
+        }
+    }
+    Ok(parts)
+}
+
+fn format_duration(seconds: u64) -> String {
+    if seconds >= 3600 {
+        format!("{}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60)
+    } else {
+        format!("{:02}:{:02}", seconds / 60, seconds % 60)
+    }
+}
+
+/// 列表中显示的短ID，手动编辑过的文件中ID可能不足8个字符
+fn short_id(id: &str) -> &str {
+    id.get(..8).unwrap_or(id)
+}
+
+/// 任务是否已结束：完成、跳过，或失败且不会再自动重试
+fn is_settled(task: &DownloadTask, max_retries: u32) -> bool {
+    match &*task.status.read() {
+        DownloadStatus::Completed | DownloadStatus::Skipped => true,
+        DownloadStatus::Failed(error) => task.retries >= max_retries || !error.is_retryable(),
+        _ => false,
+    }
+}
+
+struct Cli {
+    runtime: Arc<Runtime>,
+    config: Arc<RwLock<Config>>,
+    api: Arc<BilibiliApi>,
+}
+
+impl Cli {
+    fn new() -> Self {
+        let runtime = Arc::new(Runtime::new().unwrap());
+        let config = Arc::new(RwLock::new(Config::load()));
+        let api = Arc::new(BilibiliApi::new());
+        if let Err(e) = api.set_proxy(&config.read().api_proxy) {
+            eprintln!("{}", e);
+        }
+
+        // 已登录时获取用户信息，用于判断大会员画质是否可用
+        let cookies = config.read().cookies.clone();
+        if let Some(cookies) = cookies {
+            runtime.block_on(async {
+                api.set_cookies(&cookies).await;
+                let _ = api.get_user_info().await;
+            });
+        }
+
+        Self { runtime, config, api }
+    }
+
+    fn tr<'a>(&self, zh: &'a str, en: &'a str) -> &'a str {
+        match self.config.read().language {
+            Language::SimplifiedChinese => zh,
+            Language::English => en,
+        }
+    }
+
+    fn dispatch(&self, args: &[String]) -> Result<i32, String> {
+        let Some((command, rest)) = args.split_first() else {
+            println!("{}", self.tr(USAGE_ZH, USAGE_EN));
+            return Ok(0);
+        };
+
+        match command.as_str() {
+            "info" => self.info(rest),
+            "download" | "dl" => self.download(rest),
+            "login" => self.login(),
+            "logout" => {
+                let mut config = self.config.write();
+                config.cookies = None;
+                config.save();
+                println!("{}", self.tr("已退出登录", "Logged out"));
+                Ok(0)
+            }
+            "queue" => self.queue(rest),
+            "subscribe" | "sub" => self.subscribe(rest),
+            "sync" => self.sync(),
+            "help" | "-h" | "--help" => {
+                println!("{}", self.tr(USAGE_ZH, USAGE_EN));
+                Ok(0)
+            }
+            "version" | "-V" | "--version" => {
+                println!("bilibili-down {}", env!("CARGO_PKG_VERSION"));
+                Ok(0)
+            }
+            other => Err(format!("未知的命令: {}，使用 help 查看用法", other)),
+        }
+    }
+
+    /// 创建下载管理器，不会自动继续之前保存的队列
+    fn download_manager(&self) -> Arc<DownloadManager> {
+        let download_path = self.config.read().download_path.clone();
+        Arc::new(DownloadManager::new(
+            download_path,
+            self.runtime.clone(),
+            self.api.clone(),
+            self.config.clone(),
+        ))
+    }
+
+    fn info(&self, args: &[String]) -> Result<i32, String> {
+        let input = args.first().ok_or("请提供视频链接")?;
+        let parsed = self.runtime.block_on(self.api.parse_input(input))?;
+
+        if parsed.is_list() {
+            let videos = self.collect_list(&parsed)?;
+            for (i, video) in videos.iter().enumerate() {
+                println!("{:>4}. {}  {}", i + 1, video.bvid, video.title);
+            }
+            return Ok(0);
+        }
+
+        let info = self.runtime.block_on(self.api.get_parsed_video_info(parsed))?;
+        println!("{}: {}", self.tr("标题", "Title"), info.title);
+        println!("{}: {}", self.tr("UP主", "Uploader"), info.owner.name);
+        println!("BV: {}", info.bvid);
+        if let Some(date) = chrono::DateTime::from_timestamp(info.pubdate, 0).filter(|_| info.pubdate > 0) {
+            println!("{}: {}", self.tr("发布时间", "Published"), date.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M"));
+        }
+
+        println!("\n{} ({}):", self.tr("分P", "Parts"), info.pages.len());
+        for page in &info.pages {
+            println!("  P{:<4} [{}] {}", page.page, format_duration(page.duration), page.part);
+        }
+
+        println!("\n{}:", self.tr("画质", "Qualities"));
+        for quality in &info.qualities {
+            let availability = if quality.is_available { "" } else { self.tr(" (不可用)", " (unavailable)") };
+            println!("  {:<4} {}{}", quality.id, quality.desc, availability);
+            for stream in &quality.streams {
+                println!(
+                    "         {:<11} {}x{} {}fps {:.0}kbps",
+                    stream.codec.name(),
+                    stream.width,
+                    stream.height,
+                    stream.frame_rate,
+                    stream.bandwidth as f64 / 1000.0
+                );
+            }
+        }
+
+        if !info.audio_qualities.is_empty() {
+            println!("\n{}:", self.tr("音质", "Audio"));
+            for audio in &info.audio_qualities {
+                println!("  {:<6} {} ({:.0}kbps)", audio.id, audio.desc, audio.bandwidth as f64 / 1000.0);
+            }
+        }
+
+        Ok(0)
+    }
+
+    /// 获取视频列表的全部视频（只有基本信息）
+    fn collect_list(&self, parsed: &bilibili::ParsedInput) -> Result<Vec<VideoInfo>, String> {
+        let mut videos = Vec::new();
+        let mut pn = 1;
+        loop {
+            let page = self.runtime.block_on(self.api.get_video_list_page(parsed, pn))?;
+            if pn == 1 {
+                println!("{} ({})", page.title, page.total);
+            }
+            videos.extend(page.videos);
+            if !page.has_more {
+                break;
+            }
+            pn += 1;
+        }
+        Ok(videos)
+    }
+
+    fn download(&self, args: &[String]) -> Result<i32, String> {
+        let options = DownloadOptions::parse(args)?;
+
+        if let Some(output) = &options.output {
+            self.config.write().download_path = output.clone();
+        }
+        if options.quality > 80 && self.config.read().cookies.is_none() {
+            eprintln!("{}", self.tr(
+                "提示: 未登录时只能下载1080P及以下画质，可先运行 login 登录",
+                "Note: qualities above 1080P require login, run `login` first",
+            ));
+        }
+
+        let mut tasks = Vec::new();
+        for url in &options.urls {
+            match self.build_tasks(url, &options) {
+                Ok(new_tasks) => tasks.extend(new_tasks),
+                Err(e) => eprintln!("{} {}: {}", self.tr("解析失败", "Failed to parse"), url, e),
+            }
+        }
+        if tasks.is_empty() {
+            return Err("没有可下载的视频".to_string());
+        }
+
+        let manager = self.download_manager();
+        let ids: Vec<String> = tasks.iter().map(|task| task.id.clone()).collect();
+        for task in tasks {
+            manager.add_task(task);
+        }
+
+        let failed = self.wait_for(&manager, &ids);
+        Ok(if failed == 0 { 0 } else { 1 })
+    }
+
+    fn build_tasks(&self, url: &str, options: &DownloadOptions) -> Result<Vec<DownloadTask>, String> {
+        let parsed = self.runtime.block_on(self.api.parse_input(url))?;
+
+        // 列表中的视频只有基本信息，逐个获取分P信息
+        let videos = if parsed.is_list() {
+            let mut videos = Vec::new();
+            for stub in self.collect_list(&parsed)? {
+                match self.runtime.block_on(self.api.get_video_basic_info(&stub.bvid)) {
+                    Ok(video) => videos.push(video),
+                    Err(e) => eprintln!("{} {}: {}", self.tr("获取视频信息失败", "Failed to get video info"), stub.bvid, e),
+                }
+            }
+            videos
+        } else {
+            vec![self.runtime.block_on(self.api.get_parsed_video_info(parsed))?]
+        };
+
+        let mut tasks = Vec::new();
+        for video in &videos {
+            // 和界面一致：通过ep链接解析番剧时只下载该集
+            let target_ep_id = video.season.as_ref().and_then(|s| s.target_ep_id);
+            let pages: Vec<&PageInfo> = video
+                .pages
+                .iter()
+                .filter(|page| match &options.parts {
+                    Some(parts) => parts.contains(&page.page),
+                    None => target_ep_id.is_none() || page.ep_id == target_ep_id,
+                })
+                .collect();
+
+            let is_multi_part = video.pages.len() > 1 || video.season.is_some();
+            for page in pages {
+                let mut task = DownloadTask::for_page(video, page, options.quality, options.is_mp3, is_multi_part);
+                task.apply_config(&self.config.read());
+                if let Some(codecs) = &options.codecs {
+                    task.codecs = codecs.clone();
+                }
+                task.audio_quality = options.audio_quality;
+                task.danmaku = options.danmaku;
+                task.subtitles = options.subtitles;
+                println!("{} {}", self.tr("添加任务:", "Queued:"), task.display_title());
+                tasks.push(task);
+            }
+        }
+        Ok(tasks)
+    }
+
+    /// 打印进度直到所有任务结束，返回失败的任务数
+    fn wait_for(&self, manager: &DownloadManager, ids: &[String]) -> usize {
+        let mut last_lines: HashMap<String, String> = HashMap::new();
+        loop {
+            std::thread::sleep(Duration::from_secs(1));
+
+            let max_retries = self.config.read().max_retries;
+            let tasks: Vec<_> = manager
+                .get_tasks()
+                .into_iter()
+                .filter(|task| ids.contains(&task.read().id))
+                .collect();
+
+            for task in &tasks {
+                let t = task.read();
+                let line = self.status_line(&t);
+                if last_lines.get(&t.id) != Some(&line) {
+                    println!("[{}] {}", t.display_title(), line);
+                    last_lines.insert(t.id.clone(), line);
+                }
+            }
+
+            // 任务被删除时也视为结束
+            if tasks.iter().all(|task| is_settled(&task.read(), max_retries)) {
+                let failed = tasks
+                    .iter()
+                    .filter(|task| !matches!(*task.read().status.read(), DownloadStatus::Completed | DownloadStatus::Skipped))
+                    .count();
+                println!(
+                    "{} {}, {} {}",
+                    self.tr("完成", "Completed"),
+                    tasks.len() - failed,
+                    self.tr("失败", "failed"),
+                    failed
+                );
+                return failed;
+            }
+        }
+    }
+
+    /// 任务状态的一行描述，下载进度按10%取整，避免在日志中刷屏
+    fn status_line(&self, task: &DownloadTask) -> String {
+        match &*task.status.read() {
+            DownloadStatus::Waiting => self.tr("排队中", "Queued").to_string(),
+            DownloadStatus::Downloading { progress, .. } => {
+                format!("{} {}%", self.tr("下载中", "Downloading"), (progress * 10.0).floor() as u32 * 10)
+            }
+            DownloadStatus::Paused => self.tr("已暂停", "Paused").to_string(),
+            DownloadStatus::Merging { .. } => self.tr("合并音视频中", "Merging").to_string(),
+            DownloadStatus::Completed => match &task.output_path {
+                Some(path) => format!("{} {}", self.tr("完成:", "Done:"), path.display()),
+                None => self.tr("完成", "Done").to_string(),
+            },
+            DownloadStatus::Skipped => match &task.output_path {
+                Some(path) => format!("{} {}", self.tr("文件已存在，跳过:", "Skipped, file exists:"), path.display()),
+                None => self.tr("文件已存在，跳过", "Skipped, file exists").to_string(),
+            },
+            DownloadStatus::Failed(error) => format!("{} {}", self.tr("失败:", "Failed:"), error.localized(&self.config.read().language)),
+        }
+    }
+
+    fn login(&self) -> Result<i32, String> {
+        use qrcode::render::unicode::Dense1x2;
+        use qrcode::QrCode;
+
+        let (url, qrcode_key) = self.runtime.block_on(self.api.generate_qrcode())?;
+        let code = QrCode::new(url.as_bytes()).map_err(|e| format!("生成二维码失败: {}", e))?;
+        let image = code
+            .render::<Dense1x2>()
+            .dark_color(Dense1x2::Light)
+            .light_color(Dense1x2::Dark)
+            .build();
+        println!("{}\n", image);
+        println!("{}", self.tr("请使用手机B站APP扫描二维码登录", "Scan the QR code with the Bilibili app"));
+        println!("{}", url);
+
+        let mut scanned = false;
+        loop {
+            std::thread::sleep(Duration::from_secs(2));
+            match self.runtime.block_on(self.api.poll_qrcode(&qrcode_key))? {
+                LoginStatus::Waiting => {}
+                LoginStatus::Scanned => {
+                    if !scanned {
+                        println!("{}", self.tr("已扫码，请在手机上确认", "Scanned, confirm on your phone"));
+                        scanned = true;
+                    }
+                }
+                LoginStatus::Expired => return Err("二维码已过期，请重新运行 login".to_string()),
+                LoginStatus::Success { cookies } => {
+                    {
+                        let mut config = self.config.write();
+                        config.cookies = Some(cookies.clone());
+                        config.save();
+                    }
+                    let user = self.runtime.block_on(async {
+                        self.api.set_cookies(&cookies).await;
+                        self.api.get_user_info().await
+                    });
+                    match user {
+                        Ok(user) => println!("{} {}", self.tr("登录成功:", "Logged in as"), user.name),
+                        Err(_) => println!("{}", self.tr("登录成功", "Logged in")),
+                    }
+                    return Ok(0);
+                }
+            }
+        }
+    }
+
+    fn queue(&self, args: &[String]) -> Result<i32, String> {
+        match args.first().map(String::as_str).unwrap_or("list") {
+            "list" => {
+                let tasks = DownloadManager::saved_tasks();
+                if tasks.is_empty() {
+                    println!("{}", self.tr("队列为空", "The queue is empty"));
+                }
+                for task in &tasks {
+                    println!("{}  {:<12} {}", short_id(&task.id), self.status_line(task), task.display_title());
+                }
+                Ok(0)
+            }
+            "run" => {
+                let manager = self.download_manager();
+                manager.resume_queue();
+                let ids: Vec<String> = manager
+                    .get_tasks()
+                    .iter()
+                    .filter(|task| *task.read().status.read() == DownloadStatus::Waiting)
+                    .map(|task| task.read().id.clone())
+                    .collect();
+                if ids.is_empty() {
+                    println!("{}", self.tr("没有等待中的任务", "No queued tasks"));
+                    return Ok(0);
+                }
+
+                let failed = self.wait_for(&manager, &ids);
+                Ok(if failed == 0 { 0 } else { 1 })
+            }
+            "clear" => {
+                // 只清理已完成的任务，不删除下载好的文件
+                let manager = self.download_manager();
+                let completed: Vec<String> = manager
+                    .get_tasks()
+                    .iter()
+                    .filter(|task| matches!(*task.read().status.read(), DownloadStatus::Completed | DownloadStatus::Skipped))
+                    .map(|task| task.read().id.clone())
+                    .collect();
+                for id in &completed {
+                    manager.cancel_task(id);
+                }
+                println!("{} {}", self.tr("已清理任务:", "Removed tasks:"), completed.len());
+                Ok(0)
+            }
+            other => Err(format!("未知的队列命令: {}", other)),
+        }
+    }
+
+    fn subscribe(&self, args: &[String]) -> Result<i32, String> {
+        let downloads = self.download_manager();
+        let manager = SubscriptionManager::new(self.runtime.clone(), self.api.clone(), downloads.clone(), self.config.clone());
+        match args.first().map(String::as_str).unwrap_or("list") {
+            "list" => {
+                let subscriptions = manager.subscriptions();
+                if subscriptions.is_empty() {
+                    println!("{}", self.tr("还没有订阅", "No subscriptions yet"));
+                }
+                for s in &subscriptions {
+                    let state = if s.enabled { "" } else { self.tr(" (已停用)", " (disabled)") };
+                    println!("{}  {}{}  {}", short_id(&s.id), s.title, state, s.url);
+                }
+                Ok(0)
+            }
+            "add" => {
+                let mut url = None;
+                let mut options = SubscriptionOptions {
+                    quality: 80,
+                    is_mp3: false,
+                    danmaku: false,
+                    subtitles: false,
+                    interval: subscription::DEFAULT_INTERVAL,
+                    download_existing: false,
+                };
+                let mut iter = args[1..].iter();
+                while let Some(arg) = iter.next() {
+                    let mut value = || iter.next().ok_or_else(|| format!("{} 缺少参数值", arg));
+                    match arg.as_str() {
+                        "-q" | "--quality" => options.quality = parse_quality(value()?)?,
+                        "-f" | "--format" => options.is_mp3 = value()?.eq_ignore_ascii_case("mp3"),
+                        "--interval" => options.interval = value()?.parse().map_err(|_| "无效的检查间隔".to_string())?,
+                        "--danmaku" => options.danmaku = true,
+                        "--subtitles" => options.subtitles = true,
+                        "--all" => options.download_existing = true,
+                        _ if arg.starts_with('-') => return Err(format!("未知的选项: {}", arg)),
+                        _ => url = Some(arg.clone()),
+                    }
+                }
+                let url = url.ok_or("请提供订阅链接")?;
+
+                // 下载已有视频时，添加后直接等待下载完成
+                let download_existing = options.download_existing;
+                let subscription = self.runtime.block_on(manager.add(&url, options))?;
+                println!("{} {} ({})", self.tr("已订阅:", "Subscribed:"), subscription.title, short_id(&subscription.id));
+                if download_existing {
+                    return self.sync_with(&manager, &downloads);
+                }
+                Ok(0)
+            }
+            "remove" | "rm" => {
+                let prefix = args.get(1).ok_or("请提供订阅ID")?;
+                let matched: Vec<_> = manager.subscriptions().into_iter().filter(|s| s.id.starts_with(prefix.as_str())).collect();
+                match matched.as_slice() {
+                    [subscription] => {
+                        manager.remove(&subscription.id);
+                        println!("{} {}", self.tr("已删除订阅:", "Removed:"), subscription.title);
+                        Ok(0)
+                    }
+                    [] => Err("订阅不存在".to_string()),
+                    _ => Err("ID匹配到多个订阅，请输入更长的ID".to_string()),
+                }
+            }
+            other => Err(format!("未知的订阅命令: {}", other)),
+        }
+    }
+
+    fn sync(&self) -> Result<i32, String> {
+        let downloads = self.download_manager();
+        let manager = SubscriptionManager::new(self.runtime.clone(), self.api.clone(), downloads.clone(), self.config.clone());
+        self.sync_with(&manager, &downloads)
+    }
+
+    /// 检查所有启用的订阅，等待新视频下载完成
+    fn sync_with(&self, manager: &SubscriptionManager, downloads: &DownloadManager) -> Result<i32, String> {
+        let ids = self.runtime.block_on(manager.sync_all());
+        for s in manager.subscriptions().iter().filter(|s| s.enabled) {
+            match &s.last_error {
+                Some(error) => eprintln!("{}: {}", s.title, error),
+                None => println!("{}: {} {}", s.title, self.tr("新增", "new"), s.last_added),
+            }
+        }
+        if ids.is_empty() {
+            return Ok(0);
+        }
+
+        let failed = self.wait_for(downloads, &ids);
+        Ok(if failed == 0 { 0 } else { 1 })
+    }
+}
@@ -1,4 +1,8 @@
 use serde::{Deserialize, Serialize};
+use crate::backend::aria2;
+use crate::bilibili::VideoCodec;
+use crate::filename;
+use crate::server;
 use std::path::PathBuf;
 use std::fs;
 
@@ -15,13 +19,208 @@ pub enum Language {
     English,
 }
 
+/// 输出文件已存在时的处理方式
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+pub enum CollisionPolicy {
+    /// 跳过下载
+    Skip,
+    /// 在文件名后追加序号
+    #[default]
+    Rename,
+    Overwrite,
+}
+
+/// 下载引擎
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+pub enum DownloadBackendKind {
+    /// 外部aria2c，找不到时使用内置下载器
+    #[default]
+    Aria2,
+    /// 内置的多连接分段下载器
+    Native,
+}
+
+/// 代理服务器，支持 http://、https:// 和 socks5:// 地址
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct ProxyConfig {
+    pub enabled: bool,
+    /// 例如 socks5://127.0.0.1:1080，省略协议时使用 http://
+    pub url: String,
+    #[serde(default)]
+    pub username: String,
+    #[serde(default)]
+    pub password: String,
+}
+
+impl ProxyConfig {
+    /// 带认证信息的代理地址，未启用时返回None
+    pub fn to_url(&self) -> Result<Option<String>, String> {
+        let url = self.url.trim();
+        if !self.enabled || url.is_empty() {
+            return Ok(None);
+        }
+        let url = if url.contains("://") { url.to_string() } else { format!("http://{}", url) };
+        let mut parsed = url::Url::parse(&url).map_err(|e| format!("代理地址无效: {}", e))?;
+        if !matches!(parsed.scheme(), "http" | "https" | "socks5" | "socks5h") {
+            return Err(format!("不支持的代理协议: {}", parsed.scheme()));
+        }
+        if parsed.host_str().is_none() {
+            return Err("代理地址缺少主机名".to_string());
+        }
+        if !self.username.is_empty() {
+            parsed.set_username(&self.username).map_err(|_| "代理用户名无效".to_string())?;
+            parsed.set_password(Some(&self.password)).map_err(|_| "代理密码无效".to_string())?;
+        }
+        Ok(Some(parsed.to_string()))
+    }
+    
+    pub fn is_socks(&self) -> bool {
+        self.enabled && self.url.trim().to_lowercase().starts_with("socks")
+    }
+}
+
+/// 按时段限速，例如 01:00-07:00 不限速。结束时间早于开始时间时跨越午夜
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SpeedRule {
+    /// 开始时间，格式为 HH:MM
+    pub start: String,
+    pub end: String,
+    /// 该时段的整体限速（KB/s），0表示不限速
+    pub limit: u64,
+}
+
+impl SpeedRule {
+    /// 时间格式错误时返回false
+    pub fn contains(&self, time: chrono::NaiveTime) -> bool {
+        let parse = |value: &str| chrono::NaiveTime::parse_from_str(value.trim(), "%H:%M").ok();
+        let (Some(start), Some(end)) = (parse(&self.start), parse(&self.end)) else {
+            return false;
+        };
+        if start <= end {
+            start <= time && time < end
+        } else {
+            time >= start || time < end
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
     pub theme: Theme,
     pub language: Language,
     pub download_threads: u32,
+    #[serde(default)]
+    pub download_backend: DownloadBackendKind,
+    /// 连接已经运行的aria2，不启动也不结束aria2c进程
+    #[serde(default)]
+    pub aria2_external: bool,
+    /// 外部aria2的RPC地址
+    #[serde(default = "default_aria2_rpc_url")]
+    pub aria2_rpc_url: String,
+    /// 自动启动aria2c时优先使用的RPC端口，被占用时自动选择其他端口
+    #[serde(default = "default_aria2_rpc_port")]
+    pub aria2_rpc_port: u16,
+    /// aria2的RPC密钥（--rpc-secret），自动启动时留空则每次随机生成
+    #[serde(default)]
+    pub aria2_secret: String,
     pub download_path: PathBuf,
     pub cookies: Option<String>,
+    /// 请求B站接口使用的代理
+    #[serde(default)]
+    pub api_proxy: ProxyConfig,
+    /// 下载视频和音频文件使用的代理
+    #[serde(default)]
+    pub media_proxy: ProxyConfig,
+    /// 整体下载限速（KB/s），0表示不限速
+    #[serde(default)]
+    pub speed_limit: u64,
+    /// 单个下载的限速（KB/s），0表示不限速
+    #[serde(default)]
+    pub task_speed_limit: u64,
+    /// 按时段限速，匹配的第一条规则代替整体限速
+    #[serde(default)]
+    pub speed_rules: Vec<SpeedRule>,
+    /// 同时进行的下载任务数，其余任务排队等待
+    #[serde(default = "default_max_concurrent_tasks")]
+    pub max_concurrent_tasks: u32,
+    /// 下载失败后自动重试的次数
+    #[serde(default = "default_max_retries")]
+    pub max_retries: u32,
+    /// 视频编码优先顺序，排在前面的优先
+    #[serde(default = "default_codec_preference")]
+    pub codec_preference: Vec<VideoCodec>,
+    /// 弹幕字幕在1080P画面下的字号
+    #[serde(default = "default_danmaku_font_size")]
+    pub danmaku_font_size: u32,
+    /// 弹幕不透明度，0-1
+    #[serde(default = "default_danmaku_opacity")]
+    pub danmaku_opacity: f32,
+    /// 保留弹幕的百分比
+    #[serde(default = "default_danmaku_density")]
+    pub danmaku_density: u32,
+    /// 下载字幕时同时封装为视频的软字幕轨
+    #[serde(default)]
+    pub embed_subtitles: bool,
+    /// 在输出文件中写入元数据、封面和章节
+    #[serde(default = "default_embed_metadata")]
+    pub embed_metadata: bool,
+    /// 输出文件名模板，可以用 / 创建子文件夹
+    #[serde(default = "default_filename_template")]
+    pub filename_template: String,
+    #[serde(default)]
+    pub collision_policy: CollisionPolicy,
+    /// 启用本地HTTP控制接口
+    #[serde(default)]
+    pub api_enabled: bool,
+    #[serde(default = "default_api_port")]
+    pub api_port: u16,
+    /// 本地接口的访问令牌，首次启用时生成
+    #[serde(default)]
+    pub api_token: String,
+}
+
+fn default_max_concurrent_tasks() -> u32 {
+    3
+}
+
+fn default_max_retries() -> u32 {
+    3
+}
+
+fn default_codec_preference() -> Vec<VideoCodec> {
+    VideoCodec::ALL.to_vec()
+}
+
+fn default_danmaku_font_size() -> u32 {
+    48
+}
+
+fn default_danmaku_opacity() -> f32 {
+    0.8
+}
+
+fn default_danmaku_density() -> u32 {
+    100
+}
+
+fn default_embed_metadata() -> bool {
+    true
+}
+
+fn default_filename_template() -> String {
+    filename::DEFAULT_TEMPLATE.to_string()
+}
+
+fn default_api_port() -> u16 {
+    server::DEFAULT_PORT
+}
+
+fn default_aria2_rpc_url() -> String {
+    aria2::DEFAULT_RPC_URL.to_string()
+}
+
+fn default_aria2_rpc_port() -> u16 {
+    aria2::DEFAULT_RPC_PORT
 }
 
 impl Default for Config {
@@ -39,13 +238,47 @@ impl Default for Config {
             theme: Theme::System,
             language: Language::SimplifiedChinese,
             download_threads: 32,
+            download_backend: DownloadBackendKind::default(),
+            aria2_external: false,
+            aria2_rpc_url: default_aria2_rpc_url(),
+            aria2_rpc_port: default_aria2_rpc_port(),
+            aria2_secret: String::new(),
             download_path,
             cookies: None,
+            api_proxy: ProxyConfig::default(),
+            media_proxy: ProxyConfig::default(),
+            speed_limit: 0,
+            task_speed_limit: 0,
+            speed_rules: Vec::new(),
+            max_concurrent_tasks: default_max_concurrent_tasks(),
+            max_retries: default_max_retries(),
+            codec_preference: default_codec_preference(),
+            danmaku_font_size: default_danmaku_font_size(),
+            danmaku_opacity: default_danmaku_opacity(),
+            danmaku_density: default_danmaku_density(),
+            embed_subtitles: false,
+            embed_metadata: default_embed_metadata(),
+            filename_template: default_filename_template(),
+            collision_policy: CollisionPolicy::default(),
+            api_enabled: false,
+            api_port: default_api_port(),
+            api_token: String::new(),
         }
     }
 }
 
 impl Config {
+    /// 当前时段生效的限速规则
+    pub fn active_speed_rule(&self, time: chrono::NaiveTime) -> Option<&SpeedRule> {
+        self.speed_rules.iter().find(|rule| rule.contains(time))
+    }
+    
+    /// 当前的整体限速和单个下载限速（字节/秒），0表示不限速
+    pub fn speed_limits(&self, time: chrono::NaiveTime) -> (u64, u64) {
+        let overall = self.active_speed_rule(time).map_or(self.speed_limit, |rule| rule.limit);
+        (overall * 1024, self.task_speed_limit * 1024)
+    }
+    
     pub fn load() -> Self {
         let config_path = Self::config_path();
         if config_path.exists() {
@@ -73,11 +306,16 @@ impl Config {
     }
     
     fn config_path() -> PathBuf {
+        Self::data_file("config.json")
+    }
+    
+    /// 与config.json放在同一目录下的数据文件
+    pub fn data_file(file_name: &str) -> PathBuf {
         // 优先使用 XDG/AppData 标准配置目录
         if let Some(config_dir) = dirs::config_dir() {
             let app_config = config_dir.join("bilibili-down");
             let _ = fs::create_dir_all(&app_config);
-            return app_config.join("config.json");
+            return app_config.join(file_name);
         }
         
         // 回退：使用可执行文件旁边
@@ -85,6 +323,6 @@ impl Config {
             .unwrap_or_else(|_| PathBuf::from("."))
             .parent()
             .unwrap_or(&PathBuf::from("."))
-            .join("config.json")
+            .join(file_name)
     }
 }
@@ -0,0 +1,330 @@
+// 弹幕解析（XML / protobuf分段）与ASS字幕生成
+use regex::Regex;
+
+lazy_static::lazy_static! {
+    static ref XML_DANMAKU_RE: Regex = Regex::new(r#"<d p="([^"]*)">([^<]*)</d>"#).unwrap();
+}
+
+// ASS画布大小，播放器会按视频实际分辨率缩放
+const PLAY_RES_X: f64 = 1920.0;
+const PLAY_RES_Y: f64 = 1080.0;
+// 滚动弹幕飞过屏幕的时间和顶部/底部弹幕的停留时间（秒）
+const SCROLL_DURATION: f64 = 8.0;
+const FIXED_DURATION: f64 = 4.0;
+// B站默认的弹幕字号
+const DEFAULT_DANMAKU_SIZE: f64 = 25.0;
+
+#[derive(Debug, Clone)]
+pub struct Danmaku {
+    /// 出现时间（秒）
+    pub time: f64,
+    /// 1-3滚动，4底部，5顶部，6逆向，7以上为高级/代码弹幕
+    pub mode: u32,
+    pub font_size: u32,
+    /// RGB颜色
+    pub color: u32,
+    pub content: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct AssOptions {
+    /// 1080P画面下的标准弹幕字号
+    pub font_size: u32,
+    /// 不透明度，0-1
+    pub opacity: f32,
+    /// 保留弹幕的百分比，0-100
+    pub density: u32,
+}
+
+/// 解析XML弹幕，p属性格式：时间,模式,字号,颜色,发送时间,弹幕池,用户hash,dmid
+pub fn parse_xml(xml: &str) -> Vec<Danmaku> {
+    XML_DANMAKU_RE
+        .captures_iter(xml)
+        .filter_map(|caps| {
+            let params: Vec<&str> = caps[1].split(',').collect();
+            if params.len() < 4 {
+                return None;
+            }
+            Some(Danmaku {
+                time: params[0].parse().ok()?,
+                mode: params[1].parse().ok()?,
+                font_size: params[2].parse().ok()?,
+                color: params[3].parse().ok()?,
+                content: unescape_xml(&caps[2]),
+            })
+        })
+        .collect()
+}
+
+fn unescape_xml(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// 解析seg.so返回的DmSegMobileReply，只取需要的字段：
+/// elems = 1 { progress = 2, mode = 3, fontsize = 4, color = 5, content = 7 }
+pub fn parse_segment(data: &[u8]) -> Vec<Danmaku> {
+    let mut danmaku = Vec::new();
+    let mut reader = ProtoReader::new(data);
+    while let Some((field, value)) = reader.next_field() {
+        if let (1, ProtoValue::Bytes(elem)) = (field, value) {
+            if let Some(d) = parse_segment_elem(elem) {
+                danmaku.push(d);
+            }
+        }
+    }
+    danmaku
+}
+
+fn parse_segment_elem(data: &[u8]) -> Option<Danmaku> {
+    let mut d = Danmaku {
+        time: 0.0,
+        mode: 1,
+        font_size: DEFAULT_DANMAKU_SIZE as u32,
+        color: 0xFFFFFF,
+        content: String::new(),
+    };
+
+    let mut reader = ProtoReader::new(data);
+    while let Some((field, value)) = reader.next_field() {
+        match (field, value) {
+            (2, ProtoValue::Varint(progress)) => d.time = progress as f64 / 1000.0,
+            (3, ProtoValue::Varint(mode)) => d.mode = mode as u32,
+            (4, ProtoValue::Varint(size)) => d.font_size = size as u32,
+            (5, ProtoValue::Varint(color)) => d.color = color as u32,
+            (7, ProtoValue::Bytes(content)) => d.content = String::from_utf8_lossy(content).to_string(),
+            _ => {}
+        }
+    }
+
+    if reader.failed || d.content.is_empty() {
+        None
+    } else {
+        Some(d)
+    }
+}
+
+enum ProtoValue<'a> {
+    Varint(u64),
+    Bytes(&'a [u8]),
+    Other,
+}
+
+/// 最小化的protobuf读取器，只支持varint、定长和length-delimited字段
+struct ProtoReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    failed: bool,
+}
+
+impl<'a> ProtoReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0, failed: false }
+    }
+
+    fn read_varint(&mut self) -> Option<u64> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = *self.data.get(self.pos)?;
+            self.pos += 1;
+            value |= ((byte & 0x7F) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Some(value);
+            }
+        }
+        None
+    }
+
+    fn skip(&mut self, len: usize) -> Option<&'a [u8]> {
+        let end = self.pos.checked_add(len)?;
+        let bytes = self.data.get(self.pos..end)?;
+        self.pos = end;
+        Some(bytes)
+    }
+
+    fn next_field(&mut self) -> Option<(u64, ProtoValue<'a>)> {
+        if self.pos >= self.data.len() {
+            return None;
+        }
+
+        let result = self.read_field();
+        if result.is_none() {
+            self.failed = true;
+        }
+        result
+    }
+
+    fn read_field(&mut self) -> Option<(u64, ProtoValue<'a>)> {
+        let key = self.read_varint()?;
+        let value = match key & 0x07 {
+            0 => ProtoValue::Varint(self.read_varint()?),
+            1 => {
+                self.skip(8)?;
+                ProtoValue::Other
+            }
+            2 => {
+                let len = self.read_varint()? as usize;
+                ProtoValue::Bytes(self.skip(len)?)
+            }
+            5 => {
+                self.skip(4)?;
+                ProtoValue::Other
+            }
+            _ => return None,
+        };
+        Some((key >> 3, value))
+    }
+}
+
+/// 估算文字宽度：ASCII按半个字号，其余按一个字号
+fn text_width(text: &str, size: f64) -> f64 {
+    text.chars()
+        .map(|c| if c.is_ascii() { size * 0.5 } else { size })
+        .sum()
+}
+
+fn format_time(seconds: f64) -> String {
+    let centis = (seconds.max(0.0) * 100.0).round() as u64;
+    format!(
+        "{}:{:02}:{:02}.{:02}",
+        centis / 360000,
+        centis / 6000 % 60,
+        centis / 100 % 60,
+        centis % 100
+    )
+}
+
+fn escape_ass(text: &str) -> String {
+    text.replace('\\', "＼")
+        .replace('{', "｛")
+        .replace('}', "｝")
+        .replace('\n', "\\N")
+        .replace('\r', "")
+}
+
+/// 滚动轨道中最后一条弹幕的出现时间和宽度
+#[derive(Clone, Copy)]
+struct ScrollSlot {
+    start: f64,
+    width: f64,
+}
+
+/// 把弹幕排布到滚动/顶部/底部轨道上，放不下的弹幕丢弃
+pub fn to_ass(danmaku: &[Danmaku], options: &AssOptions) -> String {
+    let base_size = options.font_size.max(1) as f64;
+    let lane_height = base_size * 1.2;
+    let lane_count = ((PLAY_RES_Y / lane_height).floor() as usize).max(1);
+    let alpha = ((1.0 - options.opacity.clamp(0.0, 1.0)) * 255.0).round() as u8;
+
+    let mut sorted: Vec<&Danmaku> = danmaku
+        .iter()
+        .filter(|d| d.mode <= 6 && !d.content.trim().is_empty())
+        .collect();
+    sorted.sort_by(|a, b| a.time.total_cmp(&b.time));
+
+    let mut scroll_lanes: Vec<Option<ScrollSlot>> = vec![None; lane_count];
+    let mut top_lanes: Vec<f64> = vec![f64::MIN; lane_count];
+    let mut bottom_lanes: Vec<f64> = vec![f64::MIN; lane_count];
+
+    let mut events = Vec::new();
+    let density = options.density.min(100) as usize;
+    for (index, d) in sorted.into_iter().enumerate() {
+        // 按比例均匀抽取弹幕
+        if index * density / 100 == (index + 1) * density / 100 {
+            continue;
+        }
+
+        let size = base_size * d.font_size as f64 / DEFAULT_DANMAKU_SIZE;
+        let width = text_width(&d.content, size);
+        let start = d.time;
+
+        let (end, position) = match d.mode {
+            4 | 5 => {
+                let lanes = if d.mode == 5 { &mut top_lanes } else { &mut bottom_lanes };
+                let Some(lane) = lanes.iter().position(|&end| end <= start) else {
+                    continue;
+                };
+                lanes[lane] = start + FIXED_DURATION;
+
+                let y = lane as f64 * lane_height;
+                let position = if d.mode == 5 {
+                    format!("\\an8\\pos({:.0},{:.0})", PLAY_RES_X / 2.0, y)
+                } else {
+                    format!("\\an2\\pos({:.0},{:.0})", PLAY_RES_X / 2.0, PLAY_RES_Y - y)
+                };
+                (start + FIXED_DURATION, position)
+            }
+            _ => {
+                // 前一条弹幕已完全进入屏幕，且新弹幕在前一条离开前追不上它
+                let free = |slot: &Option<ScrollSlot>| match slot {
+                    None => true,
+                    Some(prev) => {
+                        let prev_speed = (PLAY_RES_X + prev.width) / SCROLL_DURATION;
+                        let entered = start >= prev.start + prev.width / prev_speed;
+                        let speed = (PLAY_RES_X + width) / SCROLL_DURATION;
+                        let catch_up = start + PLAY_RES_X / speed < prev.start + SCROLL_DURATION;
+                        entered && !catch_up
+                    }
+                };
+                let Some(lane) = scroll_lanes.iter().position(free) else {
+                    continue;
+                };
+                scroll_lanes[lane] = Some(ScrollSlot { start, width });
+
+                let y = lane as f64 * lane_height;
+                let (from, to) = if d.mode == 6 {
+                    (-width, PLAY_RES_X)
+                } else {
+                    (PLAY_RES_X, -width)
+                };
+                (start + SCROLL_DURATION, format!("\\an7\\move({:.0},{:.0},{:.0},{:.0})", from, y, to, y))
+            }
+        };
+
+        let mut tags = position;
+        if (size - base_size).abs() > f64::EPSILON {
+            tags.push_str(&format!("\\fs{:.0}", size));
+        }
+        let color = d.color & 0xFFFFFF;
+        if color != 0xFFFFFF {
+            tags.push_str(&format!(
+                "\\c&H{:02X}{:02X}{:02X}&",
+                color & 0xFF,
+                (color >> 8) & 0xFF,
+                color >> 16
+            ));
+        }
+
+        events.push(format!(
+            "Dialogue: 2,{},{},Danmaku,,0000,0000,0000,,{{{}}}{}",
+            format_time(start),
+            format_time(end),
+            tags,
+            escape_ass(&d.content)
+        ));
+    }
+
+    format!(
+        "[Script Info]\n\
+         ScriptType: v4.00+\n\
+         PlayResX: {res_x}\n\
+         PlayResY: {res_y}\n\
+         ScaledBorderAndShadow: yes\n\
+         \n\
+         [V4+ Styles]\n\
+         Format: Name, Fontname, Fontsize, PrimaryColour, SecondaryColour, OutlineColour, BackColour, Bold, Italic, Underline, StrikeOut, ScaleX, ScaleY, Spacing, Angle, BorderStyle, Outline, Shadow, Alignment, MarginL, MarginR, MarginV, Encoding\n\
+         Style: Danmaku,Microsoft YaHei,{size},&H{alpha:02X}FFFFFF,&H{alpha:02X}FFFFFF,&H{alpha:02X}000000,&H{alpha:02X}000000,1,0,0,0,100,100,0,0,1,1.5,0,7,0,0,0,1\n\
+         \n\
+         [Events]\n\
+         Format: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\n\
+         {events}\n",
+        res_x = PLAY_RES_X,
+        res_y = PLAY_RES_Y,
+        size = options.font_size,
+        alpha = alpha,
+        events = events.join("\n"),
+    )
+}
@@ -1,14 +1,19 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use parking_lot::RwLock;
-use std::collections::HashMap;
+use parking_lot::{Mutex, RwLock};
+use std::collections::{HashMap, HashSet, VecDeque};
 use tokio::runtime::Runtime;
-use crate::bilibili::BilibiliApi;
-use crate::config::Config;
+use crate::bilibili::{self, BilibiliApi, PageInfo, StreamUrls, VideoCodec, VideoInfo, AUDIO_DOLBY, AUDIO_HIRES};
+use crate::config::{CollisionPolicy, Config};
+use crate::danmaku::{self, AssOptions};
+use crate::error::ApiError;
+use crate::filename::{self, TemplateVars};
+use crate::history::{History, HistoryEntry};
+use crate::metadata::MediaMetadata;
+use crate::subtitle::{self, SubtitleFile};
+use crate::backend::{self, DownloadBackend, JobState};
 use std::process::Command;
-use aria2_ws::{Client as Aria2Client, TaskOptions};
-use aria2_ws::response::TaskStatus;
-use serde_json::json;
+use serde::{Deserialize, Serialize};
 
 #[cfg(target_os = "windows")]
 use std::os::windows::process::CommandExt;
@@ -16,20 +21,6 @@ use std::os::windows::process::CommandExt;
 #[cfg(target_os = "windows")]
 const CREATE_NO_WINDOW: u32 = 0x08000000;
 
-#[cfg(target_os = "windows")]
-use winapi::{
-    um::{
-        tlhelp32::{CreateToolhelp32Snapshot, Process32First, Process32Next, PROCESSENTRY32, TH32CS_SNAPPROCESS},
-        processthreadsapi::{OpenProcess, TerminateProcess},
-        handleapi::CloseHandle,
-        winnt::PROCESS_TERMINATE,
-    },
-    shared::minwindef::DWORD,
-};
-
-#[cfg(target_os = "windows")]
-use std::os::windows::ffi::OsStringExt;
-
 #[cfg(debug_assertions)]
 macro_rules! debug_println {
     ($($arg:tt)*) => { println!($($arg)*) }
@@ -48,22 +39,45 @@ macro_rules! debug_eprintln {
     ($($arg:tt)*) => {}
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum DownloadStatus {
     Waiting,
     Downloading { progress: f32, speed: String },
     Paused,
     Merging { progress: f32 },
     Completed,
-    Failed(String),
+    /// 输出文件已存在，按冲突策略跳过下载
+    Skipped,
+    #[serde(deserialize_with = "deserialize_failed")]
+    Failed(ApiError),
+}
+
+/// 兼容旧版tasks.json中保存为文字的失败原因
+fn deserialize_failed<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<ApiError, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Saved {
+        Error(ApiError),
+        Message(String),
+    }
+    
+    Ok(match Saved::deserialize(deserializer)? {
+        Saved::Error(error) => error,
+        Saved::Message(message) => ApiError::Other(message),
+    })
 }
 
 #[derive(Debug, Clone)]
 pub struct DownloadTask {
     pub id: String,
+    pub bvid: String,
     pub title: String,
     pub author: String,
     pub cover: String,
+    /// 视频简介
+    pub description: String,
+    /// 发布时间（秒级时间戳），0表示未知
+    pub pubdate: i64,
     pub quality: u32,
     pub is_mp3: bool,
     pub status: Arc<RwLock<DownloadStatus>>,
@@ -74,15 +88,202 @@ pub struct DownloadTask {
     pub video_gid: Option<String>,
     pub audio_gid: Option<String>,
     pub has_audio: bool,
+    /// 多P视频中对应的分P，单P视频为None
+    pub part: Option<PageInfo>,
+    /// 加入队列的时间（毫秒时间戳），用于保持队列顺序
+    pub created_at: i64,
+    /// 失败后已自动重试的次数
+    pub retries: u32,
+    /// 下次自动重试前等待的秒数，0表示不再自动重试
+    pub retry_delay: u64,
+    /// 视频编码优先顺序
+    pub codecs: Vec<VideoCodec>,
+    /// 音轨ID，0表示码率最高的普通音轨
+    pub audio_quality: u32,
+    /// 同时下载弹幕并转换为ASS字幕
+    pub danmaku: bool,
+    /// 视频时长（秒），0表示未知
+    pub duration: u64,
+    /// 下载CC/AI字幕并保存为SRT和VTT
+    pub subtitles: bool,
+    /// 把字幕作为软字幕轨封装进视频
+    pub embed_subtitles: bool,
+    /// 写入标题、UP主等元数据、封面和章节
+    pub embed_metadata: bool,
+    /// 输出文件名模板
+    pub filename_template: String,
+    pub collision_policy: CollisionPolicy,
+    /// 按模板生成的相对路径（不含扩展名），开始下载时确定
+    pub output_name: Option<PathBuf>,
+    /// 实际下载的画质，获取下载地址后确定，0表示未知
+    pub actual_quality: u32,
+    /// 实际下载的视频编码
+    pub actual_codec: Option<VideoCodec>,
+    /// 实际下载的音轨ID，请求的音轨不存在时为普通音轨，durl格式为0
+    pub actual_audio_quality: u32,
+}
+
+/// 合并时额外封装进输出文件的内容
+#[derive(Default)]
+struct MergeExtras {
+    subtitles: Vec<SubtitleFile>,
+    /// FFMETADATA文件，包含标签和章节
+    metadata: Option<PathBuf>,
+    cover: Option<PathBuf>,
+}
+
+impl MergeExtras {
+    fn is_empty(&self) -> bool {
+        self.subtitles.is_empty() && self.metadata.is_none() && self.cover.is_none()
+    }
+}
+
+/// 写入tasks.json的任务记录
+#[derive(Serialize, Deserialize)]
+struct TaskRecord {
+    id: String,
+    bvid: String,
+    title: String,
+    author: String,
+    cover: String,
+    #[serde(default)]
+    description: String,
+    #[serde(default)]
+    pubdate: i64,
+    quality: u32,
+    is_mp3: bool,
+    cid: u64,
+    part: Option<PageInfo>,
+    video_path: Option<PathBuf>,
+    audio_path: Option<PathBuf>,
+    output_path: Option<PathBuf>,
+    video_gid: Option<String>,
+    audio_gid: Option<String>,
+    has_audio: bool,
+    created_at: i64,
+    #[serde(default)]
+    codecs: Vec<VideoCodec>,
+    #[serde(default)]
+    audio_quality: u32,
+    #[serde(default)]
+    danmaku: bool,
+    #[serde(default)]
+    duration: u64,
+    #[serde(default)]
+    subtitles: bool,
+    #[serde(default)]
+    embed_subtitles: bool,
+    #[serde(default)]
+    embed_metadata: bool,
+    #[serde(default)]
+    filename_template: String,
+    #[serde(default)]
+    collision_policy: CollisionPolicy,
+    #[serde(default)]
+    output_name: Option<PathBuf>,
+    #[serde(default)]
+    actual_quality: u32,
+    #[serde(default)]
+    actual_codec: Option<VideoCodec>,
+    #[serde(default)]
+    actual_audio_quality: u32,
+    status: DownloadStatus,
+}
+
+impl TaskRecord {
+    fn from_task(task: &DownloadTask) -> Self {
+        // 进行中的状态只在运行时有意义，保存为等待，下次启动时继续下载
+        let status = match &*task.status.read() {
+            DownloadStatus::Downloading { .. } | DownloadStatus::Merging { .. } => DownloadStatus::Waiting,
+            status => status.clone(),
+        };
+        
+        Self {
+            id: task.id.clone(),
+            bvid: task.bvid.clone(),
+            title: task.title.clone(),
+            author: task.author.clone(),
+            cover: task.cover.clone(),
+            description: task.description.clone(),
+            pubdate: task.pubdate,
+            quality: task.quality,
+            is_mp3: task.is_mp3,
+            cid: task.cid,
+            part: task.part.clone(),
+            video_path: task.video_path.clone(),
+            audio_path: task.audio_path.clone(),
+            output_path: task.output_path.clone(),
+            video_gid: task.video_gid.clone(),
+            audio_gid: task.audio_gid.clone(),
+            has_audio: task.has_audio,
+            created_at: task.created_at,
+            codecs: task.codecs.clone(),
+            audio_quality: task.audio_quality,
+            danmaku: task.danmaku,
+            duration: task.duration,
+            subtitles: task.subtitles,
+            embed_subtitles: task.embed_subtitles,
+            embed_metadata: task.embed_metadata,
+            filename_template: task.filename_template.clone(),
+            collision_policy: task.collision_policy,
+            output_name: task.output_name.clone(),
+            actual_quality: task.actual_quality,
+            actual_codec: task.actual_codec,
+            actual_audio_quality: task.actual_audio_quality,
+            status,
+        }
+    }
+    
+    fn into_task(self) -> DownloadTask {
+        DownloadTask {
+            id: self.id,
+            bvid: self.bvid,
+            title: self.title,
+            author: self.author,
+            cover: self.cover,
+            description: self.description,
+            pubdate: self.pubdate,
+            quality: self.quality,
+            is_mp3: self.is_mp3,
+            status: Arc::new(RwLock::new(self.status)),
+            video_path: self.video_path,
+            audio_path: self.audio_path,
+            output_path: self.output_path,
+            cid: self.cid,
+            video_gid: self.video_gid,
+            audio_gid: self.audio_gid,
+            has_audio: self.has_audio,
+            part: self.part,
+            created_at: self.created_at,
+            retries: 0,
+            retry_delay: 0,
+            codecs: self.codecs,
+            audio_quality: self.audio_quality,
+            danmaku: self.danmaku,
+            duration: self.duration,
+            subtitles: self.subtitles,
+            embed_subtitles: self.embed_subtitles,
+            embed_metadata: self.embed_metadata,
+            filename_template: self.filename_template,
+            collision_policy: self.collision_policy,
+            output_name: self.output_name,
+            actual_quality: self.actual_quality,
+            actual_codec: self.actual_codec,
+            actual_audio_quality: self.actual_audio_quality,
+        }
+    }
 }
 
 impl DownloadTask {
-    pub fn new(id: String, title: String, author: String, cover: String, quality: u32, is_mp3: bool, cid: u64) -> Self {
+    pub fn new(bvid: String, title: String, author: String, cover: String, quality: u32, is_mp3: bool, cid: u64) -> Self {
         Self {
-            id,
+            id: uuid::Uuid::new_v4().to_string(),
+            bvid,
             title,
             author,
             cover,
+            description: String::new(),
+            pubdate: 0,
             quality,
             is_mp3,
             status: Arc::new(RwLock::new(DownloadStatus::Waiting)),
@@ -93,331 +294,611 @@ impl DownloadTask {
             video_gid: None,
             audio_gid: None,
             has_audio: false,
+            part: None,
+            created_at: chrono::Local::now().timestamp_millis(),
+            retries: 0,
+            retry_delay: 0,
+            codecs: VideoCodec::ALL.to_vec(),
+            audio_quality: 0,
+            danmaku: false,
+            duration: 0,
+            subtitles: false,
+            embed_subtitles: false,
+            embed_metadata: false,
+            filename_template: filename::DEFAULT_TEMPLATE.to_string(),
+            collision_policy: CollisionPolicy::default(),
+            output_name: None,
+            actual_quality: 0,
+            actual_codec: None,
+            actual_audio_quality: 0,
+        }
+    }
+    
+    /// 为稿件的一个分P创建任务，multi_part为true时文件名附带分P信息
+    pub fn for_page(video: &VideoInfo, page: &PageInfo, quality: u32, is_mp3: bool, multi_part: bool) -> Self {
+        let mut task = Self::new(
+            page.bvid.clone().unwrap_or_else(|| video.bvid.clone()),
+            video.title.clone(),
+            video.owner.name.clone(),
+            video.cover.clone(),
+            quality,
+            is_mp3,
+            page.cid,
+        );
+        task.description = video.desc.clone();
+        task.pubdate = video.pubdate;
+        task.duration = page.duration;
+        if multi_part {
+            task.part = Some(page.clone());
+        }
+        task
+    }
+    
+    /// 使用设置中的编码顺序、字幕/元数据封装和文件名选项
+    pub fn apply_config(&mut self, config: &Config) {
+        self.codecs = config.codec_preference.clone();
+        self.embed_subtitles = config.embed_subtitles;
+        self.embed_metadata = config.embed_metadata;
+        self.filename_template = config.filename_template.clone();
+        self.collision_policy = config.collision_policy;
+    }
+    
+    /// 输出文件扩展名。无损和杜比音轨不转码：无损视频封装为MKV，仅音频时保存为FLAC/M4A。
+    /// 获取下载地址后按实际选中的音轨决定
+    pub fn output_extension(&self) -> &'static str {
+        let audio_quality = if self.actual_quality > 0 { self.actual_audio_quality } else { self.audio_quality };
+        match (self.is_mp3, audio_quality) {
+            (true, AUDIO_HIRES) => "flac",
+            (true, AUDIO_DOLBY) => "m4a",
+            (true, _) => "mp3",
+            (false, AUDIO_HIRES) => "mkv",
+            (false, _) => "mp4",
+        }
+    }
+    
+    /// 文件名模板的变量，画质和编码使用实际下载的流
+    fn template_vars(&self, quality: u32, codec: Option<VideoCodec>) -> TemplateVars {
+        TemplateVars {
+            title: self.title.clone(),
+            full_title: self.display_title(),
+            part: self.part.as_ref().map_or(1, |p| p.page),
+            part_title: self.part.as_ref().map_or_else(|| self.title.clone(), |p| p.part.clone()),
+            uploader: self.author.clone(),
+            bvid: self.bvid.clone(),
+            cid: self.cid,
+            quality: bilibili::quality_name(quality),
+            codec: codec.map(|c| c.short_name().to_string()).unwrap_or_default(),
+            date: chrono::DateTime::from_timestamp(self.pubdate, 0)
+                .filter(|_| self.pubdate > 0)
+                .map(|d| d.with_timezone(&chrono::Local).format("%Y-%m-%d").to_string())
+                .unwrap_or_default(),
+        }
+    }
+    
+    /// 输出文件路径（不含扩展名）
+    fn output_base(&self, download_path: &Path) -> PathBuf {
+        match &self.output_name {
+            Some(name) => download_path.join(name),
+            None => download_path.join(filename::sanitize(&self.display_title())),
+        }
+    }
+    
+    /// 用于界面显示的标题，多P时附带分P序号和分P标题
+    pub fn display_title(&self) -> String {
+        match &self.part {
+            Some(part) if part.part != self.title => format!("{} - P{} {}", self.title, part.page, part.part),
+            Some(part) => format!("{} - P{}", self.title, part.page),
+            None => self.title.clone(),
         }
     }
 }
 
 pub struct DownloadManager {
     tasks: Arc<RwLock<HashMap<String, Arc<RwLock<DownloadTask>>>>>,
-    download_path: PathBuf,
     runtime: Arc<Runtime>,
-    bilibili_api: Arc<BilibiliApi>,
     config: Arc<RwLock<Config>>,
-    aria2_client: Arc<RwLock<Option<Aria2Client>>>,
-    aria2_process: Option<std::process::Child>,
+    backend: Arc<dyn DownloadBackend>,
+    store: Arc<TaskStore>,
+    scheduler: Scheduler,
 }
 
-impl DownloadManager {
-    pub fn new(
-        download_path: PathBuf,
-        _threads: u32,
-        runtime: Arc<Runtime>,
-        bilibili_api: Arc<BilibiliApi>,
-        config: Arc<RwLock<Config>>
-    ) -> Self {
-        if !download_path.exists() {
-            let _ = std::fs::create_dir_all(&download_path);
+/// tasks.json的读写。所有保存都经过同一个锁，先写入临时文件再替换，避免写到一半的文件
+struct TaskStore {
+    path: PathBuf,
+    /// 上次写入的内容，没有变化时不再写入
+    last_saved: Mutex<String>,
+}
+
+impl TaskStore {
+    fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            last_saved: Mutex::new(String::new()),
+        }
+    }
+    
+    /// 文件无法解析时改名为tasks.json.bak保留，不直接丢弃
+    fn load(&self) -> Vec<TaskRecord> {
+        let content = match std::fs::read_to_string(&self.path) {
+            Ok(content) => content,
+            Err(_) => return Vec::new(),
+        };
+        match serde_json::from_str(&content) {
+            Ok(records) => records,
+            Err(_e) => {
+                debug_eprintln!("解析任务列表失败: {}", _e);
+                let backup = self.path.with_extension("json.bak");
+                if let Err(_e) = std::fs::rename(&self.path, &backup) {
+                    debug_eprintln!("备份任务列表失败: {}", _e);
+                }
+                Vec::new()
+            }
         }
+    }
+    
+    /// 内容有变化时写入
+    fn save(&self, tasks: &RwLock<HashMap<String, Arc<RwLock<DownloadTask>>>>) {
+        let mut records: Vec<TaskRecord> = tasks.read()
+            .values()
+            .map(|task| TaskRecord::from_task(&task.read()))
+            .collect();
+        records.sort_by_key(|record| record.created_at);
         
-        let mut manager = Self {
-            tasks: Arc::new(RwLock::new(HashMap::new())),
-            download_path,
-            runtime: runtime.clone(),
-            bilibili_api,
-            config,
-            aria2_client: Arc::new(RwLock::new(None)),
-            aria2_process: None,
+        let Ok(content) = serde_json::to_string_pretty(&records) else {
+            return;
         };
+        let mut last_saved = self.last_saved.lock();
+        if content == *last_saved {
+            return;
+        }
         
-        manager.start_aria2();
-        manager.connect_aria2();
+        let temp_path = self.path.with_extension("json.tmp");
+        let result = std::fs::write(&temp_path, &content)
+            .and_then(|_| std::fs::rename(&temp_path, &self.path));
+        match result {
+            Ok(()) => *last_saved = content,
+            Err(_e) => {
+                debug_eprintln!("保存任务列表失败: {}", _e);
+            }
+        }
+    }
+}
+
+/// 自动重试的退避时间：首次5秒，之后每次翻倍，最长5分钟
+const RETRY_BASE_DELAY_SECS: u64 = 5;
+const RETRY_MAX_DELAY_SECS: u64 = 300;
+/// 被风控拦截时从1分钟开始退避
+const RATE_LIMITED_BASE_DELAY_SECS: u64 = 60;
+
+/// 按先进先出顺序启动等待中的任务，同时下载的任务数不超过设置值
+#[derive(Clone)]
+struct Scheduler {
+    runtime: Arc<Runtime>,
+    tasks: Arc<RwLock<HashMap<String, Arc<RwLock<DownloadTask>>>>>,
+    wait_list: Arc<RwLock<VecDeque<String>>>,
+    /// 正在执行下载流程的任务ID
+    active: Arc<RwLock<HashSet<String>>>,
+    /// 未结束的任务已占用的输出路径（不含扩展名），防止同时开始的任务解析出同一个文件名
+    reserved: Arc<Mutex<HashSet<PathBuf>>>,
+    download_path: PathBuf,
+    bilibili_api: Arc<BilibiliApi>,
+    backend: Arc<dyn DownloadBackend>,
+    config: Arc<RwLock<Config>>,
+    history: Arc<History>,
+}
+
+impl Scheduler {
+    /// 加入等待队列末尾
+    fn enqueue(&self, id: &str) {
+        let task = self.tasks.read().get(id).cloned();
+        let Some(task) = task else {
+            return;
+        };
+        *task.read().status.write() = DownloadStatus::Waiting;
         
-        let tasks = manager.tasks.clone();
-        let aria2_client = manager.aria2_client.clone();
-        runtime.spawn(async move {
-            loop {
-                tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
-                Self::update_all_status(tasks.clone(), aria2_client.clone()).await;
+        {
+            let mut wait_list = self.wait_list.write();
+            if !wait_list.iter().any(|waiting| waiting == id) {
+                wait_list.push_back(id.to_string());
             }
-        });
+        }
         
-        manager
+        self.schedule();
     }
     
-    #[cfg(target_os = "windows")]
-    fn kill_processes_by_name(process_name: &str) {
-        unsafe {
-            let snapshot = CreateToolhelp32Snapshot(TH32CS_SNAPPROCESS, 0);
-            if snapshot == winapi::um::handleapi::INVALID_HANDLE_VALUE {
-                return;
-            }
-            
-            let mut process_entry: PROCESSENTRY32 = std::mem::zeroed();
-            process_entry.dwSize = std::mem::size_of::<PROCESSENTRY32>() as DWORD;
-            
-            if Process32First(snapshot, &mut process_entry) != 0 {
-                loop {
-                    let exe_name_wide: Vec<u16> = process_entry.szExeFile
-                        .iter()
-                        .take_while(|&&c| c != 0)
-                        .map(|&c| c as u8 as u16)
-                        .collect();
-                    
-                    let exe_name = std::ffi::OsString::from_wide(&exe_name_wide);
-                    
-                    if let Some(exe_str) = exe_name.to_str() {
-                        if exe_str.eq_ignore_ascii_case(process_name) {
-                            let process_handle = OpenProcess(
-                                PROCESS_TERMINATE,
-                                0,
-                                process_entry.th32ProcessID
-                            );
-                            
-                            if !process_handle.is_null() {
-                                TerminateProcess(process_handle, 101);
-                                CloseHandle(process_handle);
-                                debug_println!("终止进程: {} (PID: {})", exe_str, process_entry.th32ProcessID);
-                            }
-                        }
-                    }
-                    
-                    if Process32Next(snapshot, &mut process_entry) == 0 {
-                        break;
-                    }
-                }
+    fn remove(&self, id: &str) {
+        self.wait_list.write().retain(|waiting| waiting != id);
+    }
+    
+    fn move_to_top(&self, id: &str) {
+        let mut wait_list = self.wait_list.write();
+        if let Some(index) = wait_list.iter().position(|waiting| waiting == id) {
+            if let Some(id) = wait_list.remove(index) {
+                wait_list.push_front(id);
             }
-            
-            CloseHandle(snapshot);
         }
     }
     
-    #[cfg(not(target_os = "windows"))]
-    fn kill_processes_by_name(process_name: &str) {
-        // 使用 pkill 来终止进程
-        let _ = Command::new("pkill")
-            .arg("-f")
-            .arg(process_name)
-            .output();
+    fn position(&self, id: &str) -> Option<usize> {
+        self.wait_list.read().iter().position(|waiting| waiting == id)
     }
     
-    fn start_aria2(&mut self) {
-        let aria2_path = Self::get_aria2_path();
+    /// 有空闲名额时启动队首的任务
+    fn schedule(&self) {
+        let max_tasks = self.config.read().max_concurrent_tasks.max(1) as usize;
         
-        if !aria2_path.exists() {
-            debug_eprintln!("警告: aria2c 未找到！");
-            debug_eprintln!("请下载 aria2c 并放置到: {:?}", aria2_path);
-            return;
+        loop {
+            let running = self.tasks.read()
+                .values()
+                .filter(|task| matches!(
+                    *task.read().status.read(),
+                    DownloadStatus::Downloading { .. } | DownloadStatus::Merging { .. }
+                ))
+                .count();
+            if running >= max_tasks {
+                break;
+            }
+            
+            let Some(id) = self.wait_list.write().pop_front() else {
+                break;
+            };
+            let task = self.tasks.read().get(&id).cloned();
+            if let Some(task) = task {
+                if *task.read().status.read() == DownloadStatus::Waiting {
+                    self.start(task);
+                }
+            }
         }
+    }
+    
+    fn start(&self, task: Arc<RwLock<DownloadTask>>) {
+        let (task_id, video_gid, audio_gid) = {
+            let t = task.read();
+            *t.status.write() = DownloadStatus::Downloading {
+                progress: 0.0,
+                speed: "准备中...".to_string(),
+            };
+            (t.id.clone(), t.video_gid.clone(), t.audio_gid.clone())
+        };
         
-        #[cfg(target_os = "windows")]
-        Self::kill_processes_by_name("aria2c.exe");
-        #[cfg(not(target_os = "windows"))]
-        Self::kill_processes_by_name("aria2c");
-        
-        std::thread::sleep(std::time::Duration::from_millis(500));
-        
-        let mut cmd = Command::new(&aria2_path);
-        cmd.arg("--enable-rpc")
-            .arg("--rpc-listen-all=false")
-            .arg("--rpc-listen-port=6800")
-            .arg("--rpc-allow-origin-all")
-            .arg("--continue=true")
-            .arg("--max-connection-per-server=16")
-            .arg("--split=16")
-            .arg("--min-split-size=1M")
-            .arg("--piece-length=1M")
-            .arg("--allow-piece-length-change=true")
-            .arg("--dir")
-            .arg(&self.download_path)
-            .arg("--check-certificate=false")
-            .arg("--user-agent=Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36");
+        let backend = self.backend.clone();
         
-        #[cfg(target_os = "windows")]
-        {
-            cmd.creation_flags(CREATE_NO_WINDOW);
+        // 暂停过的任务下载流程仍在等待文件下载完成，继续下载器中的任务即可
+        if !self.active.write().insert(task_id.clone()) {
+            self.runtime.spawn(async move {
+                for gid in [video_gid, audio_gid].into_iter().flatten() {
+                    backend.unpause(gid).await;
+                }
+            });
+            return;
         }
         
-        match cmd.spawn() {
-            Ok(child) => {
-                debug_println!("aria2c 启动成功，PID: {:?}", child.id());
-                self.aria2_process = Some(child);
-                std::thread::sleep(std::time::Duration::from_secs(2));
+        let download_path = self.download_path.clone();
+        let bilibili_api = self.bilibili_api.clone();
+        let reserved = self.reserved.clone();
+        let scheduler = self.clone();
+        
+        self.runtime.spawn(async move {
+            DownloadManager::resume_or_restart(task.clone(), download_path, bilibili_api.clone(), backend, reserved).await;
+            scheduler.active.write().remove(&task_id);
+            // 失败的任务重试时沿用原来的文件名，完成、跳过或已被取消时才释放
+            let finished = matches!(*task.read().status.read(), DownloadStatus::Completed | DownloadStatus::Skipped);
+            if finished || !scheduler.tasks.read().contains_key(&task_id) {
+                scheduler.release_output(&task.read());
             }
-            Err(_e) => {
-                debug_eprintln!("无法启动 aria2c: {}", _e);
+            
+            let save_danmaku = {
+                let t = task.read();
+                let completed = *t.status.read() == DownloadStatus::Completed;
+                completed && t.danmaku
+            };
+            if save_danmaku {
+                let options = {
+                    let config = scheduler.config.read();
+                    AssOptions {
+                        font_size: config.danmaku_font_size,
+                        opacity: config.danmaku_opacity,
+                        density: config.danmaku_density,
+                    }
+                };
+                DownloadManager::save_danmaku(&task, &bilibili_api, &options).await;
+            }
+            
+            if *task.read().status.read() == DownloadStatus::Completed {
+                scheduler.record_history(&task.read());
             }
+            
+            scheduler.retry_later(task);
+        });
+    }
+    
+    /// 释放任务占用的输出路径
+    fn release_output(&self, task: &DownloadTask) {
+        if task.output_name.is_some() {
+            self.reserved.lock().remove(&task.output_base(&self.download_path));
         }
     }
     
-    fn connect_aria2(&self) {
-        let aria2_client = self.aria2_client.clone();
-        let runtime = self.runtime.clone();
+    fn record_history(&self, task: &DownloadTask) {
+        let Some(output_path) = task.output_path.clone() else {
+            return;
+        };
+        let size = std::fs::metadata(&output_path).map(|m| m.len()).unwrap_or(0);
+        self.history.record(HistoryEntry {
+            id: uuid::Uuid::new_v4().to_string(),
+            bvid: task.bvid.clone(),
+            cid: task.cid,
+            title: task.display_title(),
+            author: task.author.clone(),
+            ep_id: task.part.as_ref().and_then(|p| p.ep_id),
+            quality: if task.actual_quality > 0 { task.actual_quality } else { task.quality },
+            requested_quality: task.quality,
+            codec: task.actual_codec,
+            is_mp3: task.is_mp3,
+            output_path,
+            size,
+            completed_at: chrono::Local::now().timestamp_millis(),
+        });
+    }
+    
+    /// 失败的任务按退避时间重新排队，重新获取地址后断点续传
+    fn retry_later(&self, task: Arc<RwLock<DownloadTask>>) {
+        let max_retries = self.config.read().max_retries;
+        let (task_id, retries, delay, status_arc) = {
+            let mut t = task.write();
+            t.retry_delay = 0;
+            let error = match &*t.status.read() {
+                DownloadStatus::Failed(error) => error.clone(),
+                _ => return,
+            };
+            // 需要登录、大会员等错误重试也不会成功
+            if t.retries >= max_retries || !error.is_retryable() {
+                return;
+            }
+            
+            t.retries += 1;
+            let base_delay = match error {
+                ApiError::RateLimited(_) => RATE_LIMITED_BASE_DELAY_SECS,
+                _ => RETRY_BASE_DELAY_SECS,
+            };
+            let delay = base_delay.saturating_mul(1 << (t.retries - 1).min(6)).min(RETRY_MAX_DELAY_SECS);
+            debug_println!("任务失败，{}秒后重试 ({}/{}): {}", delay, t.retries, max_retries, error);
+            t.retry_delay = delay;
+            (t.id.clone(), t.retries, delay, t.status.clone())
+        };
+        
+        let scheduler = self.clone();
+        self.runtime.spawn(async move {
+            tokio::time::sleep(tokio::time::Duration::from_secs(delay)).await;
+            // 等待期间被手动重试或删除时不再处理
+            let still_failed = matches!(*status_arc.read(), DownloadStatus::Failed(_))
+                && task.read().retries == retries;
+            if still_failed {
+                scheduler.enqueue(&task_id);
+            }
+        });
+    }
+}
+
+impl DownloadManager {
+    pub fn new(
+        download_path: PathBuf,
+        runtime: Arc<Runtime>,
+        bilibili_api: Arc<BilibiliApi>,
+        config: Arc<RwLock<Config>>
+    ) -> Self {
+        if !download_path.exists() {
+            let _ = std::fs::create_dir_all(&download_path);
+        }
+        
+        let tasks = Arc::new(RwLock::new(HashMap::new()));
+        let backend = backend::create(&config.read(), &runtime, &download_path);
+        if let Ok(proxy) = config.read().media_proxy.to_url() {
+            let _ = backend.set_proxy(proxy);
+        }
+        let scheduler = Scheduler {
+            runtime: runtime.clone(),
+            tasks: tasks.clone(),
+            wait_list: Arc::new(RwLock::new(VecDeque::new())),
+            active: Arc::new(RwLock::new(HashSet::new())),
+            reserved: Arc::new(Mutex::new(HashSet::new())),
+            download_path,
+            bilibili_api,
+            backend: backend.clone(),
+            config: config.clone(),
+            history: Arc::new(History::load()),
+        };
+        
+        let manager = Self {
+            tasks,
+            runtime: runtime.clone(),
+            config,
+            backend,
+            store: Arc::new(TaskStore::new(Config::data_file("tasks.json"))),
+            scheduler,
+        };
+        
+        manager.load_tasks();
         
+        let tasks = manager.tasks.clone();
+        let backend = manager.backend.clone();
+        let store = manager.store.clone();
+        let scheduler = manager.scheduler.clone();
+        let config = manager.config.clone();
         runtime.spawn(async move {
-            let mut retry_count = 0;
-            let max_retries = 10;
-            
+            let mut applied_limits = None;
+            let mut applied_proxy = config.read().media_proxy.clone();
             loop {
-                match Aria2Client::connect("ws://localhost:6800/jsonrpc", None).await {
-                    Ok(client) => {
-                        debug_println!("成功连接到 aria2 RPC");
-                        *aria2_client.write() = Some(client);
-                        break;
-                    }
-                    Err(_e) => {
-                        retry_count += 1;
-                        debug_eprintln!("连接aria2失败 (尝试 {}/{}): {}", retry_count, max_retries, _e);
-                        if retry_count >= max_retries {
-                            debug_eprintln!("无法连接到 aria2 RPC");
-                            break;
+                tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+                // 限速设置或所在时段变化时更新下载器
+                let limits = config.read().speed_limits(chrono::Local::now().time());
+                if applied_limits != Some(limits) && backend.set_speed_limits(limits.0, limits.1, Self::download_ids(&tasks)).await {
+                    applied_limits = Some(limits);
+                }
+                // 代理地址无效时保持原来的设置
+                let proxy = config.read().media_proxy.clone();
+                if proxy != applied_proxy {
+                    if let Ok(url) = proxy.to_url() {
+                        if let Err(_e) = backend.set_proxy(url) {
+                            debug_eprintln!("设置下载代理失败: {}", _e);
                         }
-                        tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
                     }
+                    applied_proxy = proxy;
                 }
+                Self::update_all_status(tasks.clone(), backend.clone()).await;
+                scheduler.schedule();
+                store.save(&tasks);
             }
         });
+        
+        manager
+    }
+    
+    /// 所有任务在下载器中的ID
+    fn download_ids(tasks: &RwLock<HashMap<String, Arc<RwLock<DownloadTask>>>>) -> Vec<String> {
+        tasks
+            .read()
+            .values()
+            .flat_map(|task| {
+                let t = task.read();
+                [t.video_gid.clone(), t.audio_gid.clone()]
+            })
+            .flatten()
+            .collect()
+    }
+    
+    /// 读取tasks.json中保存的任务，不启动下载器
+    pub fn saved_tasks() -> Vec<DownloadTask> {
+        TaskStore::new(Config::data_file("tasks.json"))
+            .load()
+            .into_iter()
+            .map(TaskRecord::into_task)
+            .collect()
+    }
+    
+    fn load_tasks(&self) {
+        let mut tasks = self.tasks.write();
+        for record in self.store.load() {
+            let task = record.into_task();
+            // 未完成的任务继续占用已确定的文件名
+            if task.output_name.is_some() && !matches!(*task.status.read(), DownloadStatus::Completed | DownloadStatus::Skipped) {
+                self.scheduler.reserved.lock().insert(task.output_base(&self.scheduler.download_path));
+            }
+            tasks.insert(task.id.clone(), Arc::new(RwLock::new(task)));
+        }
+        debug_println!("已恢复 {} 个下载任务", tasks.len());
     }
     
     async fn update_all_status(
         tasks: Arc<RwLock<HashMap<String, Arc<RwLock<DownloadTask>>>>>,
-        aria2_client: Arc<RwLock<Option<Aria2Client>>>
+        backend: Arc<dyn DownloadBackend>
     ) {
-        let client = {
-            let guard = aria2_client.read();
-            guard.as_ref().cloned()
-        };
+        let task_list: Vec<Arc<RwLock<DownloadTask>>> = tasks.read().values().cloned().collect();
         
-        if let Some(client) = client {
-            let task_list: Vec<(String, Arc<RwLock<DownloadTask>>)> = {
-                tasks.read().iter().map(|(id, task)| (id.clone(), task.clone())).collect()
+        for task in task_list {
+            let (video_gid, audio_gid, has_audio, current_status) = {
+                let task_read = task.read();
+                let status = task_read.status.read().clone();
+                (
+                    task_read.video_gid.clone(),
+                    task_read.audio_gid.clone(),
+                    task_read.has_audio,
+                    status,
+                )
             };
             
-            for (_id, task) in task_list.iter() {
-                let (video_gid, audio_gid, has_audio) = {
-                    let task_read = task.read();
-                    (
-                        task_read.video_gid.clone(),
-                        task_read.audio_gid.clone(),
-                        task_read.has_audio,
-                    )
-                };
-                
-                let current_status = {
-                    let task_read = task.read();
-                    let status = task_read.status.read().clone();
-                    status
-                };
+            if let DownloadStatus::Downloading { .. } = current_status {
+                let mut video_progress = 0.0;
+                let mut audio_progress = 0.0;
+                let mut total_speed = 0u64;
+                let mut all_complete = true;
+                let mut has_error = false;
+                let mut error_msg = String::new();
                 
-                match current_status {
-                    DownloadStatus::Downloading { .. } => {
-                        let mut video_progress = 0.0;
-                        let mut audio_progress = 0.0;
-                        let mut total_speed = 0u64;
-                        let mut all_complete = true;
-                        let mut has_error = false;
-                        let mut error_msg = String::new();
-                        
-                        if let Some(gid) = video_gid {
-                            match client.tell_status(gid).await {
-                                Ok(status) => {
-                                    let total = status.total_length;
-                                    let completed = status.completed_length;
-                                    
-                                    if total > 0 {
-                                        video_progress = completed as f32 / total as f32;
-                                    }
-                                    
-                                    total_speed += status.download_speed;
-                                    
-                                    match status.status {
-                                        TaskStatus::Complete => {
-                                            video_progress = 1.0;
-                                        }
-                                        TaskStatus::Active | TaskStatus::Waiting => {
-                                            all_complete = false;
-                                        }
-                                        TaskStatus::Error => {
-                                            has_error = true;
-                                            error_msg = status.error_message.unwrap_or_else(|| "视频下载失败".to_string());
-                                        }
-                                        _ => {
-                                            all_complete = false;
-                                        }
-                                    }
+                if let Some(gid) = video_gid {
+                    match backend.status(gid).await {
+                        Some(status) => {
+                            if status.total > 0 {
+                                video_progress = status.completed as f32 / status.total as f32;
+                            }
+                            total_speed += status.speed;
+                            
+                            match status.state {
+                                JobState::Complete => {
+                                    video_progress = 1.0;
                                 }
-                                Err(_) => {
+                                JobState::Error(e) => {
+                                    has_error = true;
+                                    error_msg = format!("视频下载失败: {}", e);
+                                }
+                                _ => {
                                     all_complete = false;
                                 }
                             }
                         }
-                        
-                        if has_audio {
-                            if let Some(gid) = audio_gid {
-                                match client.tell_status(gid).await {
-                                    Ok(status) => {
-                                        let total = status.total_length;
-                                        let completed = status.completed_length;
-                                        
-                                        if total > 0 {
-                                            audio_progress = completed as f32 / total as f32;
-                                        }
-                                        
-                                        total_speed += status.download_speed;
-                                        
-                                        match status.status {
-                                            TaskStatus::Complete => {
-                                                audio_progress = 1.0;
-                                            }
-                                            TaskStatus::Active | TaskStatus::Waiting => {
-                                                all_complete = false;
-                                            }
-                                            TaskStatus::Error => {
-                                                has_error = true;
-                                                error_msg = status.error_message.unwrap_or_else(|| "音频下载失败".to_string());
-                                            }
-                                            _ => {
-                                                all_complete = false;
-                                            }
-                                        }
+                        None => {
+                            all_complete = false;
+                        }
+                    }
+                }
+                
+                if has_audio {
+                    if let Some(gid) = audio_gid {
+                        match backend.status(gid).await {
+                            Some(status) => {
+                                if status.total > 0 {
+                                    audio_progress = status.completed as f32 / status.total as f32;
+                                }
+                                total_speed += status.speed;
+                                
+                                match status.state {
+                                    JobState::Complete => {
+                                        audio_progress = 1.0;
                                     }
-                                    Err(_) => {
+                                    JobState::Error(e) => {
+                                        has_error = true;
+                                        error_msg = format!("音频下载失败: {}", e);
+                                    }
+                                    _ => {
                                         all_complete = false;
                                     }
                                 }
                             }
-                        } else {
-                            audio_progress = 1.0;
-                        }
-                        
-                        let status_arc = {
-                            let task_read = task.read();
-                            task_read.status.clone()
-                        };
-                        
-                        if has_error {
-                            *status_arc.write() = DownloadStatus::Failed(error_msg);
-                        } else if all_complete && video_progress >= 1.0 && audio_progress >= 1.0 {
-                            *status_arc.write() = DownloadStatus::Merging { progress: 0.5 };
-                        } else {
-                            let total_progress = if has_audio {
-                                (video_progress + audio_progress) / 2.0
-                            } else {
-                                video_progress
-                            };
-                            
-                            let speed_str = Self::format_speed(total_speed);
-                            *status_arc.write() = DownloadStatus::Downloading {
-                                progress: total_progress,
-                                speed: speed_str,
-                            };
+                            None => {
+                                all_complete = false;
+                            }
                         }
                     }
-                    _ => {}
+                } else {
+                    audio_progress = 1.0;
+                }
+                
+                let status_arc = {
+                    let task_read = task.read();
+                    task_read.status.clone()
+                };
+                
+                if has_error {
+                    *status_arc.write() = DownloadStatus::Failed(error_msg.into());
+                } else if all_complete && video_progress >= 1.0 && audio_progress >= 1.0 {
+                    *status_arc.write() = DownloadStatus::Merging { progress: 0.5 };
+                } else {
+                    let total_progress = if has_audio {
+                        (video_progress + audio_progress) / 2.0
+                    } else {
+                        video_progress
+                    };
+                    
+                    let speed_str = Self::format_speed(total_speed);
+                    *status_arc.write() = DownloadStatus::Downloading {
+                        progress: total_progress,
+                        speed: speed_str,
+                    };
                 }
             }
         }
     }
-    
+
     fn format_speed(bytes_per_sec: u64) -> String {
         if bytes_per_sec < 1024 {
             format!("{} B/s", bytes_per_sec)
@@ -428,47 +909,6 @@ impl DownloadManager {
         }
     }
     
-    fn get_aria2_path() -> PathBuf {
-        let exe_dir = std::env::current_exe()
-            .unwrap_or_else(|_| PathBuf::from("."))
-            .parent()
-            .unwrap_or(&PathBuf::from("."))
-            .to_path_buf();
-        
-        #[cfg(target_os = "windows")]
-        let aria2_name = "aria2c.exe";
-        #[cfg(not(target_os = "windows"))]
-        let aria2_name = "aria2c";
-        
-        // 先检查 tools 目录
-        let tools_path = exe_dir.join("tools").join(aria2_name);
-        if tools_path.exists() {
-            return tools_path;
-        }
-        
-        // 再检查可执行文件旁边
-        let beside_path = exe_dir.join(aria2_name);
-        if beside_path.exists() {
-            return beside_path;
-        }
-        
-        // 非 Windows 平台：尝试从 PATH 中查找
-        #[cfg(not(target_os = "windows"))]
-        {
-            if let Ok(output) = Command::new("which").arg("aria2c").output() {
-                if output.status.success() {
-                    let path = String::from_utf8_lossy(&output.stdout).trim().to_string();
-                    if !path.is_empty() {
-                        return PathBuf::from(path);
-                    }
-                }
-            }
-        }
-        
-        // 回退到 tools 目录路径
-        tools_path
-    }
-    
     fn get_ffmpeg_path() -> PathBuf {
         let exe_dir = std::env::current_exe()
             .unwrap_or_else(|_| PathBuf::from("."))
@@ -509,6 +949,25 @@ impl DownloadManager {
         tools_path
     }
     
+    /// 上次退出时未完成的任务重新排队
+    pub fn resume_queue(&self) {
+        for task in self.get_tasks() {
+            let (id, status) = {
+                let t = task.read();
+                let status = t.status.read().clone();
+                (t.id.clone(), status)
+            };
+            if status == DownloadStatus::Waiting {
+                self.scheduler.enqueue(&id);
+            }
+        }
+    }
+    
+    /// 已完成下载的历史记录
+    pub fn history(&self) -> Arc<History> {
+        self.scheduler.history.clone()
+    }
+    
     pub fn get_config(&self) -> Arc<RwLock<Config>> {
         self.config.clone()
     }
@@ -516,30 +975,88 @@ impl DownloadManager {
     pub fn add_task(&self, task: DownloadTask) {
         let task_id = task.id.clone();
         let task = Arc::new(RwLock::new(task));
-        self.tasks.write().insert(task_id.clone(), task.clone());
+        self.tasks.write().insert(task_id.clone(), task);
         
-        let download_path = self.download_path.clone();
-        let bilibili_api = self.bilibili_api.clone();
-        let aria2_client = self.aria2_client.clone();
+        self.store.save(&self.tasks);
+        self.scheduler.enqueue(&task_id);
+    }
+    
+    /// 下载器中仍有该任务时直接继续，否则重新获取地址下载（根据控制文件断点续传）
+    async fn resume_or_restart(
+        task: Arc<RwLock<DownloadTask>>,
+        download_path: PathBuf,
+        bilibili_api: Arc<BilibiliApi>,
+        backend: Arc<dyn DownloadBackend>,
+        reserved: Arc<Mutex<HashSet<PathBuf>>>,
+    ) {
+        let gids: Vec<String> = {
+            let t = task.read();
+            match (&t.video_gid, &t.audio_gid) {
+                (Some(video_gid), Some(audio_gid)) if t.has_audio => vec![video_gid.clone(), audio_gid.clone()],
+                (Some(video_gid), _) if !t.has_audio => vec![video_gid.clone()],
+                _ => Vec::new(),
+            }
+        };
         
-        self.runtime.spawn(async move {
-            tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
-            Self::download_task(task, download_path, bilibili_api, aria2_client).await;
-        });
+        if !gids.is_empty() {
+            if backend.wait_ready().await {
+                let mut reusable = true;
+                for gid in &gids {
+                    match backend.status(gid.clone()).await {
+                        Some(status) if !matches!(status.state, JobState::Error(_) | JobState::Removed) => {}
+                        _ => reusable = false,
+                    }
+                }
+                
+                if reusable {
+                    debug_println!("下载器中找到任务，继续下载: {:?}", gids);
+                    for gid in gids {
+                        backend.unpause(gid).await;
+                    }
+                    *task.read().status.write() = DownloadStatus::Downloading {
+                        progress: 0.0,
+                        speed: "恢复中...".to_string(),
+                    };
+                    Self::finish_task(task, download_path, bilibili_api).await;
+                    return;
+                }
+                
+                // 清理失效的任务，避免和重新添加的任务写同一个文件
+                for gid in gids {
+                    backend.remove(gid).await;
+                }
+            }
+            
+            let mut t = task.write();
+            t.video_gid = None;
+            t.audio_gid = None;
+        }
+        
+        Self::download_task(task, download_path, bilibili_api, backend, reserved).await;
     }
     
     async fn download_task(
         task: Arc<RwLock<DownloadTask>>,
         download_path: PathBuf,
         bilibili_api: Arc<BilibiliApi>,
-        aria2_client: Arc<RwLock<Option<Aria2Client>>>,
+        backend: Arc<dyn DownloadBackend>,
+        reserved: Arc<Mutex<HashSet<PathBuf>>>,
     ) {
-        let (id, title, is_mp3, quality, cid) = {
+        let (bvid, _title, quality, cid, ep_id, retries, codecs, audio_quality) = {
             let t = task.read();
-            (t.id.clone(), t.title.clone(), t.is_mp3, t.quality, t.cid)
+            (
+                t.bvid.clone(),
+                t.display_title(),
+                t.quality,
+                t.cid,
+                t.part.as_ref().and_then(|p| p.ep_id),
+                t.retries,
+                t.codecs.clone(),
+                t.audio_quality,
+            )
         };
         
-        debug_println!("开始下载任务: BV={}, 标题={}, 质量={}", id, title, quality);
+        debug_println!("开始下载任务: BV={}, 标题={}, 质量={}", bvid, _title, quality);
         
         *task.write().status.write() = DownloadStatus::Downloading {
             progress: 0.0,
@@ -547,70 +1064,69 @@ impl DownloadManager {
         };
         
         debug_println!("正在获取视频下载地址...");
-        match bilibili_api.get_download_urls(&id, cid, quality).await {
-            Ok((video_url, audio_url)) => {
+        let urls = match ep_id {
+            Some(ep_id) => bilibili_api.get_pgc_download_urls(ep_id, cid, quality, &codecs, audio_quality).await,
+            None => bilibili_api.get_download_urls(&bvid, cid, quality, &codecs, audio_quality).await,
+        };
+        match urls {
+            Ok(StreamUrls { video: mut video_urls, audio: mut audio_urls, quality: actual_quality, codec, audio_quality }) => {
                 debug_println!("成功获取下载地址");
+                {
+                    let mut t = task.write();
+                    t.actual_quality = actual_quality;
+                    t.actual_codec = codec;
+                    t.actual_audio_quality = audio_quality;
+                }
                 
-                let safe_title = Self::sanitize_filename(&title);
-                let video_file = download_path.join(format!("{}_video.m4s", safe_title));
-                let audio_file = download_path.join(format!("{}_audio.m4s", safe_title));
-                let output_file = if is_mp3 {
-                    download_path.join(format!("{}.mp3", safe_title))
-                } else {
-                    download_path.join(format!("{}.mp4", safe_title))
-                };
+                // 重试时轮换镜像，让上次失败的地址排到后面
+                for urls in std::iter::once(&mut video_urls).chain(audio_urls.as_mut()) {
+                    let len = urls.len().max(1);
+                    urls.rotate_left(retries as usize % len);
+                }
                 
-                let has_audio = video_url != audio_url;
-                task.write().has_audio = has_audio;
+                // 首次下载时按模板确定文件名，重试和恢复时沿用
+                if task.read().output_name.is_none() {
+                    let (name, policy, extension) = {
+                        let t = task.read();
+                        let name = filename::render(&t.filename_template, &t.template_vars(actual_quality, codec));
+                        (name, t.collision_policy, t.output_extension())
+                    };
+                    match Self::resolve_collision(&download_path, name, extension, policy, &reserved) {
+                        Some(name) => task.write().output_name = Some(name),
+                        None => {
+                            let existing = Self::with_suffix(&task.read().output_base(&download_path), &format!(".{}", extension));
+                            debug_println!("文件已存在，跳过下载: {:?}", existing);
+                            task.write().output_path = Some(existing);
+                            *task.write().status.write() = DownloadStatus::Skipped;
+                            return;
+                        }
+                    }
+                }
                 
-                let mut retry_count = 0;
-                while aria2_client.read().is_none() && retry_count < 10 {
-                    tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
-                    retry_count += 1;
+                let base = task.read().output_base(&download_path);
+                let output_dir = base.parent().map(Path::to_path_buf).unwrap_or_else(|| download_path.clone());
+                if let Err(e) = std::fs::create_dir_all(&output_dir) {
+                    *task.write().status.write() = DownloadStatus::Failed(format!("创建文件夹失败: {}", e).into());
+                    return;
                 }
+                let video_file = Self::with_suffix(&base, "_video.m4s");
+                let audio_file = Self::with_suffix(&base, "_audio.m4s");
                 
-                let client = {
-                    let guard = aria2_client.read();
-                    guard.as_ref().cloned()
-                };
+                let has_audio = audio_urls.is_some();
+                task.write().has_audio = has_audio;
                 
-                if let Some(client) = client {
-                    let options = TaskOptions {
-                        dir: Some(download_path.to_string_lossy().to_string()),
-                        out: Some(video_file.file_name().unwrap().to_string_lossy().to_string()),
-                        header: Some(vec![
-                            "Referer: https://www.bilibili.com".to_string(),
-                            "User-Agent: Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36".to_string(),
-                            "Accept: */*".to_string(),
-                            "Accept-Language: zh-CN,zh;q=0.9,en;q=0.8".to_string(),
-                            "Origin: https://www.bilibili.com".to_string(),
-                        ]),
-                        split: Some(16),
-                        max_connection_per_server: Some(16),
-                        extra_options: json!({
-                            "min-split-size": "1M",
-                            "piece-length": "1M",
-                            "allow-piece-length-change": "true",
-                            "check-certificate": "false",
-                            "auto-file-renaming": "false",
-                            "allow-overwrite": "true",
-                        }).as_object().unwrap().clone(),
-                        ..Default::default()
-                    };
-                    
-                    debug_println!("添加视频下载任务到aria2...");
-                    match client.add_uri(vec![video_url.clone()], Some(options.clone()), None, None).await {
+                let overwrite = task.read().collision_policy == CollisionPolicy::Overwrite;
+                if backend.wait_ready().await {
+                    debug_println!("添加视频下载任务...");
+                    match backend.add(video_urls, video_file.clone(), overwrite).await {
                         Ok(gid) => {
                             debug_println!("视频下载任务已添加，GID: {}", gid);
                             task.write().video_gid = Some(gid.clone());
                             task.write().video_path = Some(video_file.clone());
                             
-                            if has_audio {
-                                let mut audio_options = options.clone();
-                                audio_options.out = Some(audio_file.file_name().unwrap().to_string_lossy().to_string());
-                                
-                                debug_println!("添加音频下载任务到aria2...");
-                                match client.add_uri(vec![audio_url.clone()], Some(audio_options), None, None).await {
+                            if let Some(audio_urls) = audio_urls {
+                                debug_println!("添加音频下载任务...");
+                                match backend.add(audio_urls, audio_file.clone(), overwrite).await {
                                     Ok(audio_gid) => {
                                         debug_println!("音频下载任务已添加，GID: {}", audio_gid);
                                         task.write().audio_gid = Some(audio_gid.clone());
@@ -618,66 +1134,232 @@ impl DownloadManager {
                                     }
                                     Err(e) => {
                                         debug_eprintln!("添加音频下载失败: {}", e);
-                                        *task.write().status.write() = DownloadStatus::Failed(format!("添加音频下载失败: {}", e));
+                                        *task.write().status.write() = DownloadStatus::Failed(format!("添加音频下载失败: {}", e).into());
                                         return;
                                     }
                                 }
                             }
                             
-                            loop {
-                                tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
-                                
-                                let status = task.read().status.read().clone();
-                                match status {
-                                    DownloadStatus::Merging { .. } => {
-                                        debug_println!("下载完成，开始合并文件...");
-                                        break;
-                                    }
-                                    DownloadStatus::Failed(_) => {
-                                        return;
-                                    }
-                                    _ => {}
+                            // 获取地址期间任务被暂停
+                            if *task.read().status.read() == DownloadStatus::Paused {
+                                let (video_gid, audio_gid) = {
+                                    let t = task.read();
+                                    (t.video_gid.clone(), t.audio_gid.clone())
+                                };
+                                for gid in [video_gid, audio_gid].into_iter().flatten() {
+                                    backend.pause(gid).await;
                                 }
                             }
                             
-                            let merge_success = if has_audio {
-                                Self::merge_audio_video(&video_file, &audio_file, &output_file, is_mp3).await
-                            } else {
-                                std::fs::rename(&video_file, &output_file).is_ok()
-                            };
-                            
-                            if merge_success {
-                                debug_println!("文件处理成功: {:?}", output_file);
-                                let _ = std::fs::remove_file(&video_file);
-                                if has_audio {
-                                    let _ = std::fs::remove_file(&audio_file);
-                                }
-                                
-                                task.write().output_path = Some(output_file);
-                                *task.write().status.write() = DownloadStatus::Completed;
-                            } else {
-                                debug_eprintln!("合并文件失败");
-                                *task.write().status.write() = DownloadStatus::Failed("合并音视频失败".to_string());
-                            }
+                            Self::finish_task(task, download_path, bilibili_api).await;
                         }
                         Err(e) => {
                             debug_eprintln!("添加视频下载任务失败: {}", e);
-                            *task.write().status.write() = DownloadStatus::Failed(format!("添加下载失败: {}", e));
+                            *task.write().status.write() = DownloadStatus::Failed(format!("添加下载失败: {}", e).into());
                         }
                     }
                 } else {
-                    debug_eprintln!("aria2客户端未连接");
-                    *task.write().status.write() = DownloadStatus::Failed("aria2客户端未连接".to_string());
+                    debug_eprintln!("下载器未就绪");
+                    *task.write().status.write() = DownloadStatus::Failed("下载器未就绪".into());
                 }
             }
             Err(e) => {
                 debug_eprintln!("获取下载地址失败: {}", e);
-                *task.write().status.write() = DownloadStatus::Failed(format!("获取下载地址失败: {}", e));
+                *task.write().status.write() = DownloadStatus::Failed(e);
             }
         }
     }
     
-    async fn merge_audio_video(video_path: &PathBuf, audio_path: &PathBuf, output_path: &PathBuf, is_mp3: bool) -> bool {
+    /// 在输出文件旁保存原始XML弹幕和转换后的ASS字幕
+    async fn save_danmaku(task: &RwLock<DownloadTask>, bilibili_api: &BilibiliApi, options: &AssOptions) {
+        let (cid, duration, output_path) = {
+            let t = task.read();
+            (t.cid, t.duration, t.output_path.clone())
+        };
+        let Some(output_path) = output_path else {
+            return;
+        };
+        
+        match bilibili_api.get_danmaku(cid, duration).await {
+            Ok((xml, list)) => {
+                debug_println!("获取到 {} 条弹幕", list.len());
+                let _ = std::fs::write(output_path.with_extension("xml"), xml);
+                if let Err(_e) = std::fs::write(output_path.with_extension("ass"), danmaku::to_ass(&list, options)) {
+                    debug_eprintln!("保存弹幕字幕失败: {}", _e);
+                }
+            }
+            Err(_e) => {
+                debug_eprintln!("获取弹幕失败: {}", _e);
+            }
+        }
+    }
+    
+    /// 下载所有字幕轨道，保存为输出文件旁的{标题}.{语言}.srt/.vtt
+    async fn save_subtitles(bvid: &str, cid: u64, output_file: &Path, bilibili_api: &BilibiliApi) -> Vec<SubtitleFile> {
+        let mut tracks = match bilibili_api.get_subtitles(bvid, cid).await {
+            Ok(tracks) => tracks,
+            Err(_e) => {
+                debug_eprintln!("获取字幕列表失败: {}", _e);
+                return Vec::new();
+            }
+        };
+        // 人工字幕排在AI字幕前面，内嵌时作为第一条字幕轨
+        tracks.sort_by_key(|track| track.is_ai);
+        
+        let mut files = Vec::new();
+        for track in tracks {
+            let lines = match bilibili_api.get_subtitle_lines(&track).await {
+                Ok(lines) => lines,
+                Err(_e) => {
+                    debug_eprintln!("下载字幕{}失败: {}", track.lan, _e);
+                    continue;
+                }
+            };
+            
+            let srt_path = output_file.with_extension(format!("{}.srt", track.lan));
+            let vtt_path = output_file.with_extension(format!("{}.vtt", track.lan));
+            if std::fs::write(&srt_path, subtitle::to_srt(&lines)).is_err() {
+                continue;
+            }
+            let _ = std::fs::write(&vtt_path, subtitle::to_vtt(&lines));
+            
+            files.push(SubtitleFile {
+                lan: track.lan,
+                lan_doc: track.lan_doc,
+                srt_path,
+            });
+        }
+        files
+    }
+    
+    /// 等待文件下载完成后合并音视频
+    async fn finish_task(task: Arc<RwLock<DownloadTask>>, download_path: PathBuf, bilibili_api: Arc<BilibiliApi>) {
+        loop {
+            tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
+            
+            let status = task.read().status.read().clone();
+            match status {
+                DownloadStatus::Merging { .. } => {
+                    debug_println!("下载完成，开始合并文件...");
+                    break;
+                }
+                DownloadStatus::Failed(_) => {
+                    return;
+                }
+                _ => {}
+            }
+        }
+        
+        let (is_mp3, extension, has_audio, video_file, audio_file) = {
+            let t = task.read();
+            (t.is_mp3, t.output_extension(), t.has_audio, t.video_path.clone(), t.audio_path.clone())
+        };
+        let (bvid, cid, subtitles, embed_subtitles, embed_metadata) = {
+            let t = task.read();
+            (t.bvid.clone(), t.cid, t.subtitles, t.embed_subtitles, t.embed_metadata)
+        };
+        let Some(video_file) = video_file else {
+            *task.write().status.write() = DownloadStatus::Failed("找不到已下载的视频文件".into());
+            return;
+        };
+        
+        let output_file = Self::with_suffix(&task.read().output_base(&download_path), &format!(".{}", extension));
+        
+        // 字幕在合并前下载，需要内嵌时与音视频一起封装
+        let subtitle_files = if subtitles {
+            Self::save_subtitles(&bvid, cid, &output_file, &bilibili_api).await
+        } else {
+            Vec::new()
+        };
+        
+        let mut extras = MergeExtras::default();
+        if embed_subtitles && !is_mp3 {
+            extras.subtitles = subtitle_files;
+        }
+        if embed_metadata {
+            Self::prepare_metadata(&task, &output_file, &bilibili_api, &mut extras).await;
+        }
+        
+        let merge_success = match (&audio_file, has_audio) {
+            (Some(audio_file), true) => Self::merge_audio_video(&video_file, Some(audio_file), &output_file, is_mp3, &extras).await,
+            _ if !extras.is_empty() => Self::merge_audio_video(&video_file, None, &output_file, is_mp3, &extras).await,
+            _ => std::fs::rename(&video_file, &output_file).is_ok(),
+        };
+        
+        for temp_file in [&extras.metadata, &extras.cover].into_iter().flatten() {
+            let _ = std::fs::remove_file(temp_file);
+        }
+        
+        if merge_success {
+            debug_println!("文件处理成功: {:?}", output_file);
+            let _ = std::fs::remove_file(&video_file);
+            if let Some(audio_file) = audio_file {
+                let _ = std::fs::remove_file(audio_file);
+            }
+            
+            task.write().output_path = Some(output_file);
+            *task.write().status.write() = DownloadStatus::Completed;
+        } else {
+            debug_eprintln!("合并文件失败");
+            *task.write().status.write() = DownloadStatus::Failed("合并音视频失败".into());
+        }
+    }
+    
+    /// 生成元数据文件（标签和章节）并下载封面，失败时跳过对应部分
+    async fn prepare_metadata(task: &RwLock<DownloadTask>, output_file: &Path, bilibili_api: &BilibiliApi, extras: &mut MergeExtras) {
+        let (mut metadata, cid, cover_url) = {
+            let t = task.read();
+            let url = match &t.part {
+                Some(PageInfo { ep_id: Some(ep_id), .. }) => format!("https://www.bilibili.com/bangumi/play/ep{}", ep_id),
+                Some(part) => format!("https://www.bilibili.com/video/{}?p={}", t.bvid, part.page),
+                None => format!("https://www.bilibili.com/video/{}", t.bvid),
+            };
+            let metadata = MediaMetadata {
+                title: t.display_title(),
+                artist: t.author.clone(),
+                description: t.description.clone(),
+                bvid: t.bvid.clone(),
+                pubdate: t.pubdate,
+                url,
+                chapters: Vec::new(),
+            };
+            (metadata, t.cid, t.cover.clone())
+        };
+        
+        match bilibili_api.get_chapters(&metadata.bvid, cid).await {
+            Ok(chapters) => metadata.chapters = chapters,
+            Err(_e) => {
+                debug_eprintln!("获取章节失败: {}", _e);
+            }
+        }
+        
+        let metadata_path = output_file.with_extension("ffmeta");
+        if std::fs::write(&metadata_path, metadata.to_ffmetadata()).is_ok() {
+            extras.metadata = Some(metadata_path);
+        }
+        
+        match bilibili_api.download_avatar(&cover_url).await {
+            Ok(bytes) => {
+                // B站封面为JPEG或PNG
+                let cover_extension = if bytes.starts_with(b"\x89PNG") { "cover.png" } else { "cover.jpg" };
+                let cover_path = output_file.with_extension(cover_extension);
+                if std::fs::write(&cover_path, bytes).is_ok() {
+                    extras.cover = Some(cover_path);
+                }
+            }
+            Err(_e) => {
+                debug_eprintln!("下载封面失败: {}", _e);
+            }
+        }
+    }
+    
+    async fn merge_audio_video(
+        video_path: &Path,
+        audio_path: Option<&Path>,
+        output_path: &Path,
+        is_mp3: bool,
+        extras: &MergeExtras,
+    ) -> bool {
         let ffmpeg_path = Self::get_ffmpeg_path();
         
         if !ffmpeg_path.exists() {
@@ -688,16 +1370,80 @@ impl DownloadManager {
         debug_println!("使用ffmpeg合并文件...");
         
         let mut cmd = Command::new(ffmpeg_path);
+        let extension = output_path.extension().and_then(|ext| ext.to_str()).unwrap_or_default();
+        // MKV的封面作为附件保存，其他格式作为attached_pic视频流
+        let cover_stream = extras.cover.as_ref().filter(|_| extension != "mkv");
+        
+        let mut input_count = 0;
+        let mut add_input = |cmd: &mut Command, path: &Path| {
+            cmd.arg("-i").arg(path.to_string_lossy().to_string());
+            input_count += 1;
+            input_count - 1
+        };
         
         if is_mp3 {
-            cmd.arg("-i").arg(audio_path.to_string_lossy().to_string())
-                .arg("-vn")
-                .arg("-acodec").arg("mp3")
-                .arg("-ab").arg("320k");
+            // 没有单独的音频流时（durl格式）视频文件中已包含音频
+            let audio_input = audio_path.unwrap_or(video_path);
+            add_input(&mut cmd, audio_input);
+            cmd.arg("-map").arg("0:a");
+            
+            if let Some(cover) = cover_stream {
+                let index = add_input(&mut cmd, cover);
+                cmd.arg("-map").arg(index.to_string())
+                    .arg("-c:v").arg("copy")
+                    .arg("-disposition:v").arg("attached_pic");
+            }
+            
+            if extension == "mp3" {
+                cmd.arg("-acodec").arg("mp3")
+                    .arg("-ab").arg("320k")
+                    .arg("-id3v2_version").arg("3");
+            } else {
+                // FLAC/E-AC-3原样封装
+                cmd.arg("-c:a").arg("copy");
+                if extension == "m4a" {
+                    cmd.arg("-f").arg("mp4");
+                }
+            }
         } else {
-            cmd.arg("-i").arg(video_path.to_string_lossy().to_string())
-                .arg("-i").arg(audio_path.to_string_lossy().to_string())
-                .arg("-c").arg("copy");
+            add_input(&mut cmd, video_path);
+            cmd.arg("-map").arg("0");
+            if let Some(audio_path) = audio_path {
+                let index = add_input(&mut cmd, audio_path);
+                cmd.arg("-map").arg(index.to_string());
+            }
+            cmd.arg("-c").arg("copy");
+            
+            if !extras.subtitles.is_empty() {
+                for sub in &extras.subtitles {
+                    let index = add_input(&mut cmd, &sub.srt_path);
+                    cmd.arg("-map").arg(index.to_string());
+                }
+                // MP4只支持mov_text字幕，MKV直接封装SRT
+                let subtitle_codec = if extension == "mp4" { "mov_text" } else { "srt" };
+                cmd.arg("-c:s").arg(subtitle_codec);
+                for (i, sub) in extras.subtitles.iter().enumerate() {
+                    cmd.arg(format!("-metadata:s:s:{}", i)).arg(format!("language={}", subtitle::iso639_2(&sub.lan)))
+                        .arg(format!("-metadata:s:s:{}", i)).arg(format!("title={}", sub.lan_doc));
+                }
+            }
+            
+            if let Some(cover) = cover_stream {
+                let index = add_input(&mut cmd, cover);
+                cmd.arg("-map").arg(index.to_string())
+                    .arg("-disposition:v:1").arg("attached_pic");
+            } else if let Some(cover) = &extras.cover {
+                let mimetype = if cover.extension().is_some_and(|ext| ext == "png") { "image/png" } else { "image/jpeg" };
+                cmd.arg("-attach").arg(cover.to_string_lossy().to_string())
+                    .arg("-metadata:s:t").arg(format!("mimetype={}", mimetype))
+                    .arg("-metadata:s:t").arg("filename=cover.jpg");
+            }
+        }
+        
+        if let Some(metadata) = &extras.metadata {
+            let index = add_input(&mut cmd, metadata);
+            cmd.arg("-map_metadata").arg(index.to_string())
+                .arg("-map_chapters").arg(index.to_string());
         }
         
         cmd.arg("-y")
@@ -709,14 +1455,13 @@ impl DownloadManager {
         }
         
         match cmd.output() {
-            Ok(output) => {
-                if output.status.success() {
-                    debug_println!("ffmpeg合并成功");
-                    true
-                } else {
-                    debug_eprintln!("ffmpeg执行失败: {}", String::from_utf8_lossy(&output.stderr));
-                    false
-                }
+            Ok(output) if output.status.success() => {
+                debug_println!("ffmpeg合并成功");
+                true
+            }
+            Ok(_output) => {
+                debug_eprintln!("ffmpeg执行失败: {}", String::from_utf8_lossy(&_output.stderr));
+                false
             }
             Err(_e) => {
                 debug_eprintln!("运行ffmpeg失败: {}", _e);
@@ -725,47 +1470,74 @@ impl DownloadManager {
         }
     }
     
-    fn sanitize_filename(filename: &str) -> String {
-        filename.chars()
-            .map(|c| {
-                if "\\/:*?\"<>|".contains(c) || c.is_control() {
-                    '_'
-                } else {
-                    c
-                }
-            })
-            .collect::<String>()
-            .trim()
-            .chars()
-            .take(200)
-            .collect()
+    /// 在路径末尾追加后缀，文件名中的点不会被当作扩展名
+    fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+        let mut path = path.as_os_str().to_owned();
+        path.push(suffix);
+        PathBuf::from(path)
+    }
+    
+    /// 按冲突策略确定最终的相对路径并占用它，跳过下载时返回None。
+    /// 同名的临时文件或已被占用的路径说明有其他任务正在使用该文件名，
+    /// 这时覆盖策略也改为重命名，避免两个任务写同一个文件
+    fn resolve_collision(
+        download_path: &Path,
+        name: PathBuf,
+        extension: &str,
+        policy: CollisionPolicy,
+        reserved: &Mutex<HashSet<PathBuf>>,
+    ) -> Option<PathBuf> {
+        // 检查和占用在同一次加锁中完成
+        let mut reserved = reserved.lock();
+        let base = download_path.join(&name);
+        let output_exists = |base: &Path| Self::with_suffix(base, &format!(".{}", extension)).exists();
+        let in_use = |base: &Path| {
+            reserved.contains(base)
+                || output_exists(base)
+                || Self::with_suffix(base, "_video.m4s").exists()
+                || Self::with_suffix(base, "_audio.m4s").exists()
+        };
+        
+        let name = match policy {
+            CollisionPolicy::Overwrite if !reserved.contains(&base) => name,
+            CollisionPolicy::Skip if reserved.contains(&base) || output_exists(&base) => return None,
+            CollisionPolicy::Skip => name,
+            _ if !in_use(&base) => name,
+            _ => {
+                let file_name = name.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+                (1..)
+                    .map(|i| name.with_file_name(format!("{} ({})", file_name, i)))
+                    .find(|candidate| !in_use(&download_path.join(candidate)))?
+            }
+        };
+        reserved.insert(download_path.join(&name));
+        Some(name)
     }
     
     pub fn get_tasks(&self) -> Vec<Arc<RwLock<DownloadTask>>> {
-        self.tasks.read().values().cloned().collect()
+        let mut tasks: Vec<Arc<RwLock<DownloadTask>>> = self.tasks.read().values().cloned().collect();
+        tasks.sort_by_key(|task| task.read().created_at);
+        tasks
     }
     
     pub fn pause_task(&self, id: &str) {
+        self.pause(id);
+        self.scheduler.schedule();
+    }
+    
+    fn pause(&self, id: &str) {
+        self.scheduler.remove(id);
+        
         if let Some(task) = self.tasks.read().get(id) {
             let (video_gid, audio_gid) = {
                 let task_read = task.read();
                 (task_read.video_gid.clone(), task_read.audio_gid.clone())
             };
             
-            let aria2_client = self.aria2_client.clone();
+            let backend = self.backend.clone();
             self.runtime.spawn(async move {
-                let client = {
-                    let guard = aria2_client.read();
-                    guard.as_ref().cloned()
-                };
-                
-                if let Some(client) = client {
-                    if let Some(gid) = video_gid {
-                        let _ = client.pause(gid).await;
-                    }
-                    if let Some(gid) = audio_gid {
-                        let _ = client.pause(gid).await;
-                    }
+                for gid in [video_gid, audio_gid].into_iter().flatten() {
+                    backend.pause(gid).await;
                 }
             });
             
@@ -774,69 +1546,109 @@ impl DownloadManager {
     }
     
     pub fn resume_task(&self, id: &str) {
+        self.scheduler.enqueue(id);
+    }
+    
+    /// 手动重试失败的任务，重新计算自动重试次数
+    pub fn retry_task(&self, id: &str) {
         if let Some(task) = self.tasks.read().get(id) {
-            let (video_gid, audio_gid) = {
-                let task_read = task.read();
-                (task_read.video_gid.clone(), task_read.audio_gid.clone())
+            task.write().retries = 0;
+        }
+        self.scheduler.enqueue(id);
+    }
+    
+    /// 将等待中的任务移到队列最前
+    pub fn move_to_top(&self, id: &str) {
+        self.scheduler.move_to_top(id);
+    }
+    
+    /// 任务在等待队列中的位置，从0开始
+    pub fn queue_position(&self, id: &str) -> Option<usize> {
+        self.scheduler.position(id)
+    }
+    
+    pub fn start_all(&self) {
+        for task in self.get_tasks() {
+            let (id, paused) = {
+                let t = task.read();
+                let paused = *t.status.read() == DownloadStatus::Paused;
+                (t.id.clone(), paused)
             };
-            
-            let aria2_client = self.aria2_client.clone();
-            self.runtime.spawn(async move {
-                let client = {
-                    let guard = aria2_client.read();
-                    guard.as_ref().cloned()
-                };
-                
-                if let Some(client) = client {
-                    if let Some(gid) = video_gid {
-                        let _ = client.unpause(gid).await;
-                    }
-                    if let Some(gid) = audio_gid {
-                        let _ = client.unpause(gid).await;
-                    }
-                }
-            });
-            
-            *task.write().status.write() = DownloadStatus::Downloading {
-                progress: 0.0,
-                speed: "恢复中...".to_string(),
+            if paused {
+                self.scheduler.enqueue(&id);
+            }
+        }
+    }
+    
+    pub fn stop_all(&self) {
+        for task in self.get_tasks() {
+            let (id, running) = {
+                let t = task.read();
+                let running = matches!(*t.status.read(), DownloadStatus::Waiting | DownloadStatus::Downloading { .. });
+                (t.id.clone(), running)
             };
+            if running {
+                self.pause(&id);
+            }
         }
     }
     
     pub fn cancel_task(&self, id: &str) {
         if let Some(task) = self.tasks.read().get(id) {
-            let (video_gid, audio_gid) = {
+            let (video_gid, audio_gid, partial_files) = {
                 let task_read = task.read();
-                (task_read.video_gid.clone(), task_read.audio_gid.clone())
+                // 未完成的任务需要清理下载了一半的文件
+                let partial_files: Vec<PathBuf> = if matches!(*task_read.status.read(), DownloadStatus::Completed | DownloadStatus::Skipped) {
+                    Vec::new()
+                } else {
+                    [&task_read.video_path, &task_read.audio_path]
+                        .into_iter()
+                        .flatten()
+                        .flat_map(|path| [path.clone(), self.backend.control_file(path)])
+                        .collect()
+                };
+                (task_read.video_gid.clone(), task_read.audio_gid.clone(), partial_files)
             };
             
-            let aria2_client = self.aria2_client.clone();
+            let backend = self.backend.clone();
             self.runtime.spawn(async move {
-                let client = {
-                    let guard = aria2_client.read();
-                    guard.as_ref().cloned()
-                };
+                for gid in [video_gid, audio_gid].into_iter().flatten() {
+                    backend.remove(gid).await;
+                }
                 
-                if let Some(client) = client {
-                    if let Some(gid) = video_gid {
-                        let _ = client.remove(gid).await;
-                    }
-                    if let Some(gid) = audio_gid {
-                        let _ = client.remove(gid).await;
-                    }
+                // 等待下载器释放文件
+                tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+                for path in partial_files {
+                    let _ = std::fs::remove_file(path);
                 }
             });
+            self.scheduler.release_output(&task.read());
         }
         
+        self.scheduler.remove(id);
         self.tasks.write().remove(id);
+        self.store.save(&self.tasks);
+        self.scheduler.schedule();
+    }
+    
+    /// 保存任务和下载进度并停止下载器。在异步代码中退出时先调用它，Drop中无法等待下载器保存进度
+    pub async fn shutdown(&self) {
+        self.store.save(&self.tasks);
+        let _ = tokio::time::timeout(tokio::time::Duration::from_secs(2), self.backend.save_state()).await;
+        self.backend.stop();
     }
 }
 
 impl Drop for DownloadManager {
     fn drop(&mut self) {
-        if let Some(mut child) = self.aria2_process.take() {
-            let _ = child.kill();
+        self.store.save(&self.tasks);
+        
+        // 退出前保存下载进度，下次启动时从断点继续。在运行时内不能block_on
+        if tokio::runtime::Handle::try_current().is_err() {
+            let _ = self.runtime.block_on(async {
+                tokio::time::timeout(tokio::time::Duration::from_secs(2), self.backend.save_state()).await
+            });
         }
+        self.backend.stop();
     }
 }
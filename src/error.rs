@@ -0,0 +1,102 @@
+// error.rs
+// B站接口和下载任务的错误类型，按错误码区分需要登录、需要大会员、地区限制、风控等情况
+use crate::config::Language;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ApiError {
+    /// 连接失败、超时等网络错误
+    Network(String),
+    /// 未登录或登录已失效 (-101)
+    NotLoggedIn,
+    /// 需要大会员或充电才能观看 (-10403、87007、87008)
+    VipRequired,
+    /// 当前地区不可观看
+    RegionLocked,
+    /// 视频不存在、已删除或不可见 (-404、62002、62004、62012)
+    NotFound,
+    /// 请求被风控拦截 (-412、-352、-509)
+    RateLimited(i32),
+    /// 其他接口错误码
+    Api { code: i32, message: String },
+    /// 解析失败、下载和合并等其他错误
+    Other(String),
+}
+
+pub type ApiResult<T> = Result<T, ApiError>;
+
+impl ApiError {
+    /// 按接口返回的错误码分类，message为接口返回的说明
+    pub fn from_code(code: i32, message: impl Into<String>) -> Self {
+        let message = message.into();
+        match code {
+            -101 => ApiError::NotLoggedIn,
+            -412 | -352 | -509 | -799 => ApiError::RateLimited(code),
+            -404 | 62002 | 62004 | 62012 => ApiError::NotFound,
+            _ if message.contains("地区") => ApiError::RegionLocked,
+            -10403 | 87007 | 87008 => ApiError::VipRequired,
+            _ => ApiError::Api { code, message },
+        }
+    }
+
+    /// 重试可能成功的错误，需要登录、大会员等情况重试没有意义
+    pub fn is_retryable(&self) -> bool {
+        !matches!(
+            self,
+            ApiError::NotLoggedIn | ApiError::VipRequired | ApiError::RegionLocked | ApiError::NotFound
+        )
+    }
+
+    /// 给用户看的说明，附带处理建议
+    pub fn localized(&self, language: &Language) -> String {
+        match language {
+            Language::SimplifiedChinese => match self {
+                ApiError::Network(e) => format!("网络错误，请检查网络或代理设置: {}", e),
+                ApiError::NotLoggedIn => "需要登录，请点击右上角头像扫码登录后重试".to_string(),
+                ApiError::VipRequired => "需要大会员，请登录大会员账号或选择较低画质".to_string(),
+                ApiError::RegionLocked => "当前地区不可观看，可在设置中配置代理后重试".to_string(),
+                ApiError::NotFound => "视频不存在、已删除或不可见".to_string(),
+                ApiError::RateLimited(code) => format!("请求过于频繁，已被B站拦截 (code={})，请稍后再试", code),
+                ApiError::Api { code, message } => format!("{} (code={})", message, code),
+                ApiError::Other(e) => e.clone(),
+            },
+            Language::English => match self {
+                ApiError::Network(e) => format!("Network error, check your connection or proxy settings: {}", e),
+                ApiError::NotLoggedIn => "Login required, click the avatar in the top-right corner to sign in and retry".to_string(),
+                ApiError::VipRequired => "Premium membership required, sign in with a premium account or choose a lower quality".to_string(),
+                ApiError::RegionLocked => "Not available in your region, set up a proxy in Settings and retry".to_string(),
+                ApiError::NotFound => "Video does not exist, was deleted or is not visible".to_string(),
+                ApiError::RateLimited(code) => format!("Too many requests, blocked by Bilibili (code={}), try again later", code),
+                ApiError::Api { code, message } => format!("{} (code={})", message, code),
+                ApiError::Other(e) => e.clone(),
+            },
+        }
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.localized(&Language::SimplifiedChinese))
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+impl From<String> for ApiError {
+    fn from(message: String) -> Self {
+        ApiError::Other(message)
+    }
+}
+
+impl From<&str> for ApiError {
+    fn from(message: &str) -> Self {
+        ApiError::Other(message.to_string())
+    }
+}
+
+impl From<ApiError> for String {
+    fn from(error: ApiError) -> Self {
+        error.to_string()
+    }
+}
@@ -0,0 +1,112 @@
+// 输出文件名模板，模板中的 / 用于创建子文件夹
+use std::path::PathBuf;
+
+pub const DEFAULT_TEMPLATE: &str = "{full_title}";
+
+/// 模板中可用的变量，用于设置页的提示
+pub const TEMPLATE_VARIABLES: [&str; 11] = [
+    "{title}",
+    "{full_title}",
+    "{part}",
+    "{part_title}",
+    "{uploader}",
+    "{bvid}",
+    "{cid}",
+    "{quality}",
+    "{codec}",
+    "{date}",
+    "{download_date}",
+];
+
+/// 文件名模板中各变量的值
+#[derive(Debug, Clone, Default)]
+pub struct TemplateVars {
+    /// 稿件标题
+    pub title: String,
+    /// 稿件标题，多P时附带分P序号和分P标题
+    pub full_title: String,
+    /// 分P序号，单P视频为1
+    pub part: u32,
+    pub part_title: String,
+    pub uploader: String,
+    pub bvid: String,
+    pub cid: u64,
+    /// 实际下载的画质，如1080P
+    pub quality: String,
+    /// 实际下载的视频编码，如HEVC
+    pub codec: String,
+    /// 发布日期
+    pub date: String,
+}
+
+impl TemplateVars {
+    /// 设置页预览用的示例值
+    pub fn sample() -> Self {
+        Self {
+            title: "示例视频".to_string(),
+            full_title: "示例视频 - P2 第二集".to_string(),
+            part: 2,
+            part_title: "第二集".to_string(),
+            uploader: "UP主".to_string(),
+            bvid: "BV1xx411c7mD".to_string(),
+            cid: 123456789,
+            quality: "1080P".to_string(),
+            codec: "HEVC".to_string(),
+            date: "2024-01-01".to_string(),
+        }
+    }
+}
+
+/// 替换文件名中的非法字符，并限制长度
+pub fn sanitize(filename: &str) -> String {
+    filename.chars()
+        .map(|c| {
+            if "\\/:*?\"<>|".contains(c) || c.is_control() {
+                '_'
+            } else {
+                c
+            }
+        })
+        .collect::<String>()
+        .trim()
+        .chars()
+        .take(200)
+        .collect()
+}
+
+/// 按模板生成相对于下载目录的路径（不含扩展名）。
+/// 变量值中的 / 会被替换，只有模板本身的 / 会创建子文件夹
+pub fn render(template: &str, vars: &TemplateVars) -> PathBuf {
+    let download_date = chrono::Local::now().format("%Y-%m-%d").to_string();
+    let values = [
+        ("{title}", vars.title.clone()),
+        ("{full_title}", vars.full_title.clone()),
+        ("{part}", vars.part.to_string()),
+        ("{part_title}", vars.part_title.clone()),
+        ("{uploader}", vars.uploader.clone()),
+        ("{bvid}", vars.bvid.clone()),
+        ("{cid}", vars.cid.to_string()),
+        ("{quality}", vars.quality.clone()),
+        ("{codec}", vars.codec.clone()),
+        ("{date}", vars.date.clone()),
+        ("{download_date}", download_date),
+    ];
+
+    let mut path = PathBuf::new();
+    for component in template.split(['/', '\\']) {
+        let mut rendered = component.to_string();
+        for (key, value) in &values {
+            rendered = rendered.replace(key, &sanitize(value));
+        }
+        // Windows不允许文件夹名以点或空格结尾
+        let rendered = sanitize(&rendered).trim_end_matches(['.', ' ']).to_string();
+        if !rendered.is_empty() {
+            path.push(rendered);
+        }
+    }
+
+    if path.as_os_str().is_empty() {
+        path.push(sanitize(&vars.full_title));
+    }
+    path
+}
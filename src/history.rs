@@ -0,0 +1,171 @@
+// history.rs
+// 下载历史记录，保存在history.json中，用于重复下载检查和历史页面
+use crate::bilibili::{self, VideoCodec};
+use crate::config::Config;
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub id: String,
+    pub bvid: String,
+    pub cid: u64,
+    /// 显示标题，多P时包含分P信息
+    pub title: String,
+    pub author: String,
+    /// 番剧剧集的ep_id，普通视频为None
+    #[serde(default)]
+    pub ep_id: Option<u64>,
+    /// 实际下载的画质
+    pub quality: u32,
+    /// 添加任务时选择的画质
+    pub requested_quality: u32,
+    pub codec: Option<VideoCodec>,
+    pub is_mp3: bool,
+    pub output_path: PathBuf,
+    /// 文件大小（字节）
+    pub size: u64,
+    /// 完成时间（毫秒时间戳）
+    pub completed_at: i64,
+}
+
+impl HistoryEntry {
+    /// 视频页面链接，用于重新下载
+    pub fn url(&self) -> String {
+        match self.ep_id {
+            Some(ep_id) => format!("https://www.bilibili.com/bangumi/play/ep{}", ep_id),
+            None => format!("https://www.bilibili.com/video/{}", self.bvid),
+        }
+    }
+
+    pub fn format_name(&self) -> &'static str {
+        if self.is_mp3 {
+            "audio"
+        } else {
+            "video"
+        }
+    }
+}
+
+pub struct History {
+    entries: RwLock<Vec<HistoryEntry>>,
+    path: PathBuf,
+}
+
+impl History {
+    pub fn load() -> Self {
+        let path = Config::data_file("history.json");
+        let entries = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+        Self {
+            entries: RwLock::new(entries),
+            path,
+        }
+    }
+
+    fn save(&self) {
+        if let Ok(content) = serde_json::to_string_pretty(&*self.entries.read()) {
+            let _ = std::fs::write(&self.path, content);
+        }
+    }
+
+    pub fn record(&self, entry: HistoryEntry) {
+        self.entries.write().push(entry);
+        self.save();
+    }
+
+    pub fn remove(&self, id: &str) {
+        self.entries.write().retain(|entry| entry.id != id);
+        self.save();
+    }
+
+    pub fn clear(&self) {
+        self.entries.write().clear();
+        self.save();
+    }
+
+    /// 全部记录，最近完成的在前
+    pub fn entries(&self) -> Vec<HistoryEntry> {
+        let mut entries = self.entries.read().clone();
+        entries.sort_by_key(|entry| std::cmp::Reverse(entry.completed_at));
+        entries
+    }
+
+    /// 按标题、UP主或BV号搜索，不区分大小写
+    pub fn search(&self, query: &str) -> Vec<HistoryEntry> {
+        let query = query.trim().to_lowercase();
+        self.entries()
+            .into_iter()
+            .filter(|entry| {
+                query.is_empty()
+                    || entry.title.to_lowercase().contains(&query)
+                    || entry.author.to_lowercase().contains(&query)
+                    || entry.bvid.to_lowercase().contains(&query)
+            })
+            .collect()
+    }
+
+    /// 查找已经以不低于该画质下载过、且文件仍然存在的记录
+    pub fn find_downloaded(&self, bvid: &str, cid: u64, is_mp3: bool, quality: u32) -> Option<HistoryEntry> {
+        self.entries
+            .read()
+            .iter()
+            .filter(|entry| entry.bvid == bvid && entry.cid == cid && entry.is_mp3 == is_mp3)
+            // 音频不区分画质；视频不可用时会回退画质，选择同样画质时也视为已下载
+            .filter(|entry| is_mp3 || entry.quality >= quality || entry.requested_quality >= quality)
+            .filter(|entry| entry.output_path.exists())
+            .max_by_key(|entry| entry.completed_at)
+            .cloned()
+    }
+}
+
+/// CSV字段中有逗号、引号或换行时用引号包裹
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+pub fn to_csv(entries: &[HistoryEntry]) -> String {
+    // 带BOM，方便Excel识别UTF-8
+    let mut csv = String::from("\u{feff}bvid,cid,title,author,format,quality,codec,size,output_path,completed_at\n");
+    for entry in entries {
+        let completed_at = chrono::DateTime::from_timestamp_millis(entry.completed_at)
+            .map(|time| time.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M:%S").to_string())
+            .unwrap_or_default();
+        let fields = [
+            entry.bvid.clone(),
+            entry.cid.to_string(),
+            entry.title.clone(),
+            entry.author.clone(),
+            entry.format_name().to_string(),
+            bilibili::quality_name(entry.quality),
+            entry.codec.map(|codec| codec.short_name().to_string()).unwrap_or_default(),
+            entry.size.to_string(),
+            entry.output_path.display().to_string(),
+            completed_at,
+        ];
+        let line: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+        csv.push_str(&line.join(","));
+        csv.push('\n');
+    }
+    csv
+}
+
+pub fn to_json(entries: &[HistoryEntry]) -> String {
+    serde_json::to_string_pretty(entries).unwrap_or_default()
+}
+
+/// 按扩展名导出为CSV或JSON
+pub fn export(entries: &[HistoryEntry], path: &Path) -> Result<(), String> {
+    let is_json = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+    let content = if is_json { to_json(entries) } else { to_csv(entries) };
+    std::fs::write(path, content).map_err(|e| format!("导出失败: {}", e))
+}
@@ -0,0 +1,16 @@
+// lib.rs
+// 与界面无关的核心：B站接口、下载管理和配置，图形界面和命令行共用
+
+pub mod backend;
+pub mod bilibili;
+pub mod config;
+pub mod danmaku;
+pub mod downloader;
+pub mod error;
+pub mod filename;
+pub mod history;
+pub mod metadata;
+pub mod rate_limit;
+pub mod server;
+pub mod subscription;
+pub mod subtitle;
@@ -2,14 +2,20 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 mod app;
-mod config;
-mod downloader;
-mod bilibili;
+mod cli;
 mod ui;
 
+use bilibili_down::{bilibili, config, downloader, error, filename, history, server, subscription};
+
 use eframe::egui;
 
 fn main() -> Result<(), eframe::Error> {
+    // 带参数启动时以命令行模式运行，不创建窗口
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if cli::is_cli_invocation(&args) {
+        std::process::exit(cli::run(args));
+    }
+
     #[cfg(not(debug_assertions))]
     {
         // Release模式下不初始化日志
@@ -0,0 +1,69 @@
+// 输出文件的元数据标签和章节，生成ffmpeg的FFMETADATA文件
+
+/// 视频看点（章节），时间单位为秒
+#[derive(Debug, Clone)]
+pub struct Chapter {
+    pub start: u64,
+    pub end: u64,
+    pub title: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct MediaMetadata {
+    pub title: String,
+    pub artist: String,
+    pub description: String,
+    pub bvid: String,
+    /// 发布时间（秒级时间戳），0表示未知
+    pub pubdate: i64,
+    pub url: String,
+    pub chapters: Vec<Chapter>,
+}
+
+/// FFMETADATA中 = ; # \ 和换行需要转义
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '=' | ';' | '#' | '\\' | '\n') {
+            escaped.push('\\');
+        }
+        if c != '\r' {
+            escaped.push(c);
+        }
+    }
+    escaped
+}
+
+impl MediaMetadata {
+    pub fn to_ffmetadata(&self) -> String {
+        let mut tags = vec![
+            ("title", self.title.clone()),
+            ("artist", self.artist.clone()),
+            ("album_artist", self.artist.clone()),
+            ("description", self.description.clone()),
+            ("comment", self.url.clone()),
+            ("episode_id", self.bvid.clone()),
+        ];
+        if let Some(date) = chrono::DateTime::from_timestamp(self.pubdate, 0).filter(|_| self.pubdate > 0) {
+            tags.push(("date", date.with_timezone(&chrono::Local).format("%Y-%m-%d").to_string()));
+        }
+
+        let mut content = String::from(";FFMETADATA1\n");
+        for (key, value) in tags {
+            if !value.is_empty() {
+                content.push_str(&format!("{}={}\n", key, escape(&value)));
+            }
+        }
+
+        for chapter in &self.chapters {
+            content.push_str(&format!(
+                "\n[CHAPTER]\nTIMEBASE=1/1000\nSTART={}\nEND={}\ntitle={}\n",
+                chapter.start * 1000,
+                chapter.end * 1000,
+                escape(&chapter.title)
+            ));
+        }
+
+        content
+    }
+}
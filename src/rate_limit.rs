@@ -0,0 +1,50 @@
+// rate_limit.rs
+// 令牌桶限速，内置下载器的下载速度和B站接口的请求频率共用
+use parking_lot::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// 多个连接或请求共用同一个桶
+pub struct RateLimiter {
+    /// 每秒补充的令牌数，0表示不限速
+    rate: AtomicU64,
+    /// 上次补充令牌的时间和剩余令牌数，令牌数为负时需要等待
+    bucket: Mutex<(Instant, f64)>,
+}
+
+impl RateLimiter {
+    pub fn new(rate: u64) -> Self {
+        Self {
+            rate: AtomicU64::new(rate),
+            bucket: Mutex::new((Instant::now(), 0.0)),
+        }
+    }
+    
+    pub fn set_rate(&self, rate: u64) {
+        self.rate.store(rate, Ordering::Relaxed);
+        *self.bucket.lock() = (Instant::now(), 0.0);
+    }
+    
+    /// 消耗令牌，不足时等待到补足为止
+    pub async fn consume(&self, tokens: u64) {
+        let rate = self.rate.load(Ordering::Relaxed);
+        if rate == 0 {
+            return;
+        }
+        let wait = {
+            let mut bucket = self.bucket.lock();
+            let now = Instant::now();
+            // 最多积累1秒的令牌，避免空闲后瞬间超速
+            let available = bucket.1 + now.duration_since(bucket.0).as_secs_f64() * rate as f64;
+            *bucket = (now, available.min(rate as f64) - tokens as f64);
+            if bucket.1 < 0.0 {
+                Duration::from_secs_f64(-bucket.1 / rate as f64)
+            } else {
+                Duration::ZERO
+            }
+        };
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
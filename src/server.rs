@@ -0,0 +1,494 @@
+// server.rs
+// 本地HTTP控制接口，只监听127.0.0.1，供浏览器脚本和自动化工具直接推送链接、管理下载队列
+use crate::bilibili::{BilibiliApi, ParsedInput, VideoCodec, VideoInfo};
+use crate::config::Config;
+use crate::downloader::{DownloadManager, DownloadStatus, DownloadTask};
+use parking_lot::RwLock;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::runtime::Runtime;
+use tokio::task::JoinHandle;
+
+#[cfg(debug_assertions)]
+macro_rules! debug_eprintln {
+    ($($arg:tt)*) => { eprintln!($($arg)*) }
+}
+#[cfg(not(debug_assertions))]
+macro_rules! debug_eprintln {
+    ($($arg:tt)*) => {}
+}
+
+pub const DEFAULT_PORT: u16 = 27180;
+
+// 请求头和请求体的大小上限
+const MAX_HEADER_SIZE: usize = 16 * 1024;
+const MAX_BODY_SIZE: usize = 1024 * 1024;
+
+// 不允许通过接口读取或修改的设置项，代理设置中包含账号密码
+const PROTECTED_SETTINGS: [&str; 7] = [
+    "cookies",
+    "api_enabled",
+    "api_port",
+    "api_token",
+    "aria2_secret",
+    "api_proxy",
+    "media_proxy",
+];
+
+struct Request {
+    method: String,
+    path: String,
+    query: Vec<(String, String)>,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Request {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// 令牌可以放在 Authorization: Bearer、X-Api-Token 请求头或 token 查询参数中
+    fn token(&self) -> Option<&str> {
+        self.header("authorization")
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .or_else(|| self.header("x-api-token"))
+            .or_else(|| self.query.iter().find(|(key, _)| key == "token").map(|(_, value)| value.as_str()))
+    }
+
+    fn json<T: for<'de> Deserialize<'de>>(&self) -> Result<T, Response> {
+        serde_json::from_slice(&self.body).map_err(|e| Response::error(400, &format!("请求体不是有效的JSON: {}", e)))
+    }
+}
+
+struct Response {
+    status: u16,
+    body: Value,
+}
+
+impl Response {
+    fn ok(body: Value) -> Self {
+        Self { status: 200, body }
+    }
+
+    fn error(status: u16, message: &str) -> Self {
+        Self { status, body: json!({ "error": message }) }
+    }
+
+    fn reason(&self) -> &'static str {
+        match self.status {
+            200 => "OK",
+            204 => "No Content",
+            400 => "Bad Request",
+            401 => "Unauthorized",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            413 => "Payload Too Large",
+            _ => "Internal Server Error",
+        }
+    }
+}
+
+/// 添加下载的请求体，只有url是必填项
+#[derive(Deserialize)]
+struct AddRequest {
+    url: String,
+    #[serde(default = "default_quality")]
+    quality: u32,
+    /// video 或 mp3
+    #[serde(default)]
+    format: Option<String>,
+    /// 分P序号，不填时下载全部（ep链接只下载该集）
+    #[serde(default)]
+    parts: Option<Vec<u32>>,
+    #[serde(default)]
+    codecs: Option<Vec<VideoCodec>>,
+    #[serde(default)]
+    audio_quality: u32,
+    #[serde(default)]
+    danmaku: bool,
+    #[serde(default)]
+    subtitles: bool,
+}
+
+fn default_quality() -> u32 {
+    80
+}
+
+/// 运行中的本地接口服务，drop时停止监听
+pub struct ApiServer {
+    pub port: u16,
+    handle: JoinHandle<()>,
+}
+
+impl Drop for ApiServer {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
+
+impl ApiServer {
+    pub fn start(
+        port: u16,
+        runtime: Arc<Runtime>,
+        manager: Arc<DownloadManager>,
+        api: Arc<BilibiliApi>,
+        config: Arc<RwLock<Config>>,
+    ) -> Result<Self, String> {
+        // 首次启用时生成访问令牌，避免任意网页通过localhost操作下载队列
+        {
+            let mut config = config.write();
+            if config.api_token.is_empty() {
+                config.api_token = uuid::Uuid::new_v4().simple().to_string();
+                config.save();
+            }
+        }
+
+        let listener = runtime
+            .block_on(TcpListener::bind(("127.0.0.1", port)))
+            .map_err(|e| format!("无法监听端口 {}: {}", port, e))?;
+
+        let handle = runtime.spawn(async move {
+            loop {
+                let Ok((stream, _)) = listener.accept().await else {
+                    continue;
+                };
+                let manager = manager.clone();
+                let api = api.clone();
+                let config = config.clone();
+                tokio::spawn(async move {
+                    if let Err(_e) = handle_connection(stream, manager, api, config).await {
+                        debug_eprintln!("处理API请求失败: {}", _e);
+                    }
+                });
+            }
+        });
+
+        Ok(Self { port, handle })
+    }
+}
+
+async fn handle_connection(
+    mut stream: TcpStream,
+    manager: Arc<DownloadManager>,
+    api: Arc<BilibiliApi>,
+    config: Arc<RwLock<Config>>,
+) -> Result<(), String> {
+    let timeout = std::time::Duration::from_secs(30);
+    let response = match tokio::time::timeout(timeout, read_request(&mut stream)).await {
+        Err(_) => return Err("读取请求超时".to_string()),
+        Ok(Err(response)) => response,
+        Ok(Ok(request)) => {
+            if request.method == "OPTIONS" {
+                Response { status: 204, body: Value::Null }
+            } else if request.token() != Some(config.read().api_token.as_str()) {
+                Response::error(401, "访问令牌无效")
+            } else {
+                route(request, &manager, &api, &config).await
+            }
+        }
+    };
+    write_response(&mut stream, response).await
+}
+
+async fn read_request(stream: &mut TcpStream) -> Result<Request, Response> {
+    let mut buffer = Vec::new();
+    let mut chunk = [0u8; 4096];
+    let header_end = loop {
+        if let Some(pos) = buffer.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos;
+        }
+        if buffer.len() > MAX_HEADER_SIZE {
+            return Err(Response::error(413, "请求头过大"));
+        }
+        let n = stream.read(&mut chunk).await.map_err(|_| Response::error(400, "读取请求失败"))?;
+        if n == 0 {
+            return Err(Response::error(400, "请求不完整"));
+        }
+        buffer.extend_from_slice(&chunk[..n]);
+    };
+
+    let head = String::from_utf8_lossy(&buffer[..header_end]).to_string();
+    let mut lines = head.split("\r\n");
+    let mut request_line = lines.next().unwrap_or_default().split_whitespace();
+    let method = request_line.next().unwrap_or_default().to_uppercase();
+    let target = request_line.next().unwrap_or("/");
+
+    let headers: Vec<(String, String)> = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+        .collect();
+
+    let (path, query) = match target.split_once('?') {
+        Some((path, query)) => (path, query),
+        None => (target, ""),
+    };
+    let query = url::form_urlencoded::parse(query.as_bytes()).into_owned().collect();
+
+    let content_length: usize = headers
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, value)| value.parse().ok())
+        .unwrap_or(0);
+    if content_length > MAX_BODY_SIZE {
+        return Err(Response::error(413, "请求体过大"));
+    }
+
+    let mut body = buffer[header_end + 4..].to_vec();
+    while body.len() < content_length {
+        let n = stream.read(&mut chunk).await.map_err(|_| Response::error(400, "读取请求失败"))?;
+        if n == 0 {
+            return Err(Response::error(400, "请求不完整"));
+        }
+        body.extend_from_slice(&chunk[..n]);
+    }
+    body.truncate(content_length);
+
+    Ok(Request {
+        method,
+        path: path.trim_end_matches('/').to_string(),
+        query,
+        headers,
+        body,
+    })
+}
+
+async fn write_response(stream: &mut TcpStream, response: Response) -> Result<(), String> {
+    let body = if response.status == 204 { String::new() } else { response.body.to_string() };
+    let head = format!(
+        "HTTP/1.1 {} {}\r\n\
+         Content-Type: application/json; charset=utf-8\r\n\
+         Content-Length: {}\r\n\
+         Access-Control-Allow-Origin: *\r\n\
+         Access-Control-Allow-Methods: GET, POST, PATCH, DELETE, OPTIONS\r\n\
+         Access-Control-Allow-Headers: Authorization, Content-Type, X-Api-Token\r\n\
+         Connection: close\r\n\r\n",
+        response.status,
+        response.reason(),
+        body.len()
+    );
+    stream.write_all(head.as_bytes()).await.map_err(|e| e.to_string())?;
+    stream.write_all(body.as_bytes()).await.map_err(|e| e.to_string())?;
+    stream.shutdown().await.map_err(|e| e.to_string())
+}
+
+async fn route(
+    request: Request,
+    manager: &DownloadManager,
+    api: &BilibiliApi,
+    config: &RwLock<Config>,
+) -> Response {
+    let segments: Vec<&str> = request.path.split('/').skip(1).collect();
+    match (request.method.as_str(), segments.as_slice()) {
+        ("GET", ["api", "status"]) => Response::ok(json!({
+            "version": env!("CARGO_PKG_VERSION"),
+            "logged_in": config.read().cookies.is_some(),
+        })),
+        ("GET", ["api", "tasks"]) => {
+            let tasks: Vec<Value> = manager
+                .get_tasks()
+                .iter()
+                .map(|task| {
+                    let task = task.read();
+                    task_json(&task, manager.queue_position(&task.id))
+                })
+                .collect();
+            Response::ok(json!({ "tasks": tasks }))
+        }
+        ("POST", ["api", "tasks"]) => match request.json::<AddRequest>() {
+            Ok(add) => add_tasks(add, manager, api, config).await,
+            Err(response) => response,
+        },
+        ("GET", ["api", "tasks", id]) => match manager.get_tasks().iter().find(|task| task.read().id == *id) {
+            Some(task) => {
+                let task = task.read();
+                Response::ok(task_json(&task, manager.queue_position(id)))
+            }
+            None => Response::error(404, "任务不存在"),
+        },
+        ("DELETE", ["api", "tasks", id]) => with_task(manager, id, |manager| manager.cancel_task(id)),
+        ("POST", ["api", "tasks", id, action]) => match *action {
+            "pause" => with_task(manager, id, |manager| manager.pause_task(id)),
+            "resume" => with_task(manager, id, |manager| manager.resume_task(id)),
+            "retry" => with_task(manager, id, |manager| manager.retry_task(id)),
+            "top" => with_task(manager, id, |manager| manager.move_to_top(id)),
+            "cancel" => with_task(manager, id, |manager| manager.cancel_task(id)),
+            _ => Response::error(404, "未知的操作"),
+        },
+        ("POST", ["api", "queue", "start"]) => {
+            manager.start_all();
+            Response::ok(json!({ "ok": true }))
+        }
+        ("POST", ["api", "queue", "stop"]) => {
+            manager.stop_all();
+            Response::ok(json!({ "ok": true }))
+        }
+        ("GET", ["api", "settings"]) => Response::ok(settings_json(&config.read())),
+        ("PATCH" | "PUT" | "POST", ["api", "settings"]) => match request.json::<Value>() {
+            Ok(patch) => update_settings(patch, config),
+            Err(response) => response,
+        },
+        _ => Response::error(404, "接口不存在"),
+    }
+}
+
+fn with_task(manager: &DownloadManager, id: &str, action: impl FnOnce(&DownloadManager)) -> Response {
+    if !manager.get_tasks().iter().any(|task| task.read().id == id) {
+        return Response::error(404, "任务不存在");
+    }
+    action(manager);
+    Response::ok(json!({ "ok": true }))
+}
+
+fn task_json(task: &DownloadTask, queue_position: Option<usize>) -> Value {
+    let (status, progress, speed, error) = match &*task.status.read() {
+        DownloadStatus::Waiting => ("waiting", 0.0, String::new(), None),
+        DownloadStatus::Downloading { progress, speed } => ("downloading", *progress, speed.clone(), None),
+        DownloadStatus::Paused => ("paused", 0.0, String::new(), None),
+        DownloadStatus::Merging { progress } => ("merging", *progress, String::new(), None),
+        DownloadStatus::Completed => ("completed", 1.0, String::new(), None),
+        DownloadStatus::Skipped => ("skipped", 1.0, String::new(), None),
+        DownloadStatus::Failed(error) => ("failed", 0.0, String::new(), Some(error.to_string())),
+    };
+
+    json!({
+        "id": task.id,
+        "bvid": task.bvid,
+        "cid": task.cid,
+        "title": task.display_title(),
+        "author": task.author,
+        "quality": task.quality,
+        "format": if task.is_mp3 { "mp3" } else { "video" },
+        "status": status,
+        "progress": progress,
+        "speed": speed,
+        "error": error,
+        "retries": task.retries,
+        "queue_position": queue_position,
+        "output_path": task.output_path,
+        "created_at": task.created_at,
+    })
+}
+
+async fn add_tasks(add: AddRequest, manager: &DownloadManager, api: &BilibiliApi, config: &RwLock<Config>) -> Response {
+    let is_mp3 = match add.format.as_deref().map(str::to_lowercase).as_deref() {
+        None | Some("video") | Some("mp4") => false,
+        Some("mp3") | Some("audio") => true,
+        Some(other) => return Response::error(400, &format!("未知的格式: {}", other)),
+    };
+
+    let parsed = match api.parse_input(&add.url).await {
+        Ok(parsed) => parsed,
+        Err(e) => return Response::error(400, &e.to_string()),
+    };
+    let videos = match resolve_videos(parsed, api).await {
+        Ok(videos) => videos,
+        Err(e) => return Response::error(400, &e),
+    };
+
+    let mut ids = Vec::new();
+    for video in &videos {
+        let target_ep_id = video.season.as_ref().and_then(|s| s.target_ep_id);
+        let is_multi_part = video.pages.len() > 1 || video.season.is_some();
+        for page in &video.pages {
+            let selected = match &add.parts {
+                Some(parts) => parts.contains(&page.page),
+                None => target_ep_id.is_none() || page.ep_id == target_ep_id,
+            };
+            if !selected {
+                continue;
+            }
+
+            let mut task = DownloadTask::for_page(video, page, add.quality, is_mp3, is_multi_part);
+            task.apply_config(&config.read());
+            if let Some(codecs) = &add.codecs {
+                task.codecs = codecs.clone();
+            }
+            task.audio_quality = add.audio_quality;
+            task.danmaku = add.danmaku;
+            task.subtitles = add.subtitles;
+            ids.push(task.id.clone());
+            manager.add_task(task);
+        }
+    }
+
+    if ids.is_empty() {
+        return Response::error(400, "没有可下载的视频");
+    }
+    Response::ok(json!({ "ids": ids }))
+}
+
+/// 解析为带分P信息的视频，列表中的视频逐个获取详情
+async fn resolve_videos(parsed: ParsedInput, api: &BilibiliApi) -> Result<Vec<VideoInfo>, String> {
+    if !parsed.is_list() {
+        return Ok(vec![api.get_parsed_video_info(parsed).await?]);
+    }
+
+    let mut stubs = Vec::new();
+    let mut pn = 1;
+    loop {
+        let page = api.get_video_list_page(&parsed, pn).await?;
+        stubs.extend(page.videos);
+        if !page.has_more {
+            break;
+        }
+        pn += 1;
+    }
+
+    let mut videos = Vec::new();
+    for stub in stubs {
+        match api.get_video_basic_info(&stub.bvid).await {
+            Ok(video) => videos.push(video),
+            Err(_e) => {
+                debug_eprintln!("获取视频信息失败 {}: {}", stub.bvid, _e);
+            }
+        }
+    }
+    Ok(videos)
+}
+
+fn settings_json(config: &Config) -> Value {
+    let mut value = serde_json::to_value(config).unwrap_or_default();
+    if let Some(object) = value.as_object_mut() {
+        for key in PROTECTED_SETTINGS {
+            object.remove(key);
+        }
+    }
+    value
+}
+
+/// 合并部分设置项，校验失败时不做任何修改
+fn update_settings(patch: Value, config: &RwLock<Config>) -> Response {
+    let Some(patch) = patch.as_object() else {
+        return Response::error(400, "请求体必须是JSON对象");
+    };
+    if let Some(key) = PROTECTED_SETTINGS.iter().find(|key| patch.contains_key(**key)) {
+        return Response::error(400, &format!("不允许修改设置项: {}", key));
+    }
+
+    let mut config = config.write();
+    let mut value = serde_json::to_value(&*config).unwrap_or_default();
+    if let Some(object) = value.as_object_mut() {
+        for (key, new_value) in patch {
+            if !object.contains_key(key) {
+                return Response::error(400, &format!("未知的设置项: {}", key));
+            }
+            object.insert(key.clone(), new_value.clone());
+        }
+    }
+
+    match serde_json::from_value::<Config>(value) {
+        Ok(new_config) => {
+            *config = new_config;
+            config.save();
+            Response::ok(settings_json(&config))
+        }
+        Err(e) => Response::error(400, &format!("设置项无效: {}", e)),
+    }
+}
@@ -0,0 +1,323 @@
+// subscription.rs
+// 订阅UP主投稿、收藏夹、合集和系列，定时检查并只下载新视频。
+// 已加入下载的BV号记录在archive.txt中，每行一个，可以手动编辑
+use crate::bilibili::{BilibiliApi, ParsedInput, VideoInfo};
+use crate::config::Config;
+use crate::downloader::{DownloadManager, DownloadTask};
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::runtime::Runtime;
+
+#[cfg(debug_assertions)]
+macro_rules! debug_eprintln {
+    ($($arg:tt)*) => { eprintln!($($arg)*) }
+}
+#[cfg(not(debug_assertions))]
+macro_rules! debug_eprintln {
+    ($($arg:tt)*) => {}
+}
+
+/// 默认检查间隔（分钟）
+pub const DEFAULT_INTERVAL: u32 = 60;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Subscription {
+    pub id: String,
+    /// 添加订阅时输入的链接
+    pub url: String,
+    pub title: String,
+    pub quality: u32,
+    pub is_mp3: bool,
+    #[serde(default)]
+    pub danmaku: bool,
+    #[serde(default)]
+    pub subtitles: bool,
+    pub enabled: bool,
+    /// 检查间隔（分钟）
+    pub interval: u32,
+    /// 上次检查的时间（秒级时间戳），0表示从未检查
+    #[serde(default)]
+    pub last_checked: i64,
+    /// 上次检查加入下载的视频数
+    #[serde(default)]
+    pub last_added: usize,
+    #[serde(default)]
+    pub last_error: Option<String>,
+}
+
+/// 添加订阅时的下载选项
+#[derive(Debug, Clone)]
+pub struct SubscriptionOptions {
+    pub quality: u32,
+    pub is_mp3: bool,
+    pub danmaku: bool,
+    pub subtitles: bool,
+    pub interval: u32,
+    /// 是否下载订阅时已有的视频，否则只下载之后的新视频
+    pub download_existing: bool,
+}
+
+/// 订阅管理，所有状态都在Arc中，可以clone后在异步任务中使用
+#[derive(Clone)]
+pub struct SubscriptionManager {
+    subscriptions: Arc<RwLock<Vec<Subscription>>>,
+    archive: Arc<RwLock<HashSet<String>>>,
+    /// 正在检查的订阅ID
+    syncing: Arc<RwLock<HashSet<String>>>,
+    runtime: Arc<Runtime>,
+    api: Arc<BilibiliApi>,
+    download_manager: Arc<DownloadManager>,
+    config: Arc<RwLock<Config>>,
+}
+
+impl SubscriptionManager {
+    pub fn new(
+        runtime: Arc<Runtime>,
+        api: Arc<BilibiliApi>,
+        download_manager: Arc<DownloadManager>,
+        config: Arc<RwLock<Config>>,
+    ) -> Self {
+        let subscriptions = std::fs::read_to_string(Self::subscriptions_path())
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+        let archive = std::fs::read_to_string(Self::archive_path())
+            .map(|content| {
+                content
+                    .lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Self {
+            subscriptions: Arc::new(RwLock::new(subscriptions)),
+            archive: Arc::new(RwLock::new(archive)),
+            syncing: Arc::new(RwLock::new(HashSet::new())),
+            runtime,
+            api,
+            download_manager,
+            config,
+        }
+    }
+
+    fn subscriptions_path() -> PathBuf {
+        Config::data_file("subscriptions.json")
+    }
+
+    fn archive_path() -> PathBuf {
+        Config::data_file("archive.txt")
+    }
+
+    fn save(&self) {
+        if let Ok(content) = serde_json::to_string_pretty(&*self.subscriptions.read()) {
+            let _ = std::fs::write(Self::subscriptions_path(), content);
+        }
+    }
+
+    fn save_archive(&self) {
+        let mut bvids: Vec<String> = self.archive.read().iter().cloned().collect();
+        bvids.sort();
+        let _ = std::fs::write(Self::archive_path(), bvids.join("\n") + "\n");
+    }
+
+    /// 启动后台定时检查，每分钟检查一次有没有到期的订阅
+    pub fn start(&self) {
+        let manager = self.clone();
+        self.runtime.spawn(async move {
+            loop {
+                manager.sync_due().await;
+                tokio::time::sleep(tokio::time::Duration::from_secs(60)).await;
+            }
+        });
+    }
+
+    pub fn subscriptions(&self) -> Vec<Subscription> {
+        self.subscriptions.read().clone()
+    }
+
+    pub fn is_syncing(&self, id: &str) -> bool {
+        self.syncing.read().contains(id)
+    }
+
+    /// 添加订阅。不下载已有视频时，把当前列表中的视频全部记入存档
+    pub async fn add(&self, url: &str, options: SubscriptionOptions) -> Result<Subscription, String> {
+        let parsed = self.api.parse_input(url).await?;
+        if !parsed.is_list() {
+            return Err("只能订阅UP主空间、收藏夹、合集或系列".to_string());
+        }
+
+        let first_page = self.api.get_video_list_page(&parsed, 1).await?;
+        let subscription = Subscription {
+            id: uuid::Uuid::new_v4().to_string(),
+            url: url.trim().to_string(),
+            title: first_page.title,
+            quality: options.quality,
+            is_mp3: options.is_mp3,
+            danmaku: options.danmaku,
+            subtitles: options.subtitles,
+            enabled: true,
+            interval: options.interval.max(1),
+            last_checked: 0,
+            last_added: 0,
+            last_error: None,
+        };
+
+        if !options.download_existing {
+            let stubs = self.list_videos(&parsed, false).await?;
+            self.archive.write().extend(stubs.into_iter().map(|video| video.bvid));
+            self.save_archive();
+        }
+
+        self.subscriptions.write().push(subscription.clone());
+        self.save();
+
+        if options.download_existing {
+            let _ = self.sync(&subscription.id).await;
+        } else {
+            self.update(&subscription.id, |s| s.last_checked = chrono::Local::now().timestamp());
+        }
+        Ok(subscription)
+    }
+
+    pub fn remove(&self, id: &str) {
+        self.subscriptions.write().retain(|s| s.id != id);
+        self.save();
+    }
+
+    pub fn set_enabled(&self, id: &str, enabled: bool) {
+        self.update(id, |s| s.enabled = enabled);
+    }
+
+    fn update(&self, id: &str, f: impl FnOnce(&mut Subscription)) {
+        if let Some(subscription) = self.subscriptions.write().iter_mut().find(|s| s.id == id) {
+            f(subscription);
+        }
+        self.save();
+    }
+
+    /// 检查所有到期的订阅
+    pub async fn sync_due(&self) {
+        let now = chrono::Local::now().timestamp();
+        let due: Vec<String> = self
+            .subscriptions
+            .read()
+            .iter()
+            .filter(|s| s.enabled && now - s.last_checked >= s.interval as i64 * 60)
+            .map(|s| s.id.clone())
+            .collect();
+        for id in due {
+            let _ = self.sync(&id).await;
+        }
+    }
+
+    /// 检查所有启用的订阅，返回加入下载的任务ID
+    pub async fn sync_all(&self) -> Vec<String> {
+        let ids: Vec<String> = self
+            .subscriptions
+            .read()
+            .iter()
+            .filter(|s| s.enabled)
+            .map(|s| s.id.clone())
+            .collect();
+        let mut task_ids = Vec::new();
+        for id in ids {
+            if let Ok(new_ids) = self.sync(&id).await {
+                task_ids.extend(new_ids);
+            }
+        }
+        task_ids
+    }
+
+    /// 立即检查一个订阅，把存档中没有的视频加入下载，返回新任务的ID
+    pub async fn sync(&self, id: &str) -> Result<Vec<String>, String> {
+        let Some(subscription) = self.subscriptions.read().iter().find(|s| s.id == id).cloned() else {
+            return Err("订阅不存在".to_string());
+        };
+        if !self.syncing.write().insert(id.to_string()) {
+            return Err("正在检查中".to_string());
+        }
+
+        let result = self.sync_subscription(&subscription).await;
+        self.syncing.write().remove(id);
+
+        let now = chrono::Local::now().timestamp();
+        match &result {
+            Ok(task_ids) => self.update(id, |s| {
+                s.last_checked = now;
+                s.last_added = task_ids.len();
+                s.last_error = None;
+            }),
+            Err(e) => self.update(id, |s| {
+                s.last_checked = now;
+                s.last_error = Some(e.clone());
+            }),
+        }
+        result
+    }
+
+    async fn sync_subscription(&self, subscription: &Subscription) -> Result<Vec<String>, String> {
+        let parsed = self.api.parse_input(&subscription.url).await?;
+        let stubs = self.list_videos(&parsed, true).await?;
+
+        // 从旧到新加入下载
+        let mut task_ids = Vec::new();
+        for stub in stubs.iter().rev() {
+            let video = match self.api.get_video_basic_info(&stub.bvid).await {
+                Ok(video) => video,
+                Err(_e) => {
+                    // 不记入存档，下次检查时重试
+                    debug_eprintln!("获取视频信息失败 {}: {}", stub.bvid, _e);
+                    continue;
+                }
+            };
+
+            let is_multi_part = video.pages.len() > 1;
+            for page in &video.pages {
+                let mut task = DownloadTask::for_page(&video, page, subscription.quality, subscription.is_mp3, is_multi_part);
+                task.apply_config(&self.config.read());
+                task.danmaku = subscription.danmaku;
+                task.subtitles = subscription.subtitles;
+                task_ids.push(task.id.clone());
+                self.download_manager.add_task(task);
+            }
+
+            self.archive.write().insert(stub.bvid.clone());
+            self.save_archive();
+        }
+        Ok(task_ids)
+    }
+
+    /// 获取列表中的视频。only_new为true时跳过存档中的视频，
+    /// 按时间倒序的列表遇到整页都已存档时停止翻页
+    async fn list_videos(&self, parsed: &ParsedInput, only_new: bool) -> Result<Vec<VideoInfo>, String> {
+        // 合集按时间正序排列，新视频在最后，需要取完所有页
+        let newest_first = !matches!(parsed, ParsedInput::Collection { .. });
+
+        let mut videos = Vec::new();
+        let mut pn = 1;
+        loop {
+            let page = self.api.get_video_list_page(parsed, pn).await?;
+            let page_size = page.videos.len();
+            let new_videos: Vec<VideoInfo> = if only_new {
+                let archive = self.archive.read();
+                page.videos.into_iter().filter(|video| !archive.contains(&video.bvid)).collect()
+            } else {
+                page.videos
+            };
+
+            let all_archived = new_videos.is_empty() && page_size > 0;
+            videos.extend(new_videos);
+            if !page.has_more || (only_new && newest_first && all_archived) {
+                break;
+            }
+            pn += 1;
+        }
+        Ok(videos)
+    }
+}
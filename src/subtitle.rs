@@ -0,0 +1,82 @@
+// CC/AI字幕转换为SRT和WebVTT
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SubtitleLine {
+    /// 开始时间（秒）
+    pub from: f64,
+    /// 结束时间（秒）
+    pub to: f64,
+    pub content: String,
+}
+
+/// 下载后保存在本地的一条字幕轨道
+#[derive(Debug, Clone)]
+pub struct SubtitleFile {
+    /// B站语言代码，如zh-CN、en-US、ai-zh
+    pub lan: String,
+    /// 语言名称，如“中文（中国）”
+    pub lan_doc: String,
+    pub srt_path: std::path::PathBuf,
+}
+
+fn format_timestamp(seconds: f64, separator: char) -> String {
+    let millis = (seconds.max(0.0) * 1000.0).round() as u64;
+    format!(
+        "{:02}:{:02}:{:02}{}{:03}",
+        millis / 3_600_000,
+        millis / 60_000 % 60,
+        millis / 1000 % 60,
+        separator,
+        millis % 1000
+    )
+}
+
+pub fn to_srt(lines: &[SubtitleLine]) -> String {
+    let mut srt = String::new();
+    for (index, line) in lines.iter().enumerate() {
+        srt.push_str(&format!(
+            "{}\n{} --> {}\n{}\n\n",
+            index + 1,
+            format_timestamp(line.from, ','),
+            format_timestamp(line.to, ','),
+            line.content.trim()
+        ));
+    }
+    srt
+}
+
+pub fn to_vtt(lines: &[SubtitleLine]) -> String {
+    let mut vtt = String::from("WEBVTT\n\n");
+    for line in lines {
+        vtt.push_str(&format!(
+            "{} --> {}\n{}\n\n",
+            format_timestamp(line.from, '.'),
+            format_timestamp(line.to, '.'),
+            line.content.trim()
+        ));
+    }
+    vtt
+}
+
+/// 转换为ffmpeg封装时使用的ISO 639-2语言代码，未知语言返回und
+pub fn iso639_2(lan: &str) -> &'static str {
+    let lan = lan.strip_prefix("ai-").unwrap_or(lan);
+    match lan.split('-').next().unwrap_or_default() {
+        "zh" => "chi",
+        "en" => "eng",
+        "ja" => "jpn",
+        "ko" => "kor",
+        "fr" => "fre",
+        "de" => "ger",
+        "es" => "spa",
+        "ru" => "rus",
+        "pt" => "por",
+        "it" => "ita",
+        "ar" => "ara",
+        "th" => "tha",
+        "vi" => "vie",
+        "id" => "ind",
+        _ => "und",
+    }
+}
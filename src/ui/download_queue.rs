@@ -4,6 +4,7 @@ use std::sync::Arc;
 use parking_lot::RwLock;
 use std::collections::HashMap;
 use crate::config::Language;
+use crate::error::ApiError;
 use std::sync::mpsc;
 
 // Windows平台特定导入
@@ -14,12 +15,39 @@ use std::os::windows::process::CommandExt;
 #[cfg(target_os = "windows")]
 const CREATE_NO_WINDOW: u32 = 0x08000000;
 
+/// 用系统文件管理器打开文件所在的文件夹
+pub fn open_folder(path: &std::path::Path) {
+    if let Some(parent) = path.parent() {
+        #[cfg(target_os = "windows")]
+        {
+            let mut cmd = std::process::Command::new("explorer");
+            cmd.arg(parent);
+            cmd.creation_flags(CREATE_NO_WINDOW);
+            let _ = cmd.spawn();
+        }
+        #[cfg(target_os = "macos")]
+        {
+            let _ = std::process::Command::new("open")
+                .arg(parent)
+                .spawn();
+        }
+        #[cfg(target_os = "linux")]
+        {
+            let _ = std::process::Command::new("xdg-open")
+                .arg(parent)
+                .spawn();
+        }
+    }
+}
+
 pub struct DownloadQueuePage {
     download_manager: Arc<DownloadManager>,
     cover_cache: HashMap<String, egui::TextureHandle>,
     cover_loading: HashMap<String, bool>,
     cover_receiver: Option<mpsc::Receiver<(String, Vec<u8>)>>,
     cover_sender: mpsc::Sender<(String, Vec<u8>)>,
+    /// 点击了失败任务上的登录按钮
+    login_requested: bool,
 }
 
 impl DownloadQueuePage {
@@ -31,9 +59,15 @@ impl DownloadQueuePage {
             cover_loading: HashMap::new(),
             cover_receiver: Some(rx),
             cover_sender: tx,
+            login_requested: false,
         }
     }
     
+    /// 是否需要打开登录窗口，读取后清除
+    pub fn take_login_request(&mut self) -> bool {
+        std::mem::take(&mut self.login_requested)
+    }
+    
     pub fn show_with_texts(&mut self, ui: &mut egui::Ui, pause_text: &str, resume_text: &str, delete_text: &str) {
         // 处理接收到的封面图片
         if let Some(receiver) = &self.cover_receiver {
@@ -97,6 +131,52 @@ impl DownloadQueuePage {
         
         let mut actions = Vec::new();
         
+        let (start_all_text, stop_all_text, limit_text, task_limit_text, unlimited_text, schedule_text) = {
+            let config = self.download_manager.get_config();
+            let lang = config.read().language.clone();
+            match lang {
+                Language::SimplifiedChinese => ("全部开始", "全部暂停", "限速", "单个下载", "不限速", "当前时段"),
+                Language::English => ("Start All", "Pause All", "Limit", "Per Download", "Unlimited", "Scheduled"),
+            }
+        };
+        
+        ui.horizontal(|ui| {
+            if ui.button(start_all_text).clicked() {
+                self.download_manager.start_all();
+            }
+            if ui.button(stop_all_text).clicked() {
+                self.download_manager.stop_all();
+            }
+            
+            // 修改后由下载管理器在一秒内应用到正在下载的任务
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                let config = self.download_manager.get_config();
+                let mut config = config.write();
+                let format_limit = |value: f64, _: std::ops::RangeInclusive<usize>| {
+                    if value <= 0.0 { unlimited_text.to_string() } else { format!("{} KB/s", value) }
+                };
+                let task_limit = ui.add(egui::DragValue::new(&mut config.task_speed_limit)
+                    .range(0..=1_000_000)
+                    .speed(16)
+                    .custom_formatter(format_limit));
+                ui.label(task_limit_text);
+                let limit = ui.add(egui::DragValue::new(&mut config.speed_limit)
+                    .range(0..=1_000_000)
+                    .speed(16)
+                    .custom_formatter(format_limit));
+                ui.label(limit_text);
+                // 拖动过程中只修改内存中的设置，结束后再保存
+                if [task_limit, limit].iter().any(|r| r.drag_stopped() || r.lost_focus()) {
+                    config.save();
+                }
+                
+                if let Some(rule) = config.active_speed_rule(chrono::Local::now().time()) {
+                    let rule_limit = if rule.limit == 0 { unlimited_text.to_string() } else { format!("{} KB/s", rule.limit) };
+                    ui.label(egui::RichText::new(format!("{} {}-{}: {}", schedule_text, rule.start, rule.end, rule_limit)).weak());
+                }
+            });
+        });
+        
         egui::ScrollArea::vertical().show(ui, |ui| {
             for task in &tasks {
                 ui.add_space(5.0);
@@ -112,6 +192,9 @@ impl DownloadQueuePage {
             match action.as_str() {
                 "pause" => self.download_manager.pause_task(&task_id),
                 "resume" => self.download_manager.resume_task(&task_id),
+                "top" => self.download_manager.move_to_top(&task_id),
+                "retry" => self.download_manager.retry_task(&task_id),
+                "login" => self.login_requested = true,
                 "delete" => {
                     self.download_manager.cancel_task(&task_id);
                     // 清理缓存
@@ -136,13 +219,14 @@ impl DownloadQueuePage {
         let lang = config.read().language.clone();
         
         ui.horizontal(|ui| {
-            let (task_id, task_title, task_author, is_mp3, cover_url) = {
+            let (task_id, task_bvid, task_title, task_author, extension, cover_url) = {
                 let task_read = task.read();
                 (
                     task_read.id.clone(),
-                    task_read.title.clone(),
+                    task_read.bvid.clone(),
+                    task_read.display_title(),
                     task_read.author.clone(),
-                    task_read.is_mp3,
+                    task_read.output_extension().to_uppercase(),
                     task_read.cover.clone(),
                 )
             };
@@ -196,14 +280,14 @@ impl DownloadQueuePage {
                 ui.label(egui::RichText::new(&task_title).size(16.0).strong());
                 
                 let (author_label, bv_label, format_label) = match lang {
-                    Language::SimplifiedChinese => ("作者", "BV号", if is_mp3 { "格式: MP3" } else { "格式: 视频" }),
-                    Language::English => ("Author", "BV ID", if is_mp3 { "Format: MP3" } else { "Format: Video" }),
+                    Language::SimplifiedChinese => ("作者", "BV号", format!("格式: {}", extension)),
+                    Language::English => ("Author", "BV ID", format!("Format: {}", extension)),
                 };
                 
                 ui.horizontal(|ui| {
                     ui.label(format!("{}: {}", author_label, task_author));
                     ui.separator();
-                    ui.label(format!("{}: {}", bv_label, task_id));
+                    ui.label(format!("{}: {}", bv_label, task_bvid));
                     ui.separator();
                     ui.label(format_label);
                 });
@@ -215,14 +299,32 @@ impl DownloadQueuePage {
                 
                 match &*status {
                     DownloadStatus::Waiting => {
-                        let waiting_text = match lang {
-                            Language::SimplifiedChinese => "等待下载...",
-                            Language::English => "Waiting to download...",
+                        let position = self.download_manager.queue_position(&task_id);
+                        let (waiting_text, top_text) = match (&lang, position) {
+                            (Language::SimplifiedChinese, Some(position)) => (format!("排队中 (第 {} 位)", position + 1), "置顶"),
+                            (Language::SimplifiedChinese, None) => ("等待下载...".to_string(), "置顶"),
+                            (Language::English, Some(position)) => (format!("Queued (#{})", position + 1), "Move to Top"),
+                            (Language::English, None) => ("Waiting to download...".to_string(), "Move to Top"),
                         };
                         ui.horizontal(|ui| {
                             ui.spinner();
                             ui.label(waiting_text);
                         });
+                        
+                        ui.add_space(5.0);
+                        ui.horizontal(|ui| {
+                            if position.is_some_and(|position| position > 0) && ui.button(top_text).clicked() {
+                                action = Some((task_id.clone(), "top".to_string()));
+                            }
+                            if ui.button(pause_text).clicked() {
+                                action = Some((task_id.clone(), "pause".to_string()));
+                            }
+                            if ui.button(egui::RichText::new(delete_text)
+                                .color(egui::Color32::from_rgb(255, 100, 100)))
+                                .clicked() {
+                                action = Some((task_id.clone(), "delete".to_string()));
+                            }
+                        });
                     }
                     DownloadStatus::Downloading { progress, speed } => {
                         let downloading_text = match lang {
@@ -270,8 +372,11 @@ impl DownloadQueuePage {
                             .show_percentage()
                             .animate(true));
                     }
-                    DownloadStatus::Completed => {
+                    DownloadStatus::Completed | DownloadStatus::Skipped => {
+                        let skipped = *status == DownloadStatus::Skipped;
                         let completed_text = match lang {
+                            Language::SimplifiedChinese if skipped => "文件已存在，已跳过",
+                            Language::English if skipped => "File exists, skipped",
                             Language::SimplifiedChinese => "下载完成",
                             Language::English => "Download completed",
                         };
@@ -285,27 +390,7 @@ impl DownloadQueuePage {
                             ui.label(egui::RichText::new(completed_text).color(egui::Color32::GREEN));
                             if let Some(path) = &task_read.output_path {
                                 if ui.button(open_folder_text).clicked() {
-                                    if let Some(parent) = path.parent() {
-                                        #[cfg(target_os = "windows")]
-                                        {
-                                            let mut cmd = std::process::Command::new("explorer");
-                                            cmd.arg(parent);
-                                            cmd.creation_flags(CREATE_NO_WINDOW);
-                                            let _ = cmd.spawn();
-                                        }
-                                        #[cfg(target_os = "macos")]
-                                        {
-                                            let _ = std::process::Command::new("open")
-                                                .arg(parent)
-                                                .spawn();
-                                        }
-                                        #[cfg(target_os = "linux")]
-                                        {
-                                            let _ = std::process::Command::new("xdg-open")
-                                                .arg(parent)
-                                                .spawn();
-                                        }
-                                    }
+                                    open_folder(path);
                                 }
                             }
                         });
@@ -320,9 +405,21 @@ impl DownloadQueuePage {
                         });
                     }
                     DownloadStatus::Failed(err) => {
-                        let failed_text = match lang {
-                            Language::SimplifiedChinese => format!("失败: {}", err),
-                            Language::English => format!("Failed: {}", err),
+                        let message = err.localized(&lang);
+                        let retry_delay = task_read.retry_delay;
+                        let (failed_text, retry_text, login_text) = match lang {
+                            Language::SimplifiedChinese if retry_delay > 0 => (
+                                format!("失败: {} ({}秒后第{}次重试)", message, retry_delay, task_read.retries),
+                                "重试",
+                                "登录",
+                            ),
+                            Language::English if retry_delay > 0 => (
+                                format!("Failed: {} (retry {} in {}s)", message, task_read.retries, retry_delay),
+                                "Retry",
+                                "Login",
+                            ),
+                            Language::SimplifiedChinese => (format!("失败: {}", message), "重试", "登录"),
+                            Language::English => (format!("Failed: {}", message), "Retry", "Login"),
                         };
                         ui.horizontal(|ui| {
                             ui.label(egui::RichText::new("✗").color(egui::Color32::RED).size(16.0));
@@ -331,6 +428,12 @@ impl DownloadQueuePage {
                         
                         ui.add_space(5.0);
                         ui.horizontal(|ui| {
+                            if *err == ApiError::NotLoggedIn && ui.button(login_text).clicked() {
+                                action = Some((task_id.clone(), "login".to_string()));
+                            }
+                            if ui.button(retry_text).clicked() {
+                                action = Some((task_id.clone(), "retry".to_string()));
+                            }
                             if ui.button(egui::RichText::new(delete_text)
                                 .color(egui::Color32::from_rgb(255, 100, 100)))
                                 .clicked() {
@@ -0,0 +1,77 @@
+use eframe::egui;
+use crate::bilibili::{BilibiliApi, FavoriteFolder};
+use crate::config::{Config, Language};
+use crate::error::ApiError;
+use std::sync::Arc;
+use tokio::runtime::Runtime;
+use std::sync::mpsc;
+use parking_lot::RwLock;
+
+/// 当前登录用户的收藏夹列表，选择后返回收藏夹的media_id
+pub struct FavoriteFoldersWindow {
+    folders: Option<Result<Vec<FavoriteFolder>, ApiError>>,
+    receiver: Option<mpsc::Receiver<Result<Vec<FavoriteFolder>, ApiError>>>,
+    config: Arc<RwLock<Config>>,
+}
+
+impl FavoriteFoldersWindow {
+    pub fn new(api: Arc<BilibiliApi>, runtime: Arc<Runtime>, config: Arc<RwLock<Config>>) -> Self {
+        let (tx, rx) = mpsc::channel();
+        runtime.spawn(async move {
+            let _ = tx.send(api.get_favorite_folders().await);
+        });
+
+        Self {
+            folders: None,
+            receiver: Some(rx),
+            config,
+        }
+    }
+
+    pub fn show(&mut self, ui: &mut egui::Ui) -> Option<u64> {
+        let mut selected = None;
+
+        if let Some(receiver) = &self.receiver {
+            if let Ok(result) = receiver.try_recv() {
+                self.folders = Some(result);
+                self.receiver = None;
+                ui.ctx().request_repaint();
+            }
+        }
+
+        let language = self.config.read().language.clone();
+        let (loading_text, empty_text, count_text) = match language {
+            Language::SimplifiedChinese => ("正在获取收藏夹...", "没有收藏夹", "个视频"),
+            Language::English => ("Loading folders...", "No folders", "videos"),
+        };
+
+        match &self.folders {
+            None => {
+                ui.horizontal(|ui| {
+                    ui.spinner();
+                    ui.label(loading_text);
+                });
+            }
+            Some(Err(e)) => {
+                ui.colored_label(egui::Color32::from_rgb(255, 100, 100), e.localized(&language));
+            }
+            Some(Ok(folders)) if folders.is_empty() => {
+                ui.label(empty_text);
+            }
+            Some(Ok(folders)) => {
+                egui::ScrollArea::vertical()
+                    .max_height(300.0)
+                    .show(ui, |ui| {
+                        for folder in folders {
+                            let label = format!("{}  ({} {})", folder.title, folder.media_count, count_text);
+                            if ui.selectable_label(false, label).clicked() {
+                                selected = Some(folder.id);
+                            }
+                        }
+                    });
+            }
+        }
+
+        selected
+    }
+}
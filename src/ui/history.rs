@@ -0,0 +1,188 @@
+use eframe::egui;
+use crate::bilibili;
+use crate::config::{Config, Language};
+use crate::history::{self, History, HistoryEntry};
+use crate::ui::download_queue::open_folder;
+use std::sync::Arc;
+use parking_lot::RwLock;
+
+pub struct HistoryPage {
+    history: Arc<History>,
+    config: Arc<RwLock<Config>>,
+    search: String,
+    message: Option<String>,
+}
+
+impl HistoryPage {
+    pub fn new(history: Arc<History>, config: Arc<RwLock<Config>>) -> Self {
+        Self {
+            history,
+            config,
+            search: String::new(),
+            message: None,
+        }
+    }
+
+    fn get_text(&self, key: &str) -> String {
+        let lang = self.config.read().language.clone();
+        match lang {
+            Language::SimplifiedChinese => {
+                match key {
+                    "title" => "下载历史".to_string(),
+                    "search_hint" => "搜索标题、UP主或BV号".to_string(),
+                    "export" => "导出".to_string(),
+                    "clear" => "清空记录".to_string(),
+                    "empty" => "还没有下载记录".to_string(),
+                    "no_match" => "没有匹配的记录".to_string(),
+                    "open_folder" => "打开文件夹".to_string(),
+                    "redownload" => "重新下载".to_string(),
+                    "redownload_hint" => "重新解析视频，可以选择更高的画质".to_string(),
+                    "remove" => "删除记录".to_string(),
+                    "file_missing" => "文件已不存在".to_string(),
+                    "exported" => "已导出".to_string(),
+                    "records" => "条记录".to_string(),
+                    _ => key.to_string(),
+                }
+            }
+            Language::English => {
+                match key {
+                    "title" => "Download History".to_string(),
+                    "search_hint" => "Search title, uploader or BV ID".to_string(),
+                    "export" => "Export".to_string(),
+                    "clear" => "Clear".to_string(),
+                    "empty" => "No downloads yet".to_string(),
+                    "no_match" => "No matching records".to_string(),
+                    "open_folder" => "Open Folder".to_string(),
+                    "redownload" => "Download Again".to_string(),
+                    "redownload_hint" => "Parse the video again to pick a higher quality".to_string(),
+                    "remove" => "Remove".to_string(),
+                    "file_missing" => "File no longer exists".to_string(),
+                    "exported" => "Exported".to_string(),
+                    "records" => "records".to_string(),
+                    _ => key.to_string(),
+                }
+            }
+        }
+    }
+
+    /// 显示历史页面，点击重新下载时返回视频链接
+    pub fn show(&mut self, ui: &mut egui::Ui) -> Option<String> {
+        let mut redownload = None;
+
+        ui.heading(egui::RichText::new(self.get_text("title")).size(24.0));
+        ui.separator();
+        ui.add_space(10.0);
+
+        let entries = self.history.search(&self.search);
+
+        let search_hint = self.get_text("search_hint");
+        ui.horizontal(|ui| {
+            ui.add(egui::TextEdit::singleline(&mut self.search)
+                .hint_text(search_hint)
+                .desired_width(300.0));
+            ui.label(format!("{} {}", entries.len(), self.get_text("records")));
+
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                if ui.button(self.get_text("clear")).clicked() {
+                    self.history.clear();
+                }
+                // 导出当前搜索结果
+                if ui.add_enabled(!entries.is_empty(), egui::Button::new(self.get_text("export"))).clicked() {
+                    self.export(&entries);
+                }
+            });
+        });
+
+        if let Some(message) = &self.message {
+            ui.label(egui::RichText::new(message).weak());
+        }
+        ui.add_space(5.0);
+
+        if entries.is_empty() {
+            ui.vertical_centered(|ui| {
+                ui.add_space(40.0);
+                let text = if self.search.trim().is_empty() { "empty" } else { "no_match" };
+                ui.label(self.get_text(text));
+            });
+            return None;
+        }
+
+        egui::ScrollArea::vertical()
+            .id_salt("history_list")
+            .show(ui, |ui| {
+                for entry in &entries {
+                    if let Some(url) = self.show_entry(ui, entry) {
+                        redownload = Some(url);
+                    }
+                    ui.add_space(5.0);
+                }
+            });
+
+        redownload
+    }
+
+    fn show_entry(&self, ui: &mut egui::Ui, entry: &HistoryEntry) -> Option<String> {
+        let mut redownload = None;
+        ui.group(|ui| {
+            ui.set_width(ui.available_width());
+            ui.horizontal(|ui| {
+                ui.vertical(|ui| {
+                    ui.label(egui::RichText::new(&entry.title).size(16.0).strong());
+
+                    let completed_at = chrono::DateTime::from_timestamp_millis(entry.completed_at)
+                        .map(|time| time.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M").to_string())
+                        .unwrap_or_default();
+                    let mut details = vec![entry.author.clone(), entry.bvid.clone()];
+                    if entry.is_mp3 {
+                        details.push("MP3".to_string());
+                    } else {
+                        details.push(bilibili::quality_name(entry.quality));
+                        if let Some(codec) = entry.codec {
+                            details.push(codec.short_name().to_string());
+                        }
+                    }
+                    details.push(format!("{:.1} MB", entry.size as f64 / 1024.0 / 1024.0));
+                    details.push(completed_at);
+                    ui.label(egui::RichText::new(details.join(" · ")).weak());
+
+                    if entry.output_path.exists() {
+                        ui.label(egui::RichText::new(entry.output_path.display().to_string()).small().weak());
+                    } else {
+                        ui.colored_label(egui::Color32::from_rgb(255, 150, 50), self.get_text("file_missing"));
+                    }
+                });
+
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    if ui.button(self.get_text("remove")).clicked() {
+                        self.history.remove(&entry.id);
+                    }
+                    if ui.button(self.get_text("redownload"))
+                        .on_hover_text(self.get_text("redownload_hint"))
+                        .clicked() {
+                        redownload = Some(entry.url());
+                    }
+                    if entry.output_path.exists() && ui.button(self.get_text("open_folder")).clicked() {
+                        open_folder(&entry.output_path);
+                    }
+                });
+            });
+        });
+        redownload
+    }
+
+    fn export(&mut self, entries: &[HistoryEntry]) {
+        let file_name = format!("bilidown-history-{}.csv", chrono::Local::now().format("%Y%m%d"));
+        let Some(path) = rfd::FileDialog::new()
+            .add_filter("CSV", &["csv"])
+            .add_filter("JSON", &["json"])
+            .set_file_name(file_name)
+            .save_file() else {
+            return;
+        };
+
+        self.message = Some(match history::export(entries, &path) {
+            Ok(()) => format!("{}: {}", self.get_text("exported"), path.display()),
+            Err(e) => e,
+        });
+    }
+}
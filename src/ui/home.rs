@@ -69,12 +69,11 @@ impl HomePage {
                         .font(egui::TextStyle::Body)
                 );
                 
-                if ui.button(egui::RichText::new(parse_btn_text).size(16.0)).clicked() 
-                    || (response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter))) {
-                    if !self.input.is_empty() {
-                        parse_requested = Some(self.input.clone());
-                        self.input.clear();
-                    }
+                if (ui.button(egui::RichText::new(parse_btn_text).size(16.0)).clicked() 
+                    || (response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter))))
+                    && !self.input.is_empty() {
+                    parse_requested = Some(self.input.clone());
+                    self.input.clear();
                 }
                 ui.add_space(input_margin);
             });
@@ -101,6 +100,7 @@ impl HomePage {
         parse_requested
     }
     
+    #[allow(clippy::too_many_arguments)]
     pub fn show_with_texts_and_language(
         &mut self,
         ui: &mut egui::Ui,
@@ -157,12 +157,11 @@ impl HomePage {
                         .font(egui::TextStyle::Body)
                 );
                 
-                if ui.button(egui::RichText::new(parse_btn_text).size(16.0)).clicked() 
-                    || (response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter))) {
-                    if !self.input.is_empty() {
-                        parse_requested = Some(self.input.clone());
-                        self.input.clear();
-                    }
+                if (ui.button(egui::RichText::new(parse_btn_text).size(16.0)).clicked() 
+                    || (response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter))))
+                    && !self.input.is_empty() {
+                    parse_requested = Some(self.input.clone());
+                    self.input.clear();
                 }
                 ui.add_space(input_margin);
             });
@@ -182,6 +181,9 @@ impl HomePage {
                                 ui.label("• BV号: BV1xx411c7XE");
                                 ui.label("• 完整链接: https://www.bilibili.com/video/BV1xx411c7XE");
                                 ui.label("• 短链接: https://b23.tv/xxxxxx");
+                                ui.label("• 番剧: https://www.bilibili.com/bangumi/play/ep… / ss… / md…");
+                                ui.label("• 收藏夹: https://space.bilibili.com/…/favlist?fid=…");
+                                ui.label("• UP主空间/合集/系列: https://space.bilibili.com/…");
                             }
                             Language::English => {
                                 ui.label("Supported input formats:");
@@ -189,6 +191,9 @@ impl HomePage {
                                 ui.label("• BV ID: BV1xx411c7XE");
                                 ui.label("• Full URL: https://www.bilibili.com/video/BV1xx411c7XE");
                                 ui.label("• Short URL: https://b23.tv/xxxxxx");
+                                ui.label("• Bangumi: https://www.bilibili.com/bangumi/play/ep… / ss… / md…");
+                                ui.label("• Favorites: https://space.bilibili.com/…/favlist?fid=…");
+                                ui.label("• Uploader space / collection / series: https://space.bilibili.com/…");
                             }
                         }
                     });
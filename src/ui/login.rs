@@ -87,14 +87,14 @@ impl LoginWindow {
                 
                 ui.add_space(10.0);
                 
-                if matches!(self.status, LoginStatus::Waiting | LoginStatus::Scanned) {
-                    if ui.button("取消登录").clicked() {
-                        self.qrcode_image = None;
-                        self.qrcode_key = None;
-                        self.qrcode_url = None;
-                        self.status = LoginStatus::Waiting;
-                        self.checking = false;
-                    }
+                if matches!(self.status, LoginStatus::Waiting | LoginStatus::Scanned)
+                    && ui.button("取消登录").clicked()
+                {
+                    self.qrcode_image = None;
+                    self.qrcode_key = None;
+                    self.qrcode_url = None;
+                    self.status = LoginStatus::Waiting;
+                    self.checking = false;
                 }
             }
         });
@@ -110,20 +110,18 @@ impl LoginWindow {
             api.generate_qrcode().await
         });
         
-        if let Ok(result) = runtime.block_on(handle) {
-            if let Ok((url, key)) = result {
-                self.qrcode_url = Some(url.clone());
-                self.qrcode_key = Some(key);
-                
-                let qr_image = self.create_qrcode_image(&url);
-                self.qrcode_image = Some(ctx.load_texture(
-                    "qrcode",
-                    qr_image,
-                    Default::default(),
-                ));
-                self.status = LoginStatus::Waiting;
-                self.checking = false;
-            }
+        if let Ok(Ok((url, key))) = runtime.block_on(handle) {
+            self.qrcode_url = Some(url.clone());
+            self.qrcode_key = Some(key);
+            
+            let qr_image = self.create_qrcode_image(&url);
+            self.qrcode_image = Some(ctx.load_texture(
+                "qrcode",
+                qr_image,
+                Default::default(),
+            ));
+            self.status = LoginStatus::Waiting;
+            self.checking = false;
         }
     }
     
@@ -148,10 +146,8 @@ impl LoginWindow {
             api.poll_qrcode(&key).await
         });
         
-        if let Ok(result) = runtime.block_on(handle) {
-            if let Ok(status) = result {
-                self.status = status;
-            }
+        if let Ok(Ok(status)) = runtime.block_on(handle) {
+            self.status = status;
         }
         
         self.checking = false;
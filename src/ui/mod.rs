@@ -2,4 +2,8 @@ pub mod home;
 pub mod download_queue;
 pub mod settings;
 pub mod login;
-pub mod video_detail;
\ No newline at end of file
+pub mod video_detail;
+pub mod video_list;
+pub mod favorites;
+pub mod subscriptions;
+pub mod history;
\ No newline at end of file
@@ -1,16 +1,32 @@
 use eframe::egui;
-use crate::config::{Config, Theme, Language};
+use crate::bilibili;
+use crate::config::{CollisionPolicy, Config, DownloadBackendKind, SpeedRule, Theme, Language};
+use crate::filename::{self, TemplateVars};
 use std::sync::Arc;
+use std::sync::mpsc;
+use tokio::runtime::Runtime;
 use parking_lot::RwLock;
 
+/// 代理测试的进度和结果，成功时为耗时（毫秒）和出口地区
+#[derive(Default)]
+struct ProxyTest {
+    receiver: Option<mpsc::Receiver<Result<(u128, String), String>>>,
+    result: Option<Result<(u128, String), String>>,
+}
+
 pub struct SettingsPage {
     config: Arc<RwLock<Config>>,
+    runtime: Arc<Runtime>,
+    /// 接口代理和下载代理的测试
+    proxy_tests: [ProxyTest; 2],
 }
 
 impl SettingsPage {
-    pub fn new(config: Arc<RwLock<Config>>) -> Self {
+    pub fn new(config: Arc<RwLock<Config>>, runtime: Arc<Runtime>) -> Self {
         Self {
             config,
+            runtime,
+            proxy_tests: Default::default(),
         }
     }
     
@@ -18,7 +34,7 @@ impl SettingsPage {
         let mut theme_changed = false;
         let mut config_changed = false;
         
-        let (title_text, theme_text, language_text, threads_text, path_text, select_folder_text, restore_text, about_text, version_text, copyright_text, license_text, disclaimer_text) = {
+        let (title_text, theme_text, language_text, threads_text, backend_text, aria2_text, proxy_text, speed_rules_text, max_tasks_text, retries_text, codec_text, danmaku_text, subtitle_text, metadata_text, template_text, collision_text, api_text, path_text, select_folder_text, restore_text, about_text, version_text, copyright_text, license_text, disclaimer_text) = {
             let config = self.config.read();
             match config.language {
                 Language::SimplifiedChinese => (
@@ -26,6 +42,19 @@ impl SettingsPage {
                     "主题:",
                     "语言:",
                     "下载线程:",
+                    "下载引擎:",
+                    "aria2:",
+                    "代理:",
+                    "限速时段:",
+                    "同时下载:",
+                    "失败重试:",
+                    "编码优先顺序:",
+                    "弹幕字幕:",
+                    "CC字幕:",
+                    "元数据:",
+                    "文件名模板:",
+                    "文件已存在时:",
+                    "本地接口:",
                     "下载路径:",
                     "选择文件夹",
                     "恢复默认",
@@ -40,6 +69,19 @@ impl SettingsPage {
                     "Theme:",
                     "Language:",
                     "Download Threads:",
+                    "Download Engine:",
+                    "aria2:",
+                    "Proxy:",
+                    "Speed Schedule:",
+                    "Concurrent Downloads:",
+                    "Retry on Failure:",
+                    "Codec Preference:",
+                    "Danmaku Subtitles:",
+                    "CC Subtitles:",
+                    "Metadata:",
+                    "Filename Template:",
+                    "If File Exists:",
+                    "Local API:",
                     "Download Path:",
                     "Select Folder",
                     "Restore Defaults",
@@ -134,6 +176,342 @@ impl SettingsPage {
                 }
                 ui.end_row();
                 
+                // 下载器在启动时创建，切换后需要重启
+                ui.label(egui::RichText::new(backend_text).size(16.0));
+                let (backend_names, restart_hint) = match config.language {
+                    Language::SimplifiedChinese => (["aria2", "内置下载器"], "重启后生效"),
+                    Language::English => (["aria2", "Built-in"], "Takes effect after restart"),
+                };
+                ui.horizontal(|ui| {
+                    egui::ComboBox::from_id_salt("backend_combo")
+                        .selected_text(match config.download_backend {
+                            DownloadBackendKind::Aria2 => backend_names[0],
+                            DownloadBackendKind::Native => backend_names[1],
+                        })
+                        .show_ui(ui, |ui| {
+                            if ui.selectable_value(&mut config.download_backend, DownloadBackendKind::Aria2, backend_names[0]).clicked() {
+                                config_changed = true;
+                            }
+                            if ui.selectable_value(&mut config.download_backend, DownloadBackendKind::Native, backend_names[1]).clicked() {
+                                config_changed = true;
+                            }
+                        });
+                    ui.label(egui::RichText::new(restart_hint).weak().small());
+                });
+                ui.end_row();
+                
+                // 外部aria2可以是共用的守护进程，此时不会启动或结束aria2c
+                if config.download_backend == DownloadBackendKind::Aria2 {
+                    ui.label(egui::RichText::new(aria2_text).size(16.0));
+                    let (external_text, url_text, port_text, secret_text, secret_hint) = match config.language {
+                        Language::SimplifiedChinese => ("连接外部aria2", "RPC地址", "RPC端口", "RPC密钥", "留空时自动生成"),
+                        Language::English => ("Use external aria2", "RPC URL", "RPC Port", "RPC Secret", "Generated when empty"),
+                    };
+                    ui.vertical(|ui| {
+                        if ui.checkbox(&mut config.aria2_external, external_text).changed() {
+                            config_changed = true;
+                        }
+                        ui.horizontal(|ui| {
+                            if config.aria2_external {
+                                ui.label(url_text);
+                                let response = ui.add(egui::TextEdit::singleline(&mut config.aria2_rpc_url).desired_width(260.0));
+                                if response.lost_focus() {
+                                    config_changed = true;
+                                }
+                            } else {
+                                ui.label(port_text);
+                                if ui.add(egui::DragValue::new(&mut config.aria2_rpc_port).range(1024..=65535)).changed() {
+                                    config_changed = true;
+                                }
+                            }
+                        });
+                        ui.horizontal(|ui| {
+                            ui.label(secret_text);
+                            let hint = if config.aria2_external { "" } else { secret_hint };
+                            let secret_edit = egui::TextEdit::singleline(&mut config.aria2_secret)
+                                .password(true)
+                                .hint_text(hint)
+                                .desired_width(200.0);
+                            if ui.add(secret_edit).lost_focus() {
+                                config_changed = true;
+                            }
+                        });
+                    });
+                    ui.end_row();
+                }
+
+                // 接口请求和视频下载分别设置，修改后立即生效
+                ui.label(egui::RichText::new(proxy_text).size(16.0));
+                let proxy_texts = match config.language {
+                    Language::SimplifiedChinese => (["接口请求", "视频下载"], "用户名", "密码", "测试", "连接成功，耗时", "，出口地区", "aria2不支持SOCKS代理，重启后将使用内置下载器"),
+                    Language::English => (["API", "Media"], "Username", "Password", "Test", "Connected in", ", exit region", "aria2 does not support SOCKS proxies; the built-in downloader is used after restart"),
+                };
+                let (proxy_names, username_text, password_text, test_text, success_text, region_text, socks_hint) = proxy_texts;
+                ui.vertical(|ui| {
+                    for (index, test) in self.proxy_tests.iter_mut().enumerate() {
+                        if let Some(result) = test.receiver.as_ref().and_then(|receiver| receiver.try_recv().ok()) {
+                            test.result = Some(result);
+                            test.receiver = None;
+                        }
+                        let proxy = if index == 0 { &mut config.api_proxy } else { &mut config.media_proxy };
+                        ui.horizontal(|ui| {
+                            if ui.checkbox(&mut proxy.enabled, proxy_names[index]).changed() {
+                                config_changed = true;
+                            }
+                            let url = ui.add(egui::TextEdit::singleline(&mut proxy.url)
+                                .hint_text("socks5://127.0.0.1:1080")
+                                .desired_width(200.0));
+                            let username = ui.add(egui::TextEdit::singleline(&mut proxy.username)
+                                .hint_text(username_text)
+                                .desired_width(80.0));
+                            let password = ui.add(egui::TextEdit::singleline(&mut proxy.password)
+                                .hint_text(password_text)
+                                .password(true)
+                                .desired_width(80.0));
+                            if url.lost_focus() || username.lost_focus() || password.lost_focus() {
+                                config_changed = true;
+                            }
+                            
+                            let testing = test.receiver.is_some();
+                            if ui.add_enabled(proxy.enabled && !testing, egui::Button::new(test_text)).clicked() {
+                                let (tx, rx) = mpsc::channel();
+                                let proxy = proxy.clone();
+                                self.runtime.spawn(async move {
+                                    let _ = tx.send(bilibili::test_proxy(&proxy).await);
+                                });
+                                test.receiver = Some(rx);
+                                test.result = None;
+                            }
+                            if testing {
+                                ui.spinner();
+                                ui.ctx().request_repaint_after(std::time::Duration::from_millis(200));
+                            }
+                        });
+                        
+                        if let Err(e) = proxy.to_url() {
+                            ui.colored_label(egui::Color32::from_rgb(255, 100, 100), e);
+                        } else if index == 1 && proxy.is_socks() && config.download_backend == DownloadBackendKind::Aria2 {
+                            ui.label(egui::RichText::new(socks_hint).weak().small());
+                        }
+                        match &test.result {
+                            Some(Ok((elapsed, region))) => {
+                                let mut message = format!("{} {} ms", success_text, elapsed);
+                                if !region.is_empty() {
+                                    message.push_str(&format!("{}: {}", region_text, region));
+                                }
+                                ui.colored_label(egui::Color32::from_rgb(100, 200, 100), message);
+                            }
+                            Some(Err(e)) => {
+                                ui.colored_label(egui::Color32::from_rgb(255, 100, 100), e);
+                            }
+                            None => {}
+                        }
+                    }
+                });
+                ui.end_row();
+                
+                // 匹配的第一个时段代替下载页设置的整体限速
+                ui.label(egui::RichText::new(speed_rules_text).size(16.0));
+                let (add_rule_text, unlimited_text, invalid_text, rules_hint) = match config.language {
+                    Language::SimplifiedChinese => ("添加时段", "不限速", "时间格式应为 HH:MM", "其他时间使用下载页设置的限速"),
+                    Language::English => ("Add Window", "Unlimited", "Time must be HH:MM", "Other times use the limit set on the Downloads page"),
+                };
+                ui.vertical(|ui| {
+                    let mut remove = None;
+                    for (i, rule) in config.speed_rules.iter_mut().enumerate() {
+                        ui.horizontal(|ui| {
+                            let start = ui.add(egui::TextEdit::singleline(&mut rule.start).desired_width(50.0));
+                            ui.label("-");
+                            let end = ui.add(egui::TextEdit::singleline(&mut rule.end).desired_width(50.0));
+                            let limit = ui.add(egui::DragValue::new(&mut rule.limit)
+                                .range(0..=1_000_000)
+                                .speed(16)
+                                .custom_formatter(|value, _| {
+                                    if value <= 0.0 { unlimited_text.to_string() } else { format!("{} KB/s", value) }
+                                }));
+                            if start.lost_focus() || end.lost_focus() || limit.drag_stopped() || limit.lost_focus() {
+                                config_changed = true;
+                            }
+                            if ui.small_button("✖").clicked() {
+                                remove = Some(i);
+                            }
+                            let parse = |value: &str| chrono::NaiveTime::parse_from_str(value.trim(), "%H:%M").is_ok();
+                            if !parse(&rule.start) || !parse(&rule.end) {
+                                ui.colored_label(egui::Color32::from_rgb(255, 100, 100), invalid_text);
+                            }
+                        });
+                    }
+                    if let Some(i) = remove {
+                        config.speed_rules.remove(i);
+                        config_changed = true;
+                    }
+                    ui.horizontal(|ui| {
+                        if ui.button(add_rule_text).clicked() {
+                            config.speed_rules.push(SpeedRule {
+                                start: "01:00".to_string(),
+                                end: "07:00".to_string(),
+                                limit: 0,
+                            });
+                            config_changed = true;
+                        }
+                        ui.label(egui::RichText::new(rules_hint).weak().small());
+                    });
+                });
+                ui.end_row();
+                
+                ui.label(egui::RichText::new(max_tasks_text).size(16.0));
+                let max_tasks_label = |tasks: u32, language: &Language| match language {
+                    Language::SimplifiedChinese => format!("{} 个任务", tasks),
+                    Language::English => format!("{} Tasks", tasks),
+                };
+                egui::ComboBox::from_id_salt("max_tasks_combo")
+                    .selected_text(max_tasks_label(config.max_concurrent_tasks, &config.language))
+                    .show_ui(ui, |ui| {
+                        for tasks in 1u32..=5 {
+                            let label = max_tasks_label(tasks, &config.language);
+                            if ui.selectable_value(&mut config.max_concurrent_tasks, tasks, label).clicked() {
+                                config_changed = true;
+                            }
+                        }
+                    });
+                ui.end_row();
+                
+                ui.label(egui::RichText::new(retries_text).size(16.0));
+                let retries_label = |retries: u32, language: &Language| match (language, retries) {
+                    (Language::SimplifiedChinese, 0) => "不重试".to_string(),
+                    (Language::SimplifiedChinese, _) => format!("{} 次", retries),
+                    (Language::English, 0) => "Never".to_string(),
+                    (Language::English, _) => format!("{} Times", retries),
+                };
+                egui::ComboBox::from_id_salt("retries_combo")
+                    .selected_text(retries_label(config.max_retries, &config.language))
+                    .show_ui(ui, |ui| {
+                        for retries in [0u32, 1, 3, 5, 10] {
+                            let label = retries_label(retries, &config.language);
+                            if ui.selectable_value(&mut config.max_retries, retries, label).clicked() {
+                                config_changed = true;
+                            }
+                        }
+                    });
+                ui.end_row();
+                
+                // 点击箭头与前一项交换位置
+                ui.label(egui::RichText::new(codec_text).size(16.0));
+                ui.horizontal(|ui| {
+                    let mut move_up = None;
+                    for (i, codec) in config.codec_preference.iter().enumerate() {
+                        if i > 0 && ui.small_button("⬅").clicked() {
+                            move_up = Some(i);
+                        }
+                        ui.label(format!("{}. {}", i + 1, codec.name()));
+                    }
+                    if let Some(i) = move_up {
+                        config.codec_preference.swap(i - 1, i);
+                        config_changed = true;
+                    }
+                });
+                ui.end_row();
+                
+                ui.label(egui::RichText::new(danmaku_text).size(16.0));
+                let (size_text, opacity_text, density_text) = match config.language {
+                    Language::SimplifiedChinese => ("字号", "不透明度", "密度"),
+                    Language::English => ("Size", "Opacity", "Density"),
+                };
+                ui.horizontal(|ui| {
+                    let size = ui.add(egui::Slider::new(&mut config.danmaku_font_size, 24..=96).text(size_text));
+                    let opacity = ui.add(egui::Slider::new(&mut config.danmaku_opacity, 0.1..=1.0).text(opacity_text));
+                    let density = ui.add(egui::Slider::new(&mut config.danmaku_density, 10..=100).suffix("%").text(density_text));
+                    if size.changed() || opacity.changed() || density.changed() {
+                        config_changed = true;
+                    }
+                });
+                ui.end_row();
+                
+                ui.label(egui::RichText::new(subtitle_text).size(16.0));
+                let embed_text = match config.language {
+                    Language::SimplifiedChinese => "封装为视频的软字幕轨",
+                    Language::English => "Embed as soft subtitle tracks",
+                };
+                if ui.checkbox(&mut config.embed_subtitles, embed_text).changed() {
+                    config_changed = true;
+                }
+                ui.end_row();
+                
+                ui.label(egui::RichText::new(metadata_text).size(16.0));
+                let metadata_hint = match config.language {
+                    Language::SimplifiedChinese => "写入标题、UP主、简介、封面和章节",
+                    Language::English => "Write title, uploader, description, cover and chapters",
+                };
+                if ui.checkbox(&mut config.embed_metadata, metadata_hint).changed() {
+                    config_changed = true;
+                }
+                ui.end_row();
+                
+                // 模板中的 / 会创建子文件夹，下方实时预览生成的路径
+                ui.label(egui::RichText::new(template_text).size(16.0));
+                ui.vertical(|ui| {
+                    let response = ui.add(egui::TextEdit::singleline(&mut config.filename_template).desired_width(360.0));
+                    if response.lost_focus() {
+                        config_changed = true;
+                    }
+                    let preview = filename::render(&config.filename_template, &TemplateVars::sample());
+                    let preview_label = match config.language {
+                        Language::SimplifiedChinese => "预览",
+                        Language::English => "Preview",
+                    };
+                    ui.label(format!("{}: {}.mp4", preview_label, preview.display()));
+                    ui.label(egui::RichText::new(filename::TEMPLATE_VARIABLES.join(" ")).weak().small());
+                });
+                ui.end_row();
+                
+                ui.label(egui::RichText::new(collision_text).size(16.0));
+                let collision_label = |policy: CollisionPolicy, language: &Language| match (language, policy) {
+                    (Language::SimplifiedChinese, CollisionPolicy::Skip) => "跳过",
+                    (Language::SimplifiedChinese, CollisionPolicy::Rename) => "自动重命名",
+                    (Language::SimplifiedChinese, CollisionPolicy::Overwrite) => "覆盖",
+                    (Language::English, CollisionPolicy::Skip) => "Skip",
+                    (Language::English, CollisionPolicy::Rename) => "Rename",
+                    (Language::English, CollisionPolicy::Overwrite) => "Overwrite",
+                };
+                egui::ComboBox::from_id_salt("collision_combo")
+                    .selected_text(collision_label(config.collision_policy, &config.language))
+                    .show_ui(ui, |ui| {
+                        for policy in [CollisionPolicy::Skip, CollisionPolicy::Rename, CollisionPolicy::Overwrite] {
+                            let label = collision_label(policy, &config.language);
+                            if ui.selectable_value(&mut config.collision_policy, policy, label).clicked() {
+                                config_changed = true;
+                            }
+                        }
+                    });
+                ui.end_row();
+                
+                // 供浏览器脚本和自动化工具调用，只监听本机
+                ui.label(egui::RichText::new(api_text).size(16.0));
+                let (enable_text, port_text, token_text, copy_text) = match config.language {
+                    Language::SimplifiedChinese => ("启用", "端口", "访问令牌", "复制"),
+                    Language::English => ("Enable", "Port", "Token", "Copy"),
+                };
+                ui.vertical(|ui| {
+                    ui.horizontal(|ui| {
+                        if ui.checkbox(&mut config.api_enabled, enable_text).changed() {
+                            config_changed = true;
+                        }
+                        ui.label(port_text);
+                        if ui.add(egui::DragValue::new(&mut config.api_port).range(1024..=65535)).changed() {
+                            config_changed = true;
+                        }
+                    });
+                    if config.api_enabled && !config.api_token.is_empty() {
+                        ui.horizontal(|ui| {
+                            ui.label(format!("http://127.0.0.1:{}/api  {}: {}", config.api_port, token_text, config.api_token));
+                            if ui.small_button(copy_text).clicked() {
+                                ui.ctx().copy_text(config.api_token.clone());
+                            }
+                        });
+                    }
+                });
+                ui.end_row();
+                
                 ui.label(egui::RichText::new(path_text).size(16.0));
                 ui.horizontal(|ui| {
                     ui.label(config.download_path.display().to_string());
@@ -0,0 +1,280 @@
+use eframe::egui;
+use crate::config::{Config, Language};
+use crate::subscription::{self, Subscription, SubscriptionManager, SubscriptionOptions};
+use crate::ui::video_list::PREFERRED_QUALITIES;
+use std::sync::Arc;
+use tokio::runtime::Runtime;
+use std::sync::mpsc;
+use parking_lot::RwLock;
+
+pub struct SubscriptionsPage {
+    manager: SubscriptionManager,
+    runtime: Arc<Runtime>,
+    config: Arc<RwLock<Config>>,
+    url_input: String,
+    quality: u32,
+    is_mp3: bool,
+    with_danmaku: bool,
+    with_subtitles: bool,
+    interval: u32,
+    download_existing: bool,
+    adding: bool,
+    add_receiver: Option<mpsc::Receiver<Result<Subscription, String>>>,
+    error: Option<String>,
+}
+
+impl SubscriptionsPage {
+    pub fn new(manager: SubscriptionManager, runtime: Arc<Runtime>, config: Arc<RwLock<Config>>) -> Self {
+        Self {
+            manager,
+            runtime,
+            config,
+            url_input: String::new(),
+            quality: 80,
+            is_mp3: false,
+            with_danmaku: false,
+            with_subtitles: false,
+            interval: subscription::DEFAULT_INTERVAL,
+            download_existing: false,
+            adding: false,
+            add_receiver: None,
+            error: None,
+        }
+    }
+
+    fn get_text(&self, key: &str) -> String {
+        let lang = self.config.read().language.clone();
+        match lang {
+            Language::SimplifiedChinese => {
+                match key {
+                    "title" => "订阅".to_string(),
+                    "hint" => "订阅UP主空间、收藏夹、合集或系列，定时检查并自动下载新视频".to_string(),
+                    "url_hint" => "粘贴UP主空间、收藏夹、合集或系列链接".to_string(),
+                    "add" => "添加订阅".to_string(),
+                    "quality" => "画质".to_string(),
+                    "audio_only" => "仅音频（MP3）".to_string(),
+                    "with_danmaku" => "下载弹幕".to_string(),
+                    "with_subtitles" => "下载字幕".to_string(),
+                    "interval" => "检查间隔".to_string(),
+                    "minutes" => "分钟".to_string(),
+                    "download_existing" => "同时下载已有视频（否则只下载之后发布的新视频）".to_string(),
+                    "empty" => "还没有订阅".to_string(),
+                    "enabled" => "启用".to_string(),
+                    "sync_now" => "立即检查".to_string(),
+                    "delete" => "删除".to_string(),
+                    "never_checked" => "尚未检查".to_string(),
+                    "last_checked" => "上次检查".to_string(),
+                    "added" => "新增".to_string(),
+                    "syncing" => "检查中...".to_string(),
+                    _ => key.to_string(),
+                }
+            }
+            Language::English => {
+                match key {
+                    "title" => "Subscriptions".to_string(),
+                    "hint" => "Subscribe to uploaders, favorites, collections or series and download new videos automatically".to_string(),
+                    "url_hint" => "Paste an uploader space, favorites, collection or series link".to_string(),
+                    "add" => "Subscribe".to_string(),
+                    "quality" => "Quality".to_string(),
+                    "audio_only" => "Audio only (MP3)".to_string(),
+                    "with_danmaku" => "Danmaku".to_string(),
+                    "with_subtitles" => "Subtitles".to_string(),
+                    "interval" => "Check every".to_string(),
+                    "minutes" => "min".to_string(),
+                    "download_existing" => "Also download existing videos (otherwise only new uploads)".to_string(),
+                    "empty" => "No subscriptions yet".to_string(),
+                    "enabled" => "Enabled".to_string(),
+                    "sync_now" => "Check Now".to_string(),
+                    "delete" => "Delete".to_string(),
+                    "never_checked" => "Never checked".to_string(),
+                    "last_checked" => "Last checked".to_string(),
+                    "added" => "added".to_string(),
+                    "syncing" => "Checking...".to_string(),
+                    _ => key.to_string(),
+                }
+            }
+        }
+    }
+
+    pub fn show(&mut self, ui: &mut egui::Ui) {
+        if let Some(receiver) = &self.add_receiver {
+            if let Ok(result) = receiver.try_recv() {
+                self.add_receiver = None;
+                self.adding = false;
+                match result {
+                    Ok(_) => {
+                        self.url_input.clear();
+                        self.error = None;
+                    }
+                    Err(e) => self.error = Some(e),
+                }
+            }
+        }
+
+        ui.heading(egui::RichText::new(self.get_text("title")).size(24.0));
+        ui.label(egui::RichText::new(self.get_text("hint")).weak());
+        ui.separator();
+        ui.add_space(10.0);
+
+        self.show_add_form(ui);
+
+        ui.add_space(10.0);
+        ui.separator();
+        ui.add_space(10.0);
+
+        let subscriptions = self.manager.subscriptions();
+        if subscriptions.is_empty() {
+            ui.vertical_centered(|ui| {
+                ui.add_space(40.0);
+                ui.label(self.get_text("empty"));
+            });
+        } else {
+            egui::ScrollArea::vertical()
+                .id_salt("subscriptions_list")
+                .show(ui, |ui| {
+                    for subscription in &subscriptions {
+                        self.show_subscription(ui, subscription);
+                        ui.add_space(5.0);
+                    }
+                });
+        }
+
+        // 检查在后台进行，定时刷新显示状态
+        ui.ctx().request_repaint_after(std::time::Duration::from_secs(1));
+    }
+
+    fn show_add_form(&mut self, ui: &mut egui::Ui) {
+        let url_hint = self.get_text("url_hint");
+        let minutes_text = self.get_text("minutes");
+        ui.horizontal(|ui| {
+            ui.add(egui::TextEdit::singleline(&mut self.url_input)
+                .hint_text(url_hint)
+                .desired_width(420.0));
+            let can_add = !self.adding && !self.url_input.trim().is_empty();
+            if ui.add_enabled(can_add, egui::Button::new(self.get_text("add"))).clicked() {
+                self.add_subscription();
+            }
+            if self.adding {
+                ui.spinner();
+            }
+        });
+
+        ui.horizontal(|ui| {
+            ui.label(format!("{}:", self.get_text("quality")));
+            let selected_text = PREFERRED_QUALITIES
+                .iter()
+                .find(|(id, _)| *id == self.quality)
+                .map(|(_, desc)| *desc)
+                .unwrap_or("1080P");
+            egui::ComboBox::from_id_salt("subscription_quality")
+                .selected_text(selected_text)
+                .show_ui(ui, |ui| {
+                    for (id, desc) in PREFERRED_QUALITIES {
+                        ui.selectable_value(&mut self.quality, id, desc);
+                    }
+                });
+
+            ui.label(format!("{}:", self.get_text("interval")));
+            ui.add(egui::DragValue::new(&mut self.interval).range(5..=10080).suffix(minutes_text));
+        });
+
+        ui.horizontal(|ui| {
+            let audio_only_text = self.get_text("audio_only");
+            ui.checkbox(&mut self.is_mp3, audio_only_text);
+            let with_danmaku_text = self.get_text("with_danmaku");
+            ui.checkbox(&mut self.with_danmaku, with_danmaku_text);
+            let with_subtitles_text = self.get_text("with_subtitles");
+            ui.checkbox(&mut self.with_subtitles, with_subtitles_text);
+        });
+        let download_existing_text = self.get_text("download_existing");
+        ui.checkbox(&mut self.download_existing, download_existing_text);
+
+        if let Some(error) = &self.error {
+            ui.colored_label(egui::Color32::from_rgb(255, 100, 100), error);
+        }
+    }
+
+    fn add_subscription(&mut self) {
+        let manager = self.manager.clone();
+        let url = self.url_input.trim().to_string();
+        let options = SubscriptionOptions {
+            quality: self.quality,
+            is_mp3: self.is_mp3,
+            danmaku: self.with_danmaku,
+            subtitles: self.with_subtitles,
+            interval: self.interval,
+            download_existing: self.download_existing,
+        };
+        let (tx, rx) = mpsc::channel();
+        self.add_receiver = Some(rx);
+        self.adding = true;
+
+        self.runtime.spawn(async move {
+            let _ = tx.send(manager.add(&url, options).await);
+        });
+    }
+
+    fn show_subscription(&mut self, ui: &mut egui::Ui, subscription: &Subscription) {
+        ui.group(|ui| {
+            ui.set_width(ui.available_width());
+            ui.horizontal(|ui| {
+                ui.vertical(|ui| {
+                    ui.label(egui::RichText::new(&subscription.title).size(16.0).strong());
+                    ui.label(egui::RichText::new(&subscription.url).weak().small());
+
+                    let quality = PREFERRED_QUALITIES
+                        .iter()
+                        .find(|(id, _)| *id == subscription.quality)
+                        .map(|(_, desc)| desc.to_string())
+                        .unwrap_or_else(|| subscription.quality.to_string());
+                    let format = if subscription.is_mp3 { "MP3" } else { "Video" };
+                    let status = if self.manager.is_syncing(&subscription.id) {
+                        self.get_text("syncing")
+                    } else if subscription.last_checked == 0 {
+                        self.get_text("never_checked")
+                    } else {
+                        let checked = chrono::DateTime::from_timestamp(subscription.last_checked, 0)
+                            .map(|time| time.with_timezone(&chrono::Local).format("%m-%d %H:%M").to_string())
+                            .unwrap_or_default();
+                        format!(
+                            "{}: {}, {} {}",
+                            self.get_text("last_checked"),
+                            checked,
+                            self.get_text("added"),
+                            subscription.last_added
+                        )
+                    };
+                    ui.label(format!(
+                        "{} · {} · {} {} · {}",
+                        quality,
+                        format,
+                        subscription.interval,
+                        self.get_text("minutes"),
+                        status
+                    ));
+                    if let Some(error) = &subscription.last_error {
+                        ui.colored_label(egui::Color32::from_rgb(255, 100, 100), error);
+                    }
+                });
+
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    if ui.button(self.get_text("delete")).clicked() {
+                        self.manager.remove(&subscription.id);
+                    }
+                    let syncing = self.manager.is_syncing(&subscription.id);
+                    if ui.add_enabled(!syncing, egui::Button::new(self.get_text("sync_now"))).clicked() {
+                        let manager = self.manager.clone();
+                        let id = subscription.id.clone();
+                        self.runtime.spawn(async move {
+                            let _ = manager.sync(&id).await;
+                        });
+                    }
+                    let mut enabled = subscription.enabled;
+                    if ui.checkbox(&mut enabled, self.get_text("enabled")).changed() {
+                        self.manager.set_enabled(&subscription.id, enabled);
+                    }
+                });
+            });
+        });
+    }
+}
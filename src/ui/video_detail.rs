@@ -1,5 +1,5 @@
 use eframe::egui;
-use crate::bilibili::{BilibiliApi, VideoInfo, QualityInfo};
+use crate::bilibili::{BilibiliApi, VideoInfo, QualityInfo, PageInfo, VideoCodec, AUDIO_DOLBY, AUDIO_HIRES};
 use crate::config::{Config, Language};
 use std::sync::Arc;
 use tokio::runtime::Runtime;
@@ -12,9 +12,31 @@ pub enum DownloadType {
     Mp3,
 }
 
+pub struct DownloadRequest {
+    pub video_info: VideoInfo,
+    pub quality: QualityInfo,
+    pub download_type: DownloadType,
+    /// 勾选的分P，单P视频只有一项
+    pub pages: Vec<PageInfo>,
+    /// 视频编码优先顺序
+    pub codecs: Vec<VideoCodec>,
+    /// 音轨ID，0表示码率最高的普通音轨
+    pub audio_quality: u32,
+    /// 同时下载弹幕
+    pub with_danmaku: bool,
+    /// 同时下载CC/AI字幕
+    pub with_subtitles: bool,
+}
+
 pub struct VideoDetailWindow {
     video_info: VideoInfo,
     selected_quality: usize,
+    selected_pages: Vec<bool>,
+    /// 本次下载指定的编码，None时按设置中的顺序
+    selected_codec: Option<VideoCodec>,
+    selected_audio: u32,
+    with_danmaku: bool,
+    with_subtitles: bool,
     api: Arc<BilibiliApi>,
     runtime: Arc<Runtime>,
     cover_texture: Option<egui::TextureHandle>,
@@ -33,9 +55,29 @@ impl VideoDetailWindow {
         
         let selected_quality = available_qualities.first().copied().unwrap_or(0);
         
+        // 默认使用最高的普通音质，无损和杜比需要手动选择
+        let selected_audio = video_info.audio_qualities
+            .iter()
+            .map(|a| a.id)
+            .filter(|&id| id != AUDIO_HIRES && id != AUDIO_DOLBY)
+            .max()
+            .unwrap_or(0);
+        
+        // 通过ep链接打开番剧时只预选该集，其余情况默认全选
+        let target_ep_id = video_info.season.as_ref().and_then(|s| s.target_ep_id);
+        let selected_pages = video_info.pages
+            .iter()
+            .map(|p| target_ep_id.is_none() || p.ep_id == target_ep_id)
+            .collect();
+        
         let mut window = Self {
             video_info: video_info.clone(),
             selected_quality,
+            selected_pages,
+            selected_codec: None,
+            selected_audio,
+            with_danmaku: false,
+            with_subtitles: false,
             api: api.clone(),
             runtime: runtime.clone(),
             cover_texture: None,
@@ -78,6 +120,41 @@ impl VideoDetailWindow {
         egui::ColorImage::from_rgba_unmultiplied([width, height], &pixels)
     }
     
+    fn format_duration(seconds: u64) -> String {
+        if seconds >= 3600 {
+            format!("{}:{:02}:{:02}", seconds / 3600, seconds % 3600 / 60, seconds % 60)
+        } else {
+            format!("{:02}:{:02}", seconds / 60, seconds % 60)
+        }
+    }
+    
+    fn format_bitrate(bandwidth: u64) -> String {
+        if bandwidth >= 1_000_000 {
+            format!("{:.1} Mbps", bandwidth as f64 / 1_000_000.0)
+        } else {
+            format!("{} kbps", bandwidth / 1000)
+        }
+    }
+    
+    /// 指定的编码排在最前，其余按设置中的顺序
+    fn codec_order(&self) -> Vec<VideoCodec> {
+        let mut codecs = self.config.read().codec_preference.clone();
+        if let Some(codec) = self.selected_codec {
+            codecs.retain(|c| *c != codec);
+            codecs.insert(0, codec);
+        }
+        codecs
+    }
+    
+    fn selected_pages(&self) -> Vec<PageInfo> {
+        self.video_info.pages
+            .iter()
+            .zip(self.selected_pages.iter())
+            .filter(|(_, &selected)| selected)
+            .map(|(page, _)| page.clone())
+            .collect()
+    }
+    
     fn get_text(&self, key: &str) -> String {
         let lang = self.config.read().language.clone();
         match lang {
@@ -88,6 +165,17 @@ impl VideoDetailWindow {
                     "video_description" => "视频简介".to_string(),
                     "select_quality" => "选择画质".to_string(),
                     "quality_unavailable" => "该画质不可用".to_string(),
+                    "select_parts" => "选择分P".to_string(),
+                    "select_episodes" => "选择剧集".to_string(),
+                    "select_all" => "全选".to_string(),
+                    "no_part_selected" => "请至少选择一个分P或剧集".to_string(),
+                    "video_codec" => "视频编码".to_string(),
+                    "codec_auto" => "自动（按设置顺序）".to_string(),
+                    "audio_quality" => "音质".to_string(),
+                    "hires_hint" => "无损音轨不转码：视频保存为MKV，仅音频保存为FLAC".to_string(),
+                    "dolby_hint" => "杜比音轨不转码：仅音频时保存为M4A".to_string(),
+                    "with_danmaku" => "同时下载弹幕（XML + ASS字幕）".to_string(),
+                    "with_subtitles" => "同时下载CC/AI字幕（SRT + VTT）".to_string(),
                     _ => key.to_string(),
                 }
             }
@@ -98,6 +186,17 @@ impl VideoDetailWindow {
                     "video_description" => "Video Description".to_string(),
                     "select_quality" => "Select Quality".to_string(),
                     "quality_unavailable" => "This quality is unavailable".to_string(),
+                    "select_parts" => "Select Parts".to_string(),
+                    "select_episodes" => "Select Episodes".to_string(),
+                    "select_all" => "Select All".to_string(),
+                    "no_part_selected" => "Select at least one part or episode".to_string(),
+                    "video_codec" => "Video Codec".to_string(),
+                    "codec_auto" => "Auto (settings order)".to_string(),
+                    "audio_quality" => "Audio Quality".to_string(),
+                    "hires_hint" => "Lossless audio is kept as-is: MKV for video, FLAC for audio only".to_string(),
+                    "dolby_hint" => "Dolby audio is kept as-is: M4A for audio only".to_string(),
+                    "with_danmaku" => "Also download danmaku (XML + ASS subtitles)".to_string(),
+                    "with_subtitles" => "Also download CC/AI subtitles (SRT + VTT)".to_string(),
                     _ => key.to_string(),
                 }
             }
@@ -110,7 +209,7 @@ impl VideoDetailWindow {
         download_video_text: &str,
         download_mp3_text: &str,
         cancel_text: &str
-    ) -> Option<(VideoInfo, QualityInfo, DownloadType)> {
+    ) -> Option<DownloadRequest> {
         let mut result = None;
         let mut should_close = false;
         
@@ -214,11 +313,7 @@ impl VideoDetailWindow {
                             let is_selectable = quality.is_available;
                             
                             ui.add_enabled_ui(is_selectable, |ui| {
-                                let label_text = if is_selectable {
-                                    quality.desc.clone()
-                                } else {
-                                    quality.desc.clone()
-                                };
+                                let label_text = quality.desc.clone();
                                 
                                 let label = if is_selectable {
                                     egui::RichText::new(label_text)
@@ -238,12 +333,121 @@ impl VideoDetailWindow {
                 ui.colored_label(egui::Color32::from_rgb(255, 100, 100), self.get_text("quality_unavailable"));
             }
             
+            // 当前画质下可用的编码和码率
+            let streams = self.video_info.qualities[self.selected_quality].streams.clone();
+            if !streams.is_empty() {
+                ui.add_space(5.0);
+                ui.horizontal(|ui| {
+                    ui.label(egui::RichText::new(format!("{}:", self.get_text("video_codec"))).strong());
+                    
+                    let auto_text = self.get_text("codec_auto");
+                    egui::ComboBox::from_id_salt("codec_select")
+                        .selected_text(self.selected_codec.map(|c| c.name().to_string()).unwrap_or_else(|| auto_text.clone()))
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(&mut self.selected_codec, None, auto_text);
+                            for stream in &streams {
+                                ui.selectable_value(&mut self.selected_codec, Some(stream.codec), stream.codec.name());
+                            }
+                        });
+                });
+                
+                for stream in &streams {
+                    ui.label(egui::RichText::new(format!(
+                        "{}  {}x{}  {} fps  {}",
+                        stream.codec.name(),
+                        stream.width,
+                        stream.height,
+                        stream.frame_rate.parse::<f32>().map(|fps| fps.round().to_string()).unwrap_or_else(|_| stream.frame_rate.clone()),
+                        Self::format_bitrate(stream.bandwidth)
+                    )).weak());
+                }
+            }
+            
+            if !self.video_info.audio_qualities.is_empty() {
+                ui.add_space(5.0);
+                ui.horizontal(|ui| {
+                    ui.label(egui::RichText::new(format!("{}:", self.get_text("audio_quality"))).strong());
+                    
+                    let audio_label = |id: u32| {
+                        self.video_info.audio_qualities
+                            .iter()
+                            .find(|a| a.id == id)
+                            .map(|a| format!("{}  {}", a.desc, Self::format_bitrate(a.bandwidth)))
+                            .unwrap_or_default()
+                    };
+                    egui::ComboBox::from_id_salt("audio_select")
+                        .selected_text(audio_label(self.selected_audio))
+                        .show_ui(ui, |ui| {
+                            for audio in &self.video_info.audio_qualities {
+                                ui.selectable_value(&mut self.selected_audio, audio.id, audio_label(audio.id));
+                            }
+                        });
+                });
+                
+                match self.selected_audio {
+                    AUDIO_HIRES => {
+                        ui.label(egui::RichText::new(self.get_text("hires_hint")).weak());
+                    }
+                    AUDIO_DOLBY => {
+                        ui.label(egui::RichText::new(self.get_text("dolby_hint")).weak());
+                    }
+                    _ => {}
+                }
+            }
+            
+            // 多P视频/番剧：分P或剧集选择
+            if self.video_info.pages.len() > 1 || self.video_info.season.is_some() {
+                ui.add_space(10.0);
+                ui.separator();
+                ui.add_space(10.0);
+                
+                ui.horizontal(|ui| {
+                    let select_parts_text = if self.video_info.season.is_some() {
+                        self.get_text("select_episodes")
+                    } else {
+                        self.get_text("select_parts")
+                    };
+                    ui.label(egui::RichText::new(format!("{} ({}):", select_parts_text, self.video_info.pages.len())).strong());
+                    
+                    let mut all_selected = self.selected_pages.iter().all(|&s| s);
+                    if ui.checkbox(&mut all_selected, self.get_text("select_all")).changed() {
+                        self.selected_pages.iter_mut().for_each(|s| *s = all_selected);
+                    }
+                });
+                
+                egui::ScrollArea::vertical()
+                    .id_salt("parts_list")
+                    .max_height(150.0)
+                    .show(ui, |ui| {
+                        for (i, page) in self.video_info.pages.iter().enumerate() {
+                            let label = if page.ep_id.is_some() {
+                                format!("{}  [{}]", page.part, Self::format_duration(page.duration))
+                            } else {
+                                format!("P{}  {}  [{}]", page.page, page.part, Self::format_duration(page.duration))
+                            };
+                            ui.checkbox(&mut self.selected_pages[i], label);
+                        }
+                    });
+                
+                if !self.selected_pages.iter().any(|&s| s) {
+                    ui.colored_label(egui::Color32::from_rgb(255, 100, 100), self.get_text("no_part_selected"));
+                }
+            }
+            
             ui.add_space(20.0);
             ui.separator();
-            ui.add_space(20.0);
+            ui.add_space(10.0);
+            
+            let with_danmaku_text = self.get_text("with_danmaku");
+            ui.checkbox(&mut self.with_danmaku, with_danmaku_text);
+            let with_subtitles_text = self.get_text("with_subtitles");
+            ui.checkbox(&mut self.with_subtitles, with_subtitles_text);
+            
+            ui.add_space(10.0);
             
             ui.horizontal(|ui| {
-                let is_quality_available = self.video_info.qualities[self.selected_quality].is_available;
+                let is_quality_available = self.video_info.qualities[self.selected_quality].is_available
+                    && self.selected_pages.iter().any(|&s| s);
                 
                 ui.add_enabled_ui(is_quality_available, |ui| {
                     if ui.button(egui::RichText::new(download_video_text)
@@ -254,11 +458,16 @@ impl VideoDetailWindow {
                             egui::Color32::from_rgb(128, 128, 128) 
                         }))
                         .clicked() {
-                        result = Some((
-                            self.video_info.clone(),
-                            self.video_info.qualities[self.selected_quality].clone(),
-                            DownloadType::Video,
-                        ));
+                        result = Some(DownloadRequest {
+                            video_info: self.video_info.clone(),
+                            quality: self.video_info.qualities[self.selected_quality].clone(),
+                            download_type: DownloadType::Video,
+                            pages: self.selected_pages(),
+                            codecs: self.codec_order(),
+                            audio_quality: self.selected_audio,
+                            with_danmaku: self.with_danmaku,
+                            with_subtitles: self.with_subtitles,
+                        });
                     }
                     
                     ui.add_space(10.0);
@@ -271,11 +480,16 @@ impl VideoDetailWindow {
                             egui::Color32::from_rgb(128, 128, 128) 
                         }))
                         .clicked() {
-                        result = Some((
-                            self.video_info.clone(),
-                            self.video_info.qualities[self.selected_quality].clone(),
-                            DownloadType::Mp3,
-                        ));
+                        result = Some(DownloadRequest {
+                            video_info: self.video_info.clone(),
+                            quality: self.video_info.qualities[self.selected_quality].clone(),
+                            download_type: DownloadType::Mp3,
+                            pages: self.selected_pages(),
+                            codecs: self.codec_order(),
+                            audio_quality: self.selected_audio,
+                            with_danmaku: self.with_danmaku,
+                            with_subtitles: self.with_subtitles,
+                        });
                     }
                 });
                 
@@ -0,0 +1,297 @@
+use eframe::egui;
+use crate::bilibili::{BilibiliApi, VideoInfo, VideoListPage, ParsedInput};
+use crate::config::{Config, Language};
+use crate::error::ApiError;
+use crate::ui::video_detail::DownloadType;
+use std::sync::Arc;
+use tokio::runtime::Runtime;
+use std::sync::mpsc;
+use parking_lot::RwLock;
+
+// 批量下载时可选的画质，实际下载时会回退到视频可用的最高画质
+pub const PREFERRED_QUALITIES: [(u32, &str); 8] = [
+    (127, "8K"),
+    (120, "4K"),
+    (116, "1080P 60"),
+    (112, "1080P+"),
+    (80, "1080P"),
+    (64, "720P"),
+    (32, "480P"),
+    (16, "360P"),
+];
+
+pub struct BatchDownloadRequest {
+    pub videos: Vec<VideoInfo>,
+    pub quality: u32,
+    pub download_type: DownloadType,
+    /// 同时下载弹幕
+    pub with_danmaku: bool,
+    /// 同时下载CC/AI字幕
+    pub with_subtitles: bool,
+}
+
+pub struct VideoListWindow {
+    source: ParsedInput,
+    title: String,
+    videos: Vec<VideoInfo>,
+    selected: Vec<bool>,
+    total: u32,
+    next_page: u32,
+    loading: bool,
+    error: Option<String>,
+    selected_quality: u32,
+    with_danmaku: bool,
+    with_subtitles: bool,
+    api: Arc<BilibiliApi>,
+    runtime: Arc<Runtime>,
+    page_receiver: Option<mpsc::Receiver<Result<VideoListPage, ApiError>>>,
+    config: Arc<RwLock<Config>>,
+    closed: bool,
+}
+
+impl VideoListWindow {
+    pub fn new(source: ParsedInput, api: Arc<BilibiliApi>, runtime: Arc<Runtime>, config: Arc<RwLock<Config>>) -> Self {
+        let mut window = Self {
+            source,
+            title: String::new(),
+            videos: Vec::new(),
+            selected: Vec::new(),
+            total: 0,
+            next_page: 1,
+            loading: false,
+            error: None,
+            selected_quality: 80,
+            with_danmaku: false,
+            with_subtitles: false,
+            api,
+            runtime,
+            page_receiver: None,
+            config,
+            closed: false,
+        };
+
+        window.load_next_page();
+        window
+    }
+
+    fn load_next_page(&mut self) {
+        let api = self.api.clone();
+        let source = self.source.clone();
+        let pn = self.next_page;
+        let (tx, rx) = mpsc::channel();
+        self.page_receiver = Some(rx);
+        self.loading = true;
+
+        self.runtime.spawn(async move {
+            let _ = tx.send(api.get_video_list_page(&source, pn).await);
+        });
+    }
+
+    /// 点击了取消按钮
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+    
+    fn get_text(&self, key: &str) -> String {
+        let lang = self.config.read().language.clone();
+        match lang {
+            Language::SimplifiedChinese => {
+                match key {
+                    "loading" => "正在加载列表...".to_string(),
+                    "select_all" => "全选".to_string(),
+                    "selected_count" => "已选择".to_string(),
+                    "preferred_quality" => "首选画质".to_string(),
+                    "quality_hint" => "不可用时自动使用最高可用画质".to_string(),
+                    "up_owner" => "UP主".to_string(),
+                    "empty_list" => "列表中没有可下载的视频".to_string(),
+                    "with_danmaku" => "同时下载弹幕（XML + ASS字幕）".to_string(),
+                    "with_subtitles" => "同时下载CC/AI字幕（SRT + VTT）".to_string(),
+                    _ => key.to_string(),
+                }
+            }
+            Language::English => {
+                match key {
+                    "loading" => "Loading list...".to_string(),
+                    "select_all" => "Select All".to_string(),
+                    "selected_count" => "Selected".to_string(),
+                    "preferred_quality" => "Preferred Quality".to_string(),
+                    "quality_hint" => "Falls back to the best available quality".to_string(),
+                    "up_owner" => "UP".to_string(),
+                    "empty_list" => "No downloadable videos in this list".to_string(),
+                    "with_danmaku" => "Also download danmaku (XML + ASS subtitles)".to_string(),
+                    "with_subtitles" => "Also download CC/AI subtitles (SRT + VTT)".to_string(),
+                    _ => key.to_string(),
+                }
+            }
+        }
+    }
+
+    pub fn show_with_texts(
+        &mut self,
+        ui: &mut egui::Ui,
+        download_video_text: &str,
+        download_mp3_text: &str,
+        cancel_text: &str
+    ) -> Option<BatchDownloadRequest> {
+        let mut result = None;
+        let mut should_close = false;
+
+        // 逐页加载，收到一页后自动请求下一页
+        if let Some(receiver) = &self.page_receiver {
+            if let Ok(page_result) = receiver.try_recv() {
+                self.page_receiver = None;
+                self.loading = false;
+                match page_result {
+                    Ok(page) => {
+                        self.title = page.title;
+                        self.total = page.total;
+                        self.selected.extend(std::iter::repeat_n(true, page.videos.len()));
+                        self.videos.extend(page.videos);
+                        if page.has_more {
+                            self.next_page += 1;
+                            self.load_next_page();
+                        }
+                    }
+                    Err(e) => {
+                        self.error = Some(e.localized(&self.config.read().language));
+                    }
+                }
+                ui.ctx().request_repaint();
+            }
+        }
+
+        ui.vertical(|ui| {
+            ui.horizontal(|ui| {
+                ui.label(egui::RichText::new(&self.title).size(18.0).strong());
+                ui.label(format!("({}/{})", self.videos.len(), self.total));
+                if self.loading {
+                    ui.spinner();
+                }
+            });
+
+            if let Some(error) = &self.error {
+                ui.colored_label(egui::Color32::from_rgb(255, 100, 100), error);
+            }
+
+            ui.add_space(5.0);
+            ui.separator();
+
+            if self.videos.is_empty() {
+                ui.vertical_centered(|ui| {
+                    ui.add_space(40.0);
+                    if self.loading {
+                        ui.label(self.get_text("loading"));
+                    } else {
+                        ui.label(self.get_text("empty_list"));
+                    }
+                    ui.add_space(40.0);
+                });
+            } else {
+                ui.horizontal(|ui| {
+                    let mut all_selected = self.selected.iter().all(|&s| s);
+                    if ui.checkbox(&mut all_selected, self.get_text("select_all")).changed() {
+                        self.selected.iter_mut().for_each(|s| *s = all_selected);
+                    }
+                    let selected_count = self.selected.iter().filter(|&&s| s).count();
+                    ui.label(format!("{}: {}", self.get_text("selected_count"), selected_count));
+                });
+
+                let up_owner_text = self.get_text("up_owner");
+                egui::ScrollArea::vertical()
+                    .id_salt("video_list")
+                    .max_height(300.0)
+                    .show(ui, |ui| {
+                        for (i, video) in self.videos.iter().enumerate() {
+                            ui.horizontal(|ui| {
+                                ui.checkbox(&mut self.selected[i], &video.title);
+                                // 合集/系列列表不含UP主信息
+                                if !video.owner.name.is_empty() {
+                                    ui.label(egui::RichText::new(format!("{}: {}", up_owner_text, video.owner.name)).weak());
+                                }
+                            });
+                        }
+                    });
+            }
+
+            ui.add_space(10.0);
+            ui.separator();
+            ui.add_space(10.0);
+
+            ui.horizontal(|ui| {
+                ui.label(egui::RichText::new(format!("{}:", self.get_text("preferred_quality"))).strong());
+                let selected_text = PREFERRED_QUALITIES
+                    .iter()
+                    .find(|(id, _)| *id == self.selected_quality)
+                    .map(|(_, desc)| *desc)
+                    .unwrap_or("1080P");
+                egui::ComboBox::from_id_salt("batch_quality_select")
+                    .selected_text(selected_text)
+                    .show_ui(ui, |ui| {
+                        for (id, desc) in PREFERRED_QUALITIES {
+                            ui.selectable_value(&mut self.selected_quality, id, desc);
+                        }
+                    });
+                ui.label(egui::RichText::new(self.get_text("quality_hint")).weak());
+            });
+
+            let with_danmaku_text = self.get_text("with_danmaku");
+            ui.checkbox(&mut self.with_danmaku, with_danmaku_text);
+            let with_subtitles_text = self.get_text("with_subtitles");
+            ui.checkbox(&mut self.with_subtitles, with_subtitles_text);
+
+            ui.add_space(20.0);
+
+            ui.horizontal(|ui| {
+                let has_selection = self.selected.iter().any(|&s| s);
+
+                ui.add_enabled_ui(has_selection, |ui| {
+                    let mut download_type = None;
+                    if ui.button(egui::RichText::new(download_video_text)
+                        .size(16.0)
+                        .color(egui::Color32::from_rgb(100, 200, 255)))
+                        .clicked() {
+                        download_type = Some(DownloadType::Video);
+                    }
+
+                    ui.add_space(10.0);
+
+                    if ui.button(egui::RichText::new(download_mp3_text)
+                        .size(16.0)
+                        .color(egui::Color32::from_rgb(100, 255, 150)))
+                        .clicked() {
+                        download_type = Some(DownloadType::Mp3);
+                    }
+
+                    if let Some(download_type) = download_type {
+                        let videos = self.videos
+                            .iter()
+                            .zip(self.selected.iter())
+                            .filter(|(_, &selected)| selected)
+                            .map(|(video, _)| video.clone())
+                            .collect();
+                        result = Some(BatchDownloadRequest {
+                            videos,
+                            quality: self.selected_quality,
+                            download_type,
+                            with_danmaku: self.with_danmaku,
+                            with_subtitles: self.with_subtitles,
+                        });
+                    }
+                });
+
+                ui.add_space(10.0);
+
+                if ui.button(egui::RichText::new(cancel_text).size(16.0)).clicked() {
+                    should_close = true;
+                }
+            });
+        });
+
+        if should_close {
+            self.closed = true;
+            None
+        } else {
+            result
+        }
+    }
+}
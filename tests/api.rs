@@ -0,0 +1,373 @@
+// tests/api.rs
+// BilibiliApi对接本地模拟服务的集成测试，响应为录制的接口JSON
+mod mock;
+
+use bilibili_down::bilibili::{BilibiliApi, BilibiliApiBuilder, LoginStatus, ParsedInput, VideoCodec, AUDIO_HIRES};
+use bilibili_down::error::ApiError;
+use mock::{MockResponse, MockServer};
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+const NAV: &str = include_str!("fixtures/nav.json");
+const NAV_LOGIN: &str = include_str!("fixtures/nav_login.json");
+const VIEW: &str = include_str!("fixtures/view.json");
+const PLAYURL_DASH: &str = include_str!("fixtures/playurl_dash.json");
+const PLAYURL_DURL: &str = include_str!("fixtures/playurl_durl.json");
+const QRCODE_GENERATE: &str = include_str!("fixtures/qrcode_generate.json");
+const QRCODE_WAITING: &str = include_str!("fixtures/qrcode_poll_waiting.json");
+const QRCODE_SCANNED: &str = include_str!("fixtures/qrcode_poll_scanned.json");
+const QRCODE_SUCCESS: &str = include_str!("fixtures/qrcode_poll_success.json");
+const QRCODE_EXPIRED: &str = include_str!("fixtures/qrcode_poll_expired.json");
+
+const BVID: &str = "BV1xx411c7mD";
+
+/// 创建指向模拟服务的BilibiliApi，Wbi密钥不写入文件
+fn run<F, Fut>(test: F)
+where
+    F: FnOnce(Arc<BilibiliApi>, MockServer) -> Fut,
+    Fut: Future<Output = ()>,
+{
+    run_with(|builder| builder.wbi_keys_path(None), test);
+}
+
+fn run_with<C, F, Fut>(configure: C, test: F)
+where
+    C: FnOnce(BilibiliApiBuilder) -> BilibiliApiBuilder,
+    F: FnOnce(Arc<BilibiliApi>, MockServer) -> Fut,
+    Fut: Future<Output = ()>,
+{
+    mock::run(|server| async move {
+        let builder = BilibiliApi::builder()
+            .base_url(&server.url)
+            .requests_per_second(0)
+            .risk_backoff(Duration::from_millis(10));
+        test(Arc::new(configure(builder).build()), server).await;
+    });
+}
+
+#[test]
+fn video_info_from_view_and_playurl() {
+    run(|api, server| async move {
+        server.route("/x/web-interface/nav", vec![MockResponse::json(NAV)]);
+        server.route("/x/web-interface/view", vec![MockResponse::json(VIEW)]);
+        server.route("/x/player/wbi/playurl", vec![MockResponse::json(PLAYURL_DASH)]);
+
+        let info = api.get_video_info(BVID).await.unwrap();
+        assert_eq!(info.bvid, BVID);
+        assert_eq!(info.title, "字幕君交流场所");
+        assert_eq!(info.owner.name, "碧诗");
+        assert_eq!(info.cid, 62131);
+        assert_eq!(info.pages.len(), 2);
+        assert_eq!(info.pages[1].part, "第二部分");
+
+        let ids: Vec<u32> = info.qualities.iter().map(|q| q.id).collect();
+        assert_eq!(ids, [80, 64, 32, 16]);
+        assert!(info.qualities.iter().all(|q| q.is_available));
+        let codecs: Vec<VideoCodec> = info.qualities[0].streams.iter().map(|s| s.codec).collect();
+        assert_eq!(codecs, [VideoCodec::Avc, VideoCodec::Hevc]);
+        let audio: Vec<u32> = info.audio_qualities.iter().map(|a| a.id).collect();
+        assert_eq!(audio, [30280, 30216]);
+
+        // 取到nav中的密钥后使用Wbi签名
+        let request = &server.requests("/x/player/wbi/playurl")[0];
+        assert_eq!(request.query["bvid"], BVID);
+        assert_eq!(request.query["cid"], "62131");
+        assert!(request.query.contains_key("wts"));
+        assert_eq!(request.query["w_rid"].len(), 32);
+        assert_eq!(server.requests("/x/web-interface/nav").len(), 1);
+    });
+}
+
+#[test]
+fn download_urls_from_dash() {
+    run(|api, server| async move {
+        server.route("/x/web-interface/nav", vec![MockResponse::json(NAV)]);
+        server.route("/x/player/wbi/playurl", vec![MockResponse::json(PLAYURL_DASH)]);
+
+        let urls = api
+            .get_download_urls(BVID, 62131, 80, &[VideoCodec::Hevc, VideoCodec::Avc], 0)
+            .await
+            .unwrap();
+        assert_eq!(urls.quality, 80);
+        assert_eq!(urls.codec, Some(VideoCodec::Hevc));
+        assert!(urls.video[0].ends_with("62131-1-100152.m4s"));
+        assert_eq!(urls.video.len(), 2);
+        // 未指定音轨时选码率最高的
+        assert_eq!(urls.audio_quality, 30280);
+        let audio = urls.audio.unwrap();
+        assert!(audio[0].ends_with("62131-1-30280.m4s"));
+
+        // 没有Hi-Res音轨时退回普通音轨，并返回实际选中的音轨
+        let urls = api
+            .get_download_urls(BVID, 62131, 80, &[VideoCodec::Avc], AUDIO_HIRES)
+            .await
+            .unwrap();
+        assert_eq!(urls.audio_quality, 30280);
+
+        // PCDN节点排到最后
+        let urls = api
+            .get_download_urls(BVID, 62131, 80, &[VideoCodec::Avc], 30216)
+            .await
+            .unwrap();
+        assert_eq!(urls.codec, Some(VideoCodec::Avc));
+        assert!(urls.video[0].contains("upos-sz-mirrorcos"));
+        assert!(urls.video[2].contains("mcdn.bilivideo.cn"));
+        assert_eq!(urls.audio_quality, 30216);
+        assert!(urls.audio.unwrap()[0].ends_with("62131-1-30216.m4s"));
+    });
+}
+
+#[test]
+fn download_urls_from_durl() {
+    run(|api, server| async move {
+        server.route("/x/web-interface/nav", vec![MockResponse::json(NAV)]);
+        server.route("/x/player/wbi/playurl", vec![MockResponse::json(PLAYURL_DURL)]);
+
+        let urls = api.get_download_urls(BVID, 62131, 80, &VideoCodec::ALL, 0).await.unwrap();
+        assert_eq!(urls.quality, 32);
+        assert_eq!(urls.codec, None);
+        assert!(urls.audio.is_none());
+        assert_eq!(urls.audio_quality, 0);
+        assert_eq!(urls.video.len(), 2);
+        assert!(urls.video[0].ends_with("62131-1-16.mp4"));
+    });
+}
+
+#[test]
+fn user_info_from_nav() {
+    run(|api, server| async move {
+        server.route("/x/web-interface/nav", vec![MockResponse::json(NAV_LOGIN)]);
+
+        api.set_cookies("SESSDATA=d3f5a1b2; bili_jct=4f1a2b3c").await;
+        let user = api.get_user_info().await.unwrap();
+        assert_eq!(user.mid, 2);
+        assert_eq!(user.name, "碧诗");
+        assert!(user.is_vip);
+
+        // 登录后仍然带上设备标识
+        let request = server.requests("/x/web-interface/nav").pop().unwrap();
+        let cookie = &request.headers["cookie"];
+        assert!(cookie.starts_with("SESSDATA=d3f5a1b2; bili_jct=4f1a2b3c; "));
+        assert!(cookie.contains("buvid3="));
+        assert!(cookie.contains("b_nut="));
+    });
+}
+
+#[test]
+fn anonymous_session() {
+    run(|api, server| async move {
+        server.route("/x/web-interface/nav", vec![MockResponse::json(NAV)]);
+
+        assert_eq!(api.get_user_info().await.unwrap_err(), ApiError::NotLoggedIn);
+
+        // 未登录时带上设备标识
+        let request = server.requests("/x/web-interface/nav").pop().unwrap();
+        let cookie = &request.headers["cookie"];
+        assert!(cookie.contains("buvid3="));
+        assert!(cookie.contains("buvid4="));
+        assert!(cookie.contains("b_nut="));
+        assert!(!cookie.contains("SESSDATA"));
+    });
+}
+
+#[test]
+fn qrcode_login_flow() {
+    run(|api, server| async move {
+        server.route("/x/passport-login/web/qrcode/generate", vec![MockResponse::json(QRCODE_GENERATE)]);
+        server.route(
+            "/x/passport-login/web/qrcode/poll",
+            vec![
+                MockResponse::json(QRCODE_WAITING),
+                MockResponse::json(QRCODE_SCANNED),
+                MockResponse::json(QRCODE_SUCCESS)
+                    .header("Set-Cookie", "SESSDATA=d3f5a1b2%2C1735689600%2C8c7e1*b1; Path=/; Domain=bilibili.com; HttpOnly; Secure")
+                    .header("Set-Cookie", "bili_jct=4f1a2b3c; Path=/; Domain=bilibili.com")
+                    .header("Set-Cookie", "DedeUserID=2; Path=/; Domain=bilibili.com"),
+                MockResponse::json(QRCODE_EXPIRED),
+            ],
+        );
+
+        let (url, key) = api.generate_qrcode().await.unwrap();
+        assert_eq!(key, "8e2b9f0a5c3d4e6f7a8b9c0d1e2f3a4b");
+        assert!(url.contains(&key));
+
+        assert!(matches!(api.poll_qrcode(&key).await.unwrap(), LoginStatus::Waiting));
+        assert!(matches!(api.poll_qrcode(&key).await.unwrap(), LoginStatus::Scanned));
+        match api.poll_qrcode(&key).await.unwrap() {
+            LoginStatus::Success { cookies } => {
+                assert_eq!(cookies, "SESSDATA=d3f5a1b2%2C1735689600%2C8c7e1*b1; bili_jct=4f1a2b3c");
+            }
+            status => panic!("unexpected status: {:?}", status),
+        }
+        assert!(matches!(api.poll_qrcode(&key).await.unwrap(), LoginStatus::Expired));
+
+        let requests = server.requests("/x/passport-login/web/qrcode/poll");
+        assert_eq!(requests.len(), 4);
+        assert!(requests.iter().all(|request| request.query["qrcode_key"] == key));
+    });
+}
+
+#[test]
+fn short_link_redirect() {
+    run(|api, server| async move {
+        server.route(
+            "/a1B2c3D",
+            vec![MockResponse::status(302, "")
+                .header("Location", "https://www.bilibili.com/video/BV1xx411c7mD?share_source=copy_web")],
+        );
+
+        let parsed = api.parse_input("【字幕君交流场所】 https://b23.tv/a1B2c3D").await.unwrap();
+        assert_eq!(parsed, ParsedInput::Video(BVID.to_string()));
+        assert_eq!(server.requests("/a1B2c3D").len(), 1);
+    });
+}
+
+#[test]
+fn risk_control_backoff() {
+    run(|api, server| async move {
+        server.route(
+            "/x/web-interface/view",
+            vec![
+                MockResponse::json(r#"{"code":-352,"message":"风控校验失败","ttl":1}"#),
+                MockResponse::status(412, "<html>412 Precondition Failed</html>"),
+                MockResponse::json(VIEW),
+            ],
+        );
+
+        let info = api.get_video_basic_info(BVID).await.unwrap();
+        assert_eq!(info.title, "字幕君交流场所");
+        assert_eq!(server.requests("/x/web-interface/view").len(), 3);
+    });
+}
+
+#[test]
+fn risk_control_gives_up() {
+    run(|api, server| async move {
+        server.route(
+            "/x/web-interface/view",
+            vec![MockResponse::status(412, "<html>412 Precondition Failed</html>")],
+        );
+
+        let error = api.get_video_basic_info(BVID).await.unwrap_err();
+        assert_eq!(error, ApiError::RateLimited(-412));
+        assert!(error.is_retryable());
+        // 首次请求加3次重试
+        assert_eq!(server.requests("/x/web-interface/view").len(), 4);
+    });
+}
+
+#[test]
+fn error_codes() {
+    run(|api, server| async move {
+        server.route(
+            "/x/web-interface/view",
+            vec![
+                MockResponse::json(r#"{"code":-404,"message":"啥都木有","ttl":1}"#),
+                MockResponse::json(r#"{"code":62002,"message":"稿件不可见","ttl":1}"#),
+                MockResponse::json(r#"{"code":-400,"message":"请求错误","ttl":1}"#),
+            ],
+        );
+
+        assert_eq!(api.get_video_basic_info(BVID).await.unwrap_err(), ApiError::NotFound);
+        assert_eq!(api.get_video_basic_info(BVID).await.unwrap_err(), ApiError::NotFound);
+        match api.get_video_basic_info(BVID).await.unwrap_err() {
+            ApiError::Api { code, message } => {
+                assert_eq!(code, -400);
+                assert!(message.contains("请求错误"));
+            }
+            error => panic!("unexpected error: {:?}", error),
+        }
+    });
+}
+
+#[test]
+fn wbi_resign_after_signature_error() {
+    run(|api, server| async move {
+        server.route("/x/web-interface/nav", vec![MockResponse::json(NAV)]);
+        server.route(
+            "/x/player/wbi/playurl",
+            vec![
+                MockResponse::json(r#"{"code":-352,"message":"风控校验失败","ttl":1}"#),
+                MockResponse::json(PLAYURL_DASH),
+            ],
+        );
+
+        let urls = api.get_download_urls(BVID, 62131, 80, &[VideoCodec::Avc], 0).await.unwrap();
+        assert_eq!(urls.quality, 80);
+
+        // 第一次签名失败后重新获取密钥再签名，不使用不签名的地址
+        assert_eq!(server.requests("/x/web-interface/nav").len(), 2);
+        assert_eq!(server.requests("/x/player/wbi/playurl").len(), 3);
+        assert!(server.requests("/x/player/playurl").is_empty());
+    });
+}
+
+#[test]
+fn wbi_unsigned_fallback() {
+    run(|api, server| async move {
+        server.route("/x/web-interface/nav", vec![MockResponse::json(NAV)]);
+        server.route(
+            "/x/player/wbi/playurl",
+            vec![MockResponse::json(r#"{"code":-403,"message":"访问权限不足","ttl":1}"#)],
+        );
+        server.route("/x/player/playurl", vec![MockResponse::json(PLAYURL_DURL)]);
+
+        let quality = api.get_actual_quality(BVID, 62131, 80).await.unwrap();
+        assert_eq!(quality, 32);
+
+        // 重新签名仍然失败才请求不签名的地址
+        assert_eq!(server.requests("/x/web-interface/nav").len(), 2);
+        assert_eq!(server.requests("/x/player/wbi/playurl").len(), 2);
+        assert_eq!(server.requests("/x/player/playurl").len(), 1);
+    });
+}
+
+#[test]
+fn wbi_keys_persisted() {
+    let path = mock::temp_path("persisted", "json");
+    // 第二次启动时直接使用文件中的密钥，不再请求nav
+    for expected_nav in [1, 0] {
+        let path = path.clone();
+        run_with(
+            |builder| builder.wbi_keys_path(Some(path)),
+            move |api, server| async move {
+                server.route("/x/web-interface/nav", vec![MockResponse::json(NAV)]);
+                server.route("/x/player/wbi/playurl", vec![MockResponse::json(PLAYURL_DASH)]);
+
+                api.get_actual_quality(BVID, 62131, 80).await.unwrap();
+                assert_eq!(server.requests("/x/web-interface/nav").len(), expected_nav);
+                assert_eq!(server.requests("/x/player/wbi/playurl").len(), 1);
+            },
+        );
+    }
+
+    let content = std::fs::read_to_string(&path).unwrap();
+    assert!(content.contains("7cd084941338484aae1ad9425b84077c"));
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn wbi_keys_expired() {
+    let path = mock::temp_path("expired", "json");
+    std::fs::write(
+        &path,
+        r#"{"img_key":"00000000000000000000000000000000","sub_key":"00000000000000000000000000000000","fetched_at":0}"#,
+    )
+    .unwrap();
+
+    run_with(
+        |builder| builder.wbi_keys_path(Some(path.clone())),
+        |api, server| async move {
+            server.route("/x/web-interface/nav", vec![MockResponse::json(NAV)]);
+            server.route("/x/player/wbi/playurl", vec![MockResponse::json(PLAYURL_DASH)]);
+
+            api.get_actual_quality(BVID, 62131, 80).await.unwrap();
+            assert_eq!(server.requests("/x/web-interface/nav").len(), 1);
+        },
+    );
+
+    let content = std::fs::read_to_string(&path).unwrap();
+    assert!(content.contains("7cd084941338484aae1ad9425b84077c"));
+    let _ = std::fs::remove_file(&path);
+}
@@ -0,0 +1,206 @@
+// tests/mock/mod.rs
+// 本地模拟的B站接口，按路径回放录制的JSON，并记录收到的请求。
+// 也可以提供支持Range请求的文件，用于测试内置下载器
+#![allow(dead_code)] // 每个测试文件只用到其中一部分
+
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::runtime::Runtime;
+
+/// 在独立的运行时中启动模拟服务并运行测试
+pub fn run<F, Fut>(test: F)
+where
+    F: FnOnce(MockServer) -> Fut,
+    Fut: Future<Output = ()>,
+{
+    Runtime::new().unwrap().block_on(async move {
+        let server = MockServer::start().await;
+        test(server).await;
+    });
+}
+
+/// 每个测试使用单独的临时文件，先删除上次留下的
+pub fn temp_path(name: &str, extension: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("bilibili_down_{}_{}.{}", name, std::process::id(), extension));
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+#[derive(Debug, Clone)]
+pub struct MockResponse {
+    pub status: u16,
+    pub body: String,
+    pub headers: Vec<(String, String)>,
+}
+
+impl MockResponse {
+    pub fn json(body: &str) -> Self {
+        Self {
+            status: 200,
+            body: body.to_string(),
+            headers: Vec::new(),
+        }
+    }
+
+    pub fn status(status: u16, body: &str) -> Self {
+        Self {
+            status,
+            ..Self::json(body)
+        }
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+}
+
+/// 收到的请求
+#[derive(Debug, Clone)]
+pub struct RecordedRequest {
+    pub path: String,
+    pub query: HashMap<String, String>,
+    /// 头部名称为小写
+    pub headers: HashMap<String, String>,
+}
+
+/// 按Range请求返回的文件，ranged为false时模拟忽略Range、总是返回整个文件的服务器
+struct MockFile {
+    data: Vec<u8>,
+    ranged: bool,
+}
+
+/// 每个路径的响应按顺序返回，最后一个会一直重复
+pub struct MockServer {
+    pub url: String,
+    routes: Arc<Mutex<HashMap<String, Vec<MockResponse>>>>,
+    files: Arc<Mutex<HashMap<String, MockFile>>>,
+    requests: Arc<Mutex<Vec<RecordedRequest>>>,
+}
+
+impl MockServer {
+    pub async fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let routes = Arc::new(Mutex::new(HashMap::new()));
+        let files = Arc::new(Mutex::new(HashMap::new()));
+        let requests = Arc::new(Mutex::new(Vec::new()));
+
+        let (routes_handle, files_handle, requests_handle) = (routes.clone(), files.clone(), requests.clone());
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(handle(stream, routes_handle.clone(), files_handle.clone(), requests_handle.clone()));
+            }
+        });
+
+        Self { url, routes, files, requests }
+    }
+
+    pub fn file(&self, path: &str, data: Vec<u8>, ranged: bool) {
+        self.files.lock().insert(path.to_string(), MockFile { data, ranged });
+    }
+
+    pub fn route(&self, path: &str, responses: Vec<MockResponse>) {
+        self.routes.lock().insert(path.to_string(), responses);
+    }
+
+    pub fn requests(&self, path: &str) -> Vec<RecordedRequest> {
+        self.requests
+            .lock()
+            .iter()
+            .filter(|request| request.path == path)
+            .cloned()
+            .collect()
+    }
+}
+
+async fn handle(
+    mut stream: TcpStream,
+    routes: Arc<Mutex<HashMap<String, Vec<MockResponse>>>>,
+    files: Arc<Mutex<HashMap<String, MockFile>>>,
+    requests: Arc<Mutex<Vec<RecordedRequest>>>,
+) {
+    // 只有GET请求，读到头部结束即可
+    let mut buffer = Vec::new();
+    let mut chunk = [0u8; 4096];
+    while !buffer.windows(4).any(|window| window == b"\r\n\r\n") {
+        match stream.read(&mut chunk).await {
+            Ok(0) | Err(_) => return,
+            Ok(n) => buffer.extend_from_slice(&chunk[..n]),
+        }
+    }
+
+    let head = String::from_utf8_lossy(&buffer).to_string();
+    let mut lines = head.split("\r\n");
+    let target = lines
+        .next()
+        .and_then(|line| line.split_whitespace().nth(1))
+        .unwrap_or("/")
+        .to_string();
+    let (path, query) = target.split_once('?').unwrap_or((&target, ""));
+    let headers: HashMap<String, String> = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_lowercase(), value.trim().to_string()))
+        .collect();
+    let range = headers.get("range").and_then(|value| parse_range(value));
+    let query = url::form_urlencoded::parse(query.as_bytes()).into_owned().collect();
+    requests.lock().push(RecordedRequest {
+        path: path.to_string(),
+        query,
+        headers,
+    });
+
+    let file_response = files.lock().get(path).map(|file| {
+        let total = file.data.len() as u64;
+        match range.filter(|_| file.ranged) {
+            Some((start, end)) => {
+                let end = end.unwrap_or(total - 1).min(total - 1);
+                (
+                    MockResponse::status(206, "")
+                        .header("Content-Type", "application/octet-stream")
+                        .header("Content-Range", &format!("bytes {}-{}/{}", start, end, total)),
+                    file.data[start as usize..=end as usize].to_vec(),
+                )
+            }
+            None => (
+                MockResponse::status(200, "").header("Content-Type", "application/octet-stream"),
+                file.data.clone(),
+            ),
+        }
+    });
+    let (response, body) = file_response.unwrap_or_else(|| {
+        let mut routes = routes.lock();
+        let response = match routes.get_mut(path) {
+            Some(responses) if responses.len() > 1 => responses.remove(0),
+            Some(responses) if !responses.is_empty() => responses[0].clone(),
+            _ => MockResponse::status(404, r#"{"code":-404,"message":"啥都木有"}"#),
+        };
+        let body = response.body.clone().into_bytes();
+        (response.header("Content-Type", "application/json; charset=utf-8"), body)
+    });
+
+    let mut output = format!(
+        "HTTP/1.1 {} Mock\r\nContent-Length: {}\r\nConnection: close\r\n",
+        response.status,
+        body.len()
+    );
+    for (name, value) in &response.headers {
+        output.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    output.push_str("\r\n");
+    let mut output = output.into_bytes();
+    output.extend_from_slice(&body);
+    let _ = stream.write_all(&output).await;
+    let _ = stream.shutdown().await;
+}
+
+/// bytes=start-end，end可以省略
+fn parse_range(value: &str) -> Option<(u64, Option<u64>)> {
+    let (start, end) = value.strip_prefix("bytes=")?.split_once('-')?;
+    Some((start.parse().ok()?, end.parse().ok()))
+}
@@ -0,0 +1,145 @@
+// tests/native.rs
+// 内置分段下载器对接本地模拟服务的集成测试
+mod mock;
+
+use bilibili_down::backend::{DownloadBackend, JobState, NativeBackend};
+use mock::{run, MockResponse, MockServer};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// 3.5MB，按1MB分段时有4段
+const FILE_SIZE: usize = 3 * 1024 * 1024 + 512 * 1024;
+
+fn file_data() -> Vec<u8> {
+    (0..FILE_SIZE).map(|i| (i * 31 % 251) as u8).collect()
+}
+
+/// 每个测试使用单独的输出文件
+fn output_file(name: &str) -> PathBuf {
+    let path = mock::temp_path(&format!("native_{}", name), "m4s");
+    let _ = std::fs::remove_file(state_path(&path));
+    path
+}
+
+fn state_path(output: &Path) -> PathBuf {
+    let mut path = output.to_path_buf().into_os_string();
+    path.push(".bdstate");
+    PathBuf::from(path)
+}
+
+/// 添加下载并等待结束
+async fn download(backend: &NativeBackend, urls: Vec<String>, output: &Path) -> JobState {
+    let id = backend.add(urls, output.to_path_buf(), false).await.unwrap();
+    for _ in 0..300 {
+        let status = backend.status(id.clone()).await.unwrap();
+        if matches!(status.state, JobState::Complete | JobState::Error(_)) {
+            return status.state;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    panic!("download timed out");
+}
+
+/// Range请求头的起始位置
+fn range_starts(server: &MockServer, path: &str) -> Vec<u64> {
+    server
+        .requests(path)
+        .iter()
+        .filter_map(|request| request.headers.get("range"))
+        .filter_map(|range| range.strip_prefix("bytes=")?.split('-').next()?.parse().ok())
+        .collect()
+}
+
+#[test]
+fn ranged_multi_segment_download() {
+    run(|server| async move {
+        server.file("/video.m4s", file_data(), true);
+        let output = output_file("ranged");
+
+        let backend = NativeBackend::new(4);
+        let state = download(&backend, vec![format!("{}/video.m4s", server.url)], &output).await;
+        assert_eq!(state, JobState::Complete);
+        assert_eq!(std::fs::read(&output).unwrap(), file_data());
+        // 完成后删除进度文件
+        assert!(!state_path(&output).exists());
+
+        // 探测请求加4个分段
+        let mut starts = range_starts(&server, "/video.m4s");
+        starts.sort();
+        assert_eq!(starts, [0, 0, 917504, 1835008, 2752512]);
+        let _ = std::fs::remove_file(&output);
+    });
+}
+
+#[test]
+fn failing_first_mirror() {
+    run(|server| async move {
+        server.route("/bad.m4s", vec![MockResponse::status(503, "")]);
+        server.file("/good.m4s", file_data(), true);
+        let output = output_file("mirror");
+
+        let backend = NativeBackend::new(2);
+        let urls = vec![format!("{}/bad.m4s", server.url), format!("{}/good.m4s", server.url)];
+        let state = download(&backend, urls, &output).await;
+        assert_eq!(state, JobState::Complete);
+        assert_eq!(std::fs::read(&output).unwrap(), file_data());
+
+        // 探测和第一个分段都先请求了失败的镜像，之后换用下一个
+        assert_eq!(server.requests("/bad.m4s").len(), 2);
+        let _ = std::fs::remove_file(&output);
+    });
+}
+
+#[test]
+fn server_ignoring_range() {
+    run(|server| async move {
+        server.file("/video.m4s", file_data(), false);
+        let output = output_file("no_range");
+
+        let backend = NativeBackend::new(4);
+        let state = download(&backend, vec![format!("{}/video.m4s", server.url)], &output).await;
+        assert_eq!(state, JobState::Complete);
+        assert_eq!(std::fs::read(&output).unwrap(), file_data());
+
+        // 不支持Range时只用一个连接：探测加一次下载
+        assert_eq!(server.requests("/video.m4s").len(), 2);
+        let _ = std::fs::remove_file(&output);
+    });
+}
+
+#[test]
+fn resume_from_saved_state() {
+    run(|server| async move {
+        let data = file_data();
+        server.file("/video.m4s", data.clone(), true);
+        let output = output_file("resume");
+
+        // 上次下载了前半部分
+        let half = FILE_SIZE / 2;
+        let mut partial = data[..half].to_vec();
+        partial.resize(FILE_SIZE, 0);
+        std::fs::write(&output, partial).unwrap();
+        std::fs::write(
+            state_path(&output),
+            format!(
+                r#"{{"total":{},"segments":[{{"start":0,"end":{},"downloaded":{}}},{{"start":{},"end":{},"downloaded":0}}]}}"#,
+                FILE_SIZE,
+                half - 1,
+                half,
+                half,
+                FILE_SIZE - 1
+            ),
+        )
+        .unwrap();
+
+        let backend = NativeBackend::new(2);
+        let state = download(&backend, vec![format!("{}/video.m4s", server.url)], &output).await;
+        assert_eq!(state, JobState::Complete);
+        assert_eq!(std::fs::read(&output).unwrap(), data);
+        assert!(!state_path(&output).exists());
+
+        // 只有探测请求和后半部分的分段
+        assert_eq!(range_starts(&server, "/video.m4s"), [0, half as u64]);
+        let _ = std::fs::remove_file(&output);
+    });
+}